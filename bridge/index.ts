@@ -1,28 +1,201 @@
 import Fastify from "fastify";
+import { existsSync, readFileSync, renameSync, writeFileSync } from "node:fs";
 
-const QS_API = process.env.QS_API as string; // e.g. https://…shuttleapp.rs
+const QS_WALLETD = process.env.QS_WALLETD_URL || "http://127.0.0.1:8787";
+const SOLANA_RPC = process.env.QS_SOLANA_RPC_URL || "http://127.0.0.1:8899";
+// NOVA intents are paid from this qs-walletd wallet; without all three the
+// bridge refuses intents rather than using them up and doing nothing.
+const NOVA_MINT = process.env.QS_NOVA_MINT;
+const BRIDGE_WALLET = process.env.QS_BRIDGE_WALLET;
+const BRIDGE_PASSWORD = process.env.QS_BRIDGE_PASSWORD;
+// Receipts and transfers not yet sent survive restarts in this file.
+const STATE_FILE = process.env.QS_BRIDGE_STATE || ".bridge-state.json";
 const fastify = Fastify({ logger: true });
 
+type SignedTransfer = { signature: string; from: string; destination_account: string; transaction_base64: string };
+type Receipt = { nonce: string; dest_chain: string; dest_address: string; token: string; amount: string };
+type Outcome = { code: number; body: unknown };
+type Answered = { at: number; code: number; body: unknown };
+type Unsent = { at: number; signed: SignedTransfer; receipt: Receipt };
+
+// Accepted intents by idempotency key, so a client retrying after a lost
+// response gets its receipt back instead of "nonce already used". Only
+// successes are kept (an hour, as long as an intent can be valid): anyone
+// can send any key, but only the real intent gets past verification.
+const IDEMPOTENCY_TTL_MS = 60 * 60 * 1000;
+const answered = new Map<string, Answered>();
+
+// Intents already used up whose transaction may not have reached the chain,
+// by source and nonce. Sending one again resends the same signed
+// transaction (the node ignores a second copy) instead of failing as
+// replayed.
+const unsent = new Map<string, Unsent>();
+
+function loadState() {
+  if (!existsSync(STATE_FILE)) return;
+  const state = JSON.parse(readFileSync(STATE_FILE, "utf8"));
+  for (const [k, v] of Object.entries(state.answered ?? {})) answered.set(k, v as Answered);
+  for (const [k, v] of Object.entries(state.unsent ?? {})) unsent.set(k, v as Unsent);
+}
+
+/** Written before answering, so what a client was told survives a crash. */
+function saveState() {
+  const tmp = `${STATE_FILE}.tmp`;
+  const state = { answered: Object.fromEntries(answered), unsent: Object.fromEntries(unsent) };
+  writeFileSync(tmp, JSON.stringify(state), { mode: 0o600 });
+  renameSync(tmp, STATE_FILE);
+}
+
+loadState();
+
 fastify.post("/bridge", async (req, reply) => {
-  const { public_key, message, signature, amount, token } = (req.body as any) || {};
-  if (!public_key || !message || !signature) {
-    return reply.code(400).send({ ok: false, error: "missing fields" });
+  const key = req.headers["idempotency-key"];
+  const now = Date.now();
+  for (const [k, v] of answered) if (now - v.at > IDEMPOTENCY_TTL_MS) answered.delete(k);
+  for (const [k, v] of unsent) if (now - v.at > IDEMPOTENCY_TTL_MS) unsent.delete(k);
+  const seen = typeof key === "string" ? answered.get(key) : undefined;
+  if (seen) {
+    return reply.code(seen.code).send(seen.body);
   }
+  const { code, body } = await handleIntent(req);
+  if (typeof key === "string" && code === 200) {
+    answered.set(key, { at: now, code, body });
+    saveState();
+  }
+  return reply.code(code).send(body);
+});
 
-  // verify QS signature first
-  const ver = await fetch(`${QS_API}/verify/${encodeURIComponent(message)}/${encodeURIComponent(signature)}`);
-  const vr = await ver.json();
+class Upstream extends Error {
+  constructor(readonly code: number, message: string) { super(message); }
+}
 
-  if (!vr?.verified) {
-    return reply.code(401).send({ ok: false, error: "invalid QS signature" });
+async function post(service: string, url: string, body: unknown): Promise<any> {
+  let res: Response;
+  try {
+    res = await fetch(url, { method: "POST", headers: { "content-type": "application/json" }, body: JSON.stringify(body) });
+  } catch (e) {
+    throw new Upstream(502, `${service} unavailable: ${e}`);
+  }
+  if (!res.ok) {
+    // refusals (bad password, policy) are the caller's to see; anything else is ours
+    throw new Upstream(res.status === 403 ? 403 : 502, `${service} answered ${res.status}: ${await res.text()}`);
   }
+  return res.json();
+}
 
-  // TODO: perform Solana/EVM action here (server wallet or program)
-  // For now, just log accepted intent:
-  req.log.info({ public_key, amount, token }, "Bridge verified. Proceeding with transfer…");
+async function rpc(method: string, params: unknown[]): Promise<any> {
+  const res = await post("solana-rpc", SOLANA_RPC, { jsonrpc: "2.0", id: 1, method, params });
+  if (res.error) throw new Upstream(502, `solana rpc error ${res.error.code}: ${res.error.message}`);
+  return res.result;
+}
 
-  return reply.send({ ok: true, verified: true });
-});
+/** Asks qs-walletd to check or verify an intent; its refusals become 401s. */
+async function judge(path: "check" | "verify", signedIntent: unknown): Promise<any> {
+  let res: Response;
+  try {
+    res = await fetch(`${QS_WALLETD}/bridge-intents/${path}`, {
+      method: "POST",
+      headers: { "content-type": "application/json" },
+      body: JSON.stringify(signedIntent),
+    });
+  } catch (e) {
+    throw new Upstream(502, `qs-walletd unavailable: ${e}`);
+  }
+  if (res.status >= 500) throw new Upstream(502, "qs-walletd unavailable");
+  if (!res.ok) throw new Upstream(401, await res.text());
+  return (await res.json()).intent;
+}
+
+/** Digits after the point: 2 for "1.50", 0 for "3". */
+function scale(amount: string): number {
+  const point = amount.indexOf(".");
+  return point < 0 ? 0 : amount.length - point - 1;
+}
+
+async function send(signed: SignedTransfer): Promise<void> {
+  const signature = await rpc("sendTransaction", [
+    signed.transaction_base64,
+    { encoding: "base64", preflightCommitment: "confirmed" },
+  ]);
+  if (signature !== signed.signature) {
+    throw new Upstream(502, `node reported transaction ${signature}, expected ${signed.signature}`);
+  }
+}
+
+async function handleIntent(req: any): Promise<Outcome> {
+  // a SignedIntent, as returned by qs-walletd for POST /wallets/:name/bridge-intent
+  const signedIntent = req.body || {};
+  const claimed = signedIntent.intent;
+  if (!claimed || !signedIntent.public_key_hex || !signedIntent.signed_hex) {
+    return { code: 400, body: { ok: false, error: "expected a signed bridge intent" } };
+  }
+  if (!NOVA_MINT || !BRIDGE_WALLET || !BRIDGE_PASSWORD) {
+    return { code: 503, body: { ok: false, error: "bridge is not configured (QS_NOVA_MINT, QS_BRIDGE_WALLET, QS_BRIDGE_PASSWORD)" } };
+  }
+  if (claimed.dest_chain !== "solana" || claimed.token !== NOVA_MINT) {
+    return { code: 422, body: { ok: false, error: `intents for ${claimed.token} on ${claimed.dest_chain} are not supported` } };
+  }
+  try {
+    const slot = `${claimed.source}:${claimed.nonce}`;
+    const pending = unsent.get(slot);
+    if (pending) return await finish(req, slot, pending.signed, pending.receipt);
+
+    // signature, expiry and replay, without using the intent up: a bad one
+    // never gets the bridge wallet to sign (or spend its policy quota)
+    const checked = await judge("check", signedIntent);
+    const { value: supply } = await rpc("getTokenSupply", [NOVA_MINT]);
+    // the signature covers the amount's scale, so only one spelling of a
+    // value is accepted: the mint's ("1.500000" for 6 decimals, not "1.5")
+    const amount = String(checked.amount);
+    if (scale(amount) !== supply.decimals) {
+      return { code: 422, body: { ok: false, error: `amount ${amount} must have exactly ${supply.decimals} decimals` } };
+    }
+    // sign before using the intent up: if signing fails the intent is still usable
+    const { value: latest } = await rpc("getLatestBlockhash", [{ commitment: "confirmed" }]);
+    const signed: SignedTransfer = await post("qs-walletd", `${QS_WALLETD}/wallets/${encodeURIComponent(BRIDGE_WALLET)}/solana/spl-transfer`, {
+      password: BRIDGE_PASSWORD,
+      recent_blockhash: latest.blockhash,
+      mint: NOVA_MINT,
+      to: checked.dest_address,
+      amount,
+      create_recipient_account: true,
+    });
+
+    const intent = await judge("verify", signedIntent);
+    if (intent.source !== checked.source || intent.nonce !== checked.nonce || intent.dest_address !== checked.dest_address
+        || intent.token !== checked.token || intent.amount !== checked.amount) {
+      return { code: 502, body: { ok: false, error: "qs-walletd verified a different intent" } };
+    }
+    const receipt: Receipt = {
+      nonce: intent.nonce,
+      dest_chain: intent.dest_chain,
+      dest_address: intent.dest_address,
+      token: intent.token,
+      amount: intent.amount,
+    };
+    unsent.set(slot, { at: Date.now(), signed, receipt });
+    saveState();
+    return await finish(req, slot, signed, receipt);
+  } catch (e) {
+    if (e instanceof Upstream) return { code: e.code, body: { ok: false, error: e.message } };
+    throw e;
+  }
+}
+
+async function finish(req: any, slot: string, signed: SignedTransfer, receipt: Receipt): Promise<Outcome> {
+  try {
+    await send(signed);
+  } catch (e) {
+    req.log.error({ nonce: receipt.nonce, signature: signed.signature, err: String(e) }, "bridge transfer not sent");
+    const error = e instanceof Upstream ? e.message : String(e);
+    // 503: the intent is used up, but sending it again resends this transaction
+    return { code: 503, body: { ok: false, error, signature: signed.signature } };
+  }
+  unsent.delete(slot);
+  saveState();
+  req.log.info({ ...receipt, signature: signed.signature }, "bridge transfer sent");
+  return { code: 200, body: { ok: true, verified: true, ...receipt, signature: signed.signature } };
+}
 
 fastify.listen({ port: Number(process.env.PORT) || 3001, host: "0.0.0.0" })
   .catch((e) => {
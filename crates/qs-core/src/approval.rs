@@ -0,0 +1,413 @@
+//! Human approval for signing with protected wallets.
+//!
+//! A protected wallet never signs on request. The request is checked (right
+//! wallet password, valid domain), queued with a description, and signed only
+//! when someone approves it with the approver password, a second credential
+//! set when protection is turned on. The wallet's key is sealed under that
+//! password in the approvals store, so approving never needs the wallet
+//! password and knowing the wallet password is not enough to sign. Pending
+//! requests that nobody decides on expire. Neither password is stored.
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{collections::BTreeMap, path::PathBuf, sync::Mutex};
+
+use qs_crypto::{Domain, SealedBox, keypair_matches, open_with_password, seal_with_password, sign_domain};
+use qs_utils::{ensure_wallet_dir, read_json, wallet_dir, write_json};
+
+use crate::{
+    audit, check_domain, check_signable, invalid, io, load_keyfile, now_secs, password, policy, throttle, unlock,
+    CoreError, Result,
+};
+
+/// How long a request stays pending unless the wallet sets its own TTL.
+pub const DEFAULT_TTL_SECS: u64 = 15 * 60;
+pub const MAX_DESCRIPTION_LEN: usize = 1024;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalState { Pending, Approved, Rejected, Expired }
+
+impl ApprovalState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApprovalState::Pending  => "pending",
+            ApprovalState::Approved => "approved",
+            ApprovalState::Rejected => "rejected",
+            ApprovalState::Expired  => "expired",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SignRequest {
+    pub id: String,
+    pub wallet: String,
+    pub description: String,
+    pub domain: Domain,
+    pub message_hex: String,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub state: ApprovalState,
+    pub decided_at: Option<u64>,
+    pub signed_hex: Option<String>,
+    pub reject_reason: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Protection { pub ttl_secs: u64 }
+
+#[derive(Serialize, Deserialize, Default)]
+struct Store {
+    protected: BTreeMap<String, Protection>,
+    /// Each protected wallet's secret key, sealed under its approver password.
+    #[serde(default)]
+    approvers: BTreeMap<String, SealedBox>,
+    requests: BTreeMap<String, SignRequest>,
+}
+
+fn store_path() -> PathBuf {
+    wallet_dir().join(".approvals.json")
+}
+
+fn load() -> Result<Store> {
+    let path = store_path();
+    if !path.exists() {
+        return Ok(Store::default());
+    }
+    read_json(path).map_err(io)
+}
+
+fn save(store: &Store) -> Result<()> {
+    ensure_wallet_dir().map_err(io)?;
+    write_json(store_path(), store).map_err(io)
+}
+
+fn new_id() -> String {
+    use rand::RngCore;
+    let mut id = [0u8; 16];
+    rand::rngs::OsRng.fill_bytes(&mut id);
+    hex::encode(id)
+}
+
+/// Marks pending requests past their deadline as expired; returns those.
+fn expire(store: &mut Store, now: u64) -> Vec<SignRequest> {
+    let mut out = Vec::new();
+    for r in store.requests.values_mut() {
+        if r.state == ApprovalState::Pending && r.expires_at <= now {
+            r.state = ApprovalState::Expired;
+            r.decided_at = Some(now);
+            out.push(r.clone());
+        }
+    }
+    out
+}
+
+pub fn protection(name: &str) -> Result<Option<Protection>> {
+    let _guard = LOCK.lock().map_err(io)?;
+    Ok(load()?.protected.get(name).copied())
+}
+
+pub fn requires_approval(name: &str) -> Result<bool> {
+    Ok(protection(name)?.is_some())
+}
+
+/// Binds a sealed key to the wallet name and public key it was sealed for.
+fn approver_aad(name: &str, public_hex: &str) -> Vec<u8> {
+    format!("QuantumShield approver\n{name}\n{public_hex}").into_bytes()
+}
+
+/// Turns approval mode on (with `ttl_secs`). Needs the wallet password, and
+/// sets `approver_password`, which must pass the [`password`] rules and
+/// differ from the wallet password. Refused while an approver password is
+/// set: the wallet password alone must not replace it, so protection has to
+/// be switched off (with the approver password) before it is set up anew.
+pub fn enable_protection(name: &str, password: &str, approver_password: &str, ttl_secs: Option<u64>) -> Result<Protection> {
+    if approver_password == password {
+        return Err(invalid("the approver password must differ from the wallet password"));
+    }
+    password::check(name, approver_password)?;
+    check_no_approver(name, &load_locked()?)?;
+    let (ek, secret) = unlock(name, password)?;
+    let sealed = seal_with_password(&secret, approver_password, &approver_aad(name, &ek.public_hex));
+    let protection = Protection { ttl_secs: ttl_secs.unwrap_or(DEFAULT_TTL_SECS).max(1) };
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    // again under the lock: another call may have set one during the KDF
+    check_no_approver(name, &store)?;
+    store.protected.insert(name.to_string(), protection);
+    store.approvers.insert(name.to_string(), sealed);
+    save(&store)?;
+    audit::log(Some(name), "protect", "ok", json!({ "ttl_secs": protection.ttl_secs }))?;
+    Ok(protection)
+}
+
+fn load_locked() -> Result<Store> {
+    let _guard = LOCK.lock().map_err(io)?;
+    load()
+}
+
+fn check_no_approver(name: &str, store: &Store) -> Result<()> {
+    if store.approvers.contains_key(name) {
+        return Err(CoreError::Invalid(format!(
+            "wallet `{name}` is already protected; turn protection off with the approver password first"
+        )));
+    }
+    Ok(())
+}
+
+/// Turns approval mode off. Needs the approver password, so a caller with
+/// only the wallet password cannot switch protection off.
+pub fn disable_protection(name: &str, approver_password: &str) -> Result<()> {
+    unseal_approver_key(name, approver_password)?;
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    store.protected.remove(name);
+    store.approvers.remove(name);
+    save(&store)?;
+    audit::log(Some(name), "unprotect", "ok", serde_json::Value::Null)
+}
+
+/// The wallet's secret key, opened with the approver password, if it still
+/// is the wallet's key.
+fn open_approver_key(name: &str, approver_password: &str) -> Result<Vec<u8>> {
+    let (public_hex, secret) = unseal_approver_key(name, approver_password)?;
+    let public = hex::decode(&public_hex).map_err(invalid)?;
+    if !keypair_matches(&public, &secret) {
+        return Err(CoreError::Invalid(format!("wallet `{name}` changed keys since protection was set; turn it off and on again")));
+    }
+    Ok(secret)
+}
+
+/// Opens the sealed key with the approver password, returning the wallet's
+/// public key with it. Failures count towards the wallet's unlock throttle
+/// like wrong wallet passwords.
+fn unseal_approver_key(name: &str, approver_password: &str) -> Result<(String, Vec<u8>)> {
+    let sealed = load_locked()?.approvers.remove(name);
+    let sealed = sealed.ok_or_else(|| CoreError::Invalid(format!(
+        "wallet `{name}` has no approver password; turn protection on again to set one"
+    )))?;
+    let ek = load_keyfile(name)?;
+    throttle::begin(name)?;
+    let Some(secret) = open_with_password(&sealed, approver_password, &approver_aad(name, &ek.public_hex)) else {
+        audit::log(Some(name), "approver_unlock", "bad_password", serde_json::Value::Null)?;
+        return Err(CoreError::BadPassword);
+    };
+    throttle::succeeded(name)?;
+    Ok((ek.public_hex, secret))
+}
+
+/// Queues a sign request for protected wallet `name`. The password is checked
+/// now so the queue only holds requests from someone who knows it.
+pub fn submit(name: &str, password: &str, domain: &Domain, message: &[u8], description: &str) -> Result<SignRequest> {
+    check_signable(domain)?;
+    if description.len() > MAX_DESCRIPTION_LEN {
+        return Err(invalid(format!("description longer than {MAX_DESCRIPTION_LEN} bytes")));
+    }
+    unlock(name, password)?;
+    // refuse early what the policy would refuse at approval time anyway
+    policy::check(name, &policy::SignContext::raw(domain, message))?;
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    let ttl = store.protected.get(name).map_or(DEFAULT_TTL_SECS, |p| p.ttl_secs);
+    let now = now_secs();
+    let request = SignRequest {
+        id: new_id(),
+        wallet: name.to_string(),
+        description: description.to_string(),
+        domain: domain.clone(),
+        message_hex: hex::encode(message),
+        created_at: now,
+        expires_at: now + ttl,
+        state: ApprovalState::Pending,
+        decided_at: None,
+        signed_hex: None,
+        reject_reason: None,
+    };
+    store.requests.insert(request.id.clone(), request.clone());
+    save(&store)?;
+    Ok(request)
+}
+
+/// Requests, newest first, optionally only those in `state`.
+pub fn list(state: Option<ApprovalState>) -> Result<Vec<SignRequest>> {
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut out: Vec<SignRequest> = load()?.requests.into_values()
+        .map(|mut r| {
+            // report overdue requests as expired even before the sweep saves it
+            if r.state == ApprovalState::Pending && r.expires_at <= now_secs() { r.state = ApprovalState::Expired; }
+            r
+        })
+        .filter(|r| state.is_none_or(|s| r.state == s))
+        .collect();
+    out.sort_by_key(|r| std::cmp::Reverse(r.created_at));
+    Ok(out)
+}
+
+pub fn get(id: &str) -> Result<SignRequest> {
+    list(None)?.into_iter().find(|r| r.id == id).ok_or_else(|| CoreError::UnknownRequest(id.to_string()))
+}
+
+fn decide(id: &str, f: impl FnOnce(&mut SignRequest) -> Result<()>) -> Result<SignRequest> {
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    let now = now_secs();
+    expire(&mut store, now);
+    let request = store.requests.get_mut(id).ok_or_else(|| CoreError::UnknownRequest(id.to_string()))?;
+    if request.state != ApprovalState::Pending {
+        let state = request.state;
+        save(&store)?;
+        return Err(CoreError::Invalid(format!("sign request `{id}` is {}, not pending", state.as_str())));
+    }
+    f(request)?;
+    request.decided_at = Some(now);
+    let request = request.clone();
+    save(&store)?;
+    Ok(request)
+}
+
+/// Signs a pending request with the wallet's approver password. The
+/// approvals store is not locked while the key is opened and used; the
+/// request must still be pending when the signature is recorded.
+pub fn approve(id: &str, approver_password: &str) -> Result<SignRequest> {
+    let pending = get(id)?;
+    if pending.state != ApprovalState::Pending {
+        return Err(CoreError::Invalid(format!("sign request `{id}` is {}, not pending", pending.state.as_str())));
+    }
+    let name = &pending.wallet;
+    check_domain(&pending.domain)?;
+    let message = hex::decode(&pending.message_hex).map_err(invalid)?;
+    let secret = open_approver_key(name, approver_password)?;
+    policy::enforce(name, &policy::SignContext::raw(&pending.domain, &message))?;
+    let signed = sign_domain(&secret, &pending.domain, &message).ok_or_else(|| invalid("signing failed"))?;
+    let request = decide(id, |r| {
+        r.state = ApprovalState::Approved;
+        r.signed_hex = Some(hex::encode(&signed));
+        Ok(())
+    })?;
+    audit::log(Some(name), "sign", "ok", json!({
+        "domain": pending.domain, "message_len": message.len(), "approval": id,
+    }))?;
+    Ok(request)
+}
+
+pub fn reject(id: &str, reason: Option<String>) -> Result<SignRequest> {
+    decide(id, |r| {
+        r.state = ApprovalState::Rejected;
+        r.reject_reason = reason;
+        Ok(())
+    })
+}
+
+/// Persists expiry of overdue requests and returns the ones that just expired.
+pub fn expire_due() -> Result<Vec<SignRequest>> {
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    let expired = expire(&mut store, now_secs());
+    if !expired.is_empty() {
+        save(&store)?;
+    }
+    Ok(expired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, PASSWORD};
+
+    const APPROVER: &str = "Saffron-Beacon-Tundra-31";
+
+    fn protected(prefix: &str) -> String {
+        let name = testing::wallet(prefix);
+        enable_protection(&name, PASSWORD, APPROVER, None).unwrap();
+        name
+    }
+
+    #[test]
+    fn approver_password_is_separate() {
+        let name = testing::wallet("approval");
+        assert!(enable_protection(&name, PASSWORD, PASSWORD, None).is_err());
+        assert!(matches!(enable_protection(&name, PASSWORD, "short", None), Err(CoreError::WeakPassword(_))));
+        assert!(matches!(enable_protection(&name, "wrong password", APPROVER, None), Err(CoreError::BadPassword)));
+        assert!(!requires_approval(&name).unwrap());
+
+        enable_protection(&name, PASSWORD, APPROVER, Some(60)).unwrap();
+        assert_eq!(protection(&name).unwrap().map(|p| p.ttl_secs), Some(60));
+        // the wallet password alone can neither approve nor switch protection off
+        assert!(matches!(disable_protection(&name, PASSWORD), Err(CoreError::BadPassword)));
+        let domain = Domain::new("test", "approval");
+        let request = submit(&name, PASSWORD, &domain, b"pay", "payment").unwrap();
+        assert!(matches!(approve(&request.id, PASSWORD), Err(CoreError::BadPassword)));
+        assert_eq!(get(&request.id).unwrap().state, ApprovalState::Pending);
+
+        disable_protection(&name, APPROVER).unwrap();
+        assert!(!requires_approval(&name).unwrap());
+    }
+
+    #[test]
+    fn wallet_password_cannot_replace_the_approver_password() {
+        const MINE: &str = "Cobalt-Harbor-Meadow-58";
+        let name = testing::wallet("approval");
+        enable_protection(&name, PASSWORD, APPROVER, Some(60)).unwrap();
+        let request = submit(&name, PASSWORD, &Domain::new("test", "approval"), b"pay", "payment").unwrap();
+
+        let err = enable_protection(&name, PASSWORD, MINE, Some(3600)).unwrap_err();
+        assert!(err.to_string().contains("already protected"), "{err}");
+        assert_eq!(protection(&name).unwrap().map(|p| p.ttl_secs), Some(60));
+        assert!(matches!(approve(&request.id, MINE), Err(CoreError::BadPassword)));
+        assert_eq!(get(&request.id).unwrap().state, ApprovalState::Pending);
+
+        // switched off by the approver, it can be set up anew
+        disable_protection(&name, APPROVER).unwrap();
+        enable_protection(&name, PASSWORD, MINE, None).unwrap();
+        assert!(requires_approval(&name).unwrap());
+    }
+
+    #[test]
+    fn approve_signs_once() {
+        let name = protected("approval");
+        let domain = Domain::new("test", "approval");
+        assert!(matches!(crate::sign(&name, PASSWORD, &domain, b"pay"), Err(CoreError::ApprovalRequired(_))));
+        assert!(matches!(submit(&name, "wrong password", &domain, b"pay", ""), Err(CoreError::BadPassword)));
+        assert!(submit(&name, PASSWORD, &qs_crypto::rotation_domain(), b"pay", "").is_err());
+
+        let request = submit(&name, PASSWORD, &domain, b"pay", "payment").unwrap();
+        let approved = approve(&request.id, APPROVER).unwrap();
+        assert_eq!(approved.state, ApprovalState::Approved);
+        let signed = hex::decode(approved.signed_hex.unwrap()).unwrap();
+        assert_eq!(crate::verify(&name, &domain, &signed).unwrap().as_deref(), Some(&b"pay"[..]));
+
+        assert!(approve(&request.id, APPROVER).is_err());
+        assert!(reject(&request.id, None).is_err());
+        assert!(matches!(approve("no-such-request", APPROVER), Err(CoreError::UnknownRequest(_))));
+    }
+
+    #[test]
+    fn rejected_and_expired_requests_stay_unsigned() {
+        let name = testing::wallet("approval");
+        enable_protection(&name, PASSWORD, APPROVER, Some(1)).unwrap();
+        let domain = Domain::new("test", "approval");
+        let rejected = submit(&name, PASSWORD, &domain, b"a", "").unwrap();
+        assert_eq!(reject(&rejected.id, Some("no".into())).unwrap().state, ApprovalState::Rejected);
+        assert!(approve(&rejected.id, APPROVER).is_err());
+
+        let overdue = submit(&name, PASSWORD, &domain, b"b", "").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(1100));
+        assert_eq!(get(&overdue.id).unwrap().state, ApprovalState::Expired);
+        assert!(approve(&overdue.id, APPROVER).is_err());
+        assert!(expire_due().unwrap().iter().all(|r| r.state == ApprovalState::Expired));
+        assert!(get(&overdue.id).unwrap().signed_hex.is_none());
+    }
+
+    #[test]
+    fn protected_wallets_keep_their_key_to_themselves() {
+        let name = protected("approval");
+        let other = crate::public_key(&testing::wallet("other")).unwrap();
+        let approval_required = |r: Result<()>| matches!(r, Err(CoreError::ApprovalRequired(_)));
+        assert!(approval_required(crate::rotation::rotate(&name, PASSWORD, None).map(drop)));
+        assert!(approval_required(crate::revocation::revoke_with_wallet(&name, PASSWORD, None, "").map(drop)));
+        assert!(approval_required(crate::revocation::designate_wallet_recovery(&name, PASSWORD, &other).map(drop)));
+        assert!(approval_required(crate::qskey::export(&name, PASSWORD, APPROVER).map(drop)));
+    }
+}
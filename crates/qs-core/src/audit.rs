@@ -0,0 +1,505 @@
+//! Append-only, hash-chained audit log of key operations, one JSON entry per
+//! line in `.audit.jsonl` in the wallet directory.
+//!
+//! Each entry carries the hash of the one before it (see
+//! [`qs_crypto::audit_entry_hash`]). Every [`CHECKPOINT_EVERY`] entries, and
+//! whenever [`checkpoint`] is called, a `checkpoint` entry signs the chain
+//! head with the daemon's audit key. The key is sealed under a passphrase in
+//! `.audit-key.json` and opened once with [`unlock_key`]; the public key is
+//! then taken from memory, never re-read from disk, so swapping the file
+//! does not change what checkpoints are signed or verified with. Until the
+//! key is unlocked entries are chained but not signed. Entries after the last
+//! checkpoint can be cut off without trace; everything before it cannot be
+//! changed.
+//!
+//! Writing an entry is part of the operation it records: if the log cannot be
+//! written (or its last line is damaged) the operation fails.
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use qs_crypto::{
+    AUDIT_GENESIS, SCHEME, SealedBox, address_from_pubkey, audit_entry_hash, generate_dilithium3, keypair_matches,
+    open_with_password, seal_with_password, sign_checkpoint, verify_checkpoint,
+};
+use qs_utils::{ensure_wallet_dir, read_json, wallet_dir, write_json};
+
+use crate::{invalid, io, now_secs, CoreError, Result};
+
+/// A checkpoint is appended automatically after this many unsigned entries.
+pub const CHECKPOINT_EVERY: u64 = 64;
+pub const CHECKPOINT_ACTION: &str = "checkpoint";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuditEvent {
+    pub at: u64,
+    pub wallet: Option<String>,
+    pub action: String,  // e.g. "sign"
+    pub outcome: String, // e.g. "denied"
+    pub detail: Value,
+}
+
+impl AuditEvent {
+    pub fn new(wallet: Option<&str>, action: &str, outcome: &str, detail: Value) -> Self {
+        AuditEvent { at: now_secs(), wallet: wallet.map(str::to_string), action: action.to_string(), outcome: outcome.to_string(), detail }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AuditEntry {
+    pub seq: u64,
+    pub prev_hash: String,
+    pub event: AuditEvent,
+    pub hash: String,
+}
+
+impl AuditEntry {
+    fn chain(seq: u64, prev_hash: String, event: AuditEvent) -> Result<Self> {
+        let hash = audit_entry_hash(seq, &prev_hash, &serde_json::to_vec(&event).map_err(io)?);
+        Ok(AuditEntry { seq, prev_hash, event, hash })
+    }
+
+    fn hash_matches(&self) -> bool {
+        serde_json::to_vec(&self.event).is_ok_and(|body| audit_entry_hash(self.seq, &self.prev_hash, &body) == self.hash)
+    }
+}
+
+/// Result of checking a log from its first entry to its last.
+#[derive(Serialize, Clone, Debug)]
+pub struct AuditReport {
+    pub entries: u64,
+    pub head_hash: String,
+    pub checkpoints: u64,
+    pub signer_public_hex: Option<String>,
+    pub last_checkpoint_seq: Option<u64>,
+    /// Entries after the last checkpoint; these are chained but not yet signed.
+    pub unsigned_tail: u64,
+}
+
+/// `secret_hex` is how keys were stored before they were sealed; such a file
+/// is sealed the first time it is unlocked.
+#[derive(Serialize, Deserialize)]
+struct AuditKey {
+    scheme: String,
+    public_hex: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sealed: Option<SealedBox>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secret_hex: Option<String>,
+    created_at: u64,
+}
+
+struct Signer { public: Vec<u8>, secret: Vec<u8> }
+
+/// Where the next entry goes, cached so appending does not re-read the log.
+struct Head { path: PathBuf, seq: u64, hash: String, since_checkpoint: u64 }
+
+static HEAD: Mutex<Option<Head>> = Mutex::new(None);
+static SIGNER: Mutex<Option<Signer>> = Mutex::new(None);
+
+pub fn log_path() -> PathBuf {
+    wallet_dir().join(".audit.jsonl")
+}
+
+fn key_path() -> PathBuf {
+    wallet_dir().join(".audit-key.json")
+}
+
+fn parse_line(line: &str) -> Result<AuditEntry> {
+    serde_json::from_str(line).map_err(|e| CoreError::Tampered(format!("audit log line is not an entry: {e}")))
+}
+
+fn read_head(path: &Path) -> Result<Head> {
+    let mut head = Head { path: path.to_path_buf(), seq: 0, hash: AUDIT_GENESIS.to_string(), since_checkpoint: 0 };
+    if !path.exists() {
+        return Ok(head);
+    }
+    let file = fs::File::open(path).map_err(io)?;
+    let mut last = None;
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(io)?;
+        if line.trim().is_empty() { continue; }
+        let is_checkpoint = serde_json::from_str::<AuditEntry>(&line).is_ok_and(|e| e.event.action == CHECKPOINT_ACTION);
+        head.since_checkpoint = if is_checkpoint { 0 } else { head.since_checkpoint + 1 };
+        last = Some(line);
+    }
+    if let Some(line) = last {
+        let entry = parse_line(&line)?;
+        if !entry.hash_matches() {
+            return Err(CoreError::Tampered(format!("last audit entry ({}) does not match its hash", entry.seq)));
+        }
+        head.seq = entry.seq + 1;
+        head.hash = entry.hash;
+    }
+    Ok(head)
+}
+
+fn key_aad(public_hex: &str) -> Vec<u8> {
+    format!("qs-audit-key|{public_hex}").into_bytes()
+}
+
+fn write_key(path: &Path, key: &AuditKey) -> Result<()> {
+    write_json(path, key).map_err(io)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600)).map_err(io)?;
+    }
+    Ok(())
+}
+
+/// Opens the audit key (creating it on first use) with `passphrase` and keeps
+/// it in memory for checkpoints. Returns the public key.
+pub fn unlock_key(passphrase: &str) -> Result<Vec<u8>> {
+    if passphrase.is_empty() {
+        return Err(invalid("the audit key passphrase is empty"));
+    }
+    let mut signer = SIGNER.lock().map_err(io)?;
+    let path = key_path();
+    let (public, secret) = if path.exists() {
+        let mut key: AuditKey = read_json(&path).map_err(io)?;
+        if key.scheme != SCHEME {
+            return Err(CoreError::Invalid(format!("audit key uses scheme `{}`, this build signs with `{SCHEME}`", key.scheme)));
+        }
+        let public = hex::decode(&key.public_hex).map_err(invalid)?;
+        let secret = match (&key.sealed, &key.secret_hex) {
+            (Some(sealed), _) => open_with_password(sealed, passphrase, &key_aad(&key.public_hex)).ok_or(CoreError::BadPassword)?,
+            (None, Some(secret_hex)) => hex::decode(secret_hex).map_err(invalid)?,
+            (None, None) => return Err(CoreError::Tampered("audit key file has no secret".into())),
+        };
+        if !keypair_matches(&public, &secret) {
+            return Err(CoreError::Tampered("audit key secret does not match its public key".into()));
+        }
+        if key.sealed.is_none() {
+            key.sealed = Some(seal_with_password(&secret, passphrase, &key_aad(&key.public_hex)));
+            key.secret_hex = None;
+            write_key(&path, &key)?;
+        }
+        (public, secret)
+    } else {
+        ensure_wallet_dir().map_err(io)?;
+        let kp = generate_dilithium3();
+        let public_hex = hex::encode(&kp.public);
+        let sealed = Some(seal_with_password(&kp.secret, passphrase, &key_aad(&public_hex)));
+        write_key(&path, &AuditKey { scheme: SCHEME.to_string(), public_hex, sealed, secret_hex: None, created_at: now_secs() })?;
+        (kp.public, kp.secret)
+    };
+    *signer = Some(Signer { public: public.clone(), secret });
+    Ok(public)
+}
+
+/// Whether `.audit-key.json` still holds its secret in the clear.
+pub fn key_is_unsealed() -> Result<bool> {
+    let path = key_path();
+    if !path.exists() {
+        return Ok(false);
+    }
+    let key: AuditKey = read_json(&path).map_err(io)?;
+    Ok(key.sealed.is_none())
+}
+
+fn locked() -> CoreError {
+    CoreError::Invalid("the audit key is locked; unlock it with its passphrase first".into())
+}
+
+/// The unlocked audit public key.
+pub fn public_key() -> Result<Vec<u8>> {
+    let signer = SIGNER.lock().map_err(io)?;
+    signer.as_ref().map(|s| s.public.clone()).ok_or_else(locked)
+}
+
+fn append(head: &mut Head, event: AuditEvent) -> Result<AuditEntry> {
+    let checkpoint = event.action == CHECKPOINT_ACTION;
+    let entry = AuditEntry::chain(head.seq, head.hash.clone(), event)?;
+    let mut line = serde_json::to_vec(&entry).map_err(io)?;
+    line.push(b'\n');
+    let mut file = OpenOptions::new().create(true).append(true).open(&head.path).map_err(io)?;
+    file.write_all(&line).map_err(io)?;
+    head.seq += 1;
+    head.hash = entry.hash.clone();
+    head.since_checkpoint = if checkpoint { 0 } else { head.since_checkpoint + 1 };
+    Ok(entry)
+}
+
+fn append_checkpoint(head: &mut Head, signer: &Signer) -> Result<AuditEntry> {
+    let signed = sign_checkpoint(&signer.secret, &head.hash).ok_or_else(|| invalid("audit checkpoint signing failed"))?;
+    let detail = json!({
+        "head_seq": head.seq.checked_sub(1),
+        "head_hash": head.hash,
+        "signer_public_hex": hex::encode(&signer.public),
+        "signed_hex": hex::encode(signed),
+    });
+    append(head, AuditEvent::new(None, CHECKPOINT_ACTION, "signed", detail))
+}
+
+fn with_head<T>(f: impl FnOnce(&mut Head) -> Result<T>) -> Result<T> {
+    let mut guard = HEAD.lock().map_err(io)?;
+    ensure_wallet_dir().map_err(io)?;
+    let path = log_path();
+    if guard.as_ref().is_none_or(|h| h.path != path) {
+        *guard = Some(read_head(&path)?);
+    }
+    let result = f(guard.as_mut().expect("head was just loaded"));
+    if result.is_err() {
+        // the file may now differ from the cache; re-read it next time
+        *guard = None;
+    }
+    result
+}
+
+pub fn record(event: &AuditEvent) -> Result<()> {
+    with_head(|head| {
+        append(head, event.clone())?;
+        if head.since_checkpoint >= CHECKPOINT_EVERY {
+            if let Some(signer) = SIGNER.lock().map_err(io)?.as_ref() {
+                append_checkpoint(head, signer)?;
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Shorthand for [`record`] of a new event.
+pub(crate) fn log(wallet: Option<&str>, action: &str, outcome: &str, detail: Value) -> Result<()> {
+    record(&AuditEvent::new(wallet, action, outcome, detail))
+}
+
+/// Signs the current chain head unless it is already signed; returns the
+/// checkpoint entry if one was written. Fails while the key is locked.
+pub fn checkpoint() -> Result<Option<AuditEntry>> {
+    with_head(|head| {
+        if head.since_checkpoint == 0 {
+            return Ok(None);
+        }
+        let signer = SIGNER.lock().map_err(io)?;
+        append_checkpoint(head, signer.as_ref().ok_or_else(locked)?).map(Some)
+    })
+}
+
+/// Every entry of the log at `path`, oldest first; unparseable lines are errors.
+pub fn read_file(path: &Path) -> Result<Vec<AuditEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let file = fs::File::open(path).map_err(io)?;
+    BufReader::new(file).lines()
+        .filter(|line| line.as_ref().map_or(true, |l| !l.trim().is_empty()))
+        .map(|line| parse_line(&line.map_err(io)?))
+        .collect()
+}
+
+/// Every entry of this wallet directory's log, oldest first.
+pub fn read_all() -> Result<Vec<AuditEntry>> {
+    let _guard = HEAD.lock().map_err(io)?;
+    read_file(&log_path())
+}
+
+#[derive(Deserialize, Default, Clone, Debug)]
+pub struct AuditQuery {
+    pub wallet: Option<String>,
+    pub action: Option<String>,
+    /// Only entries with `seq` greater than this.
+    pub after: Option<u64>,
+    pub limit: Option<usize>,
+}
+
+pub const DEFAULT_QUERY_LIMIT: usize = 100;
+pub const MAX_QUERY_LIMIT: usize = 1000;
+
+/// Matching entries, oldest first, at most `limit` of them.
+pub fn query(q: &AuditQuery) -> Result<Vec<AuditEntry>> {
+    let limit = q.limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT);
+    Ok(read_all()?.into_iter()
+        .filter(|e| q.after.is_none_or(|a| e.seq > a))
+        .filter(|e| q.wallet.as_ref().is_none_or(|w| e.event.wallet.as_ref() == Some(w)))
+        .filter(|e| q.action.as_ref().is_none_or(|a| e.event.action == *a))
+        .take(limit)
+        .collect())
+}
+
+fn broken(seq: u64, reason: impl fmt::Display) -> CoreError {
+    CoreError::Tampered(format!("audit log broken at entry {seq}: {reason}"))
+}
+
+/// Checks the whole chain and every checkpoint signature. With `trusted_key`
+/// every checkpoint must be signed by it; without, by the same key as the first.
+pub fn verify(entries: &[AuditEntry], trusted_key: Option<&[u8]>) -> Result<AuditReport> {
+    let mut prev = AUDIT_GENESIS.to_string();
+    let mut signer: Option<Vec<u8>> = trusted_key.map(<[u8]>::to_vec);
+    let mut report = AuditReport {
+        entries: 0,
+        head_hash: prev.clone(),
+        checkpoints: 0,
+        signer_public_hex: None,
+        last_checkpoint_seq: None,
+        unsigned_tail: 0,
+    };
+    for (i, e) in entries.iter().enumerate() {
+        if e.seq != i as u64 {
+            return Err(broken(i as u64, format!("expected seq {i}, found {}", e.seq)));
+        }
+        if e.prev_hash != prev {
+            return Err(broken(e.seq, "does not link to the entry before it"));
+        }
+        if !e.hash_matches() {
+            return Err(broken(e.seq, "contents do not match its hash"));
+        }
+        report.unsigned_tail += 1;
+        if e.event.action == CHECKPOINT_ACTION {
+            let field = |k: &str| e.event.detail.get(k).and_then(Value::as_str).map(hex::decode);
+            let (Some(Ok(public)), Some(Ok(signed))) = (field("signer_public_hex"), field("signed_hex")) else {
+                return Err(broken(e.seq, "checkpoint is missing its signature"));
+            };
+            if e.event.detail.get("head_hash").and_then(Value::as_str) != Some(prev.as_str()) {
+                return Err(broken(e.seq, "checkpoint names a different head"));
+            }
+            match &signer {
+                Some(k) if *k != public => {
+                    return Err(broken(e.seq, format!("checkpoint signed by unexpected key {}", address_from_pubkey(&public))));
+                }
+                Some(_) => {}
+                None => signer = Some(public.clone()),
+            }
+            if !verify_checkpoint(&public, &prev, &signed) {
+                return Err(broken(e.seq, "checkpoint signature does not verify"));
+            }
+            report.checkpoints += 1;
+            report.last_checkpoint_seq = Some(e.seq);
+            report.unsigned_tail = 0;
+        }
+        prev = e.hash.clone();
+        report.entries += 1;
+    }
+    report.head_hash = prev;
+    report.signer_public_hex = signer.map(hex::encode);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn temp_log(prefix: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("qs-audit-{}-{}", std::process::id(), testing::name(prefix)));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("audit.jsonl")
+    }
+
+    fn signer() -> Signer {
+        let kp = generate_dilithium3();
+        Signer { public: kp.public, secret: kp.secret }
+    }
+
+    /// Three events, a checkpoint, then two more events.
+    fn sample_log(path: &Path, signer: &Signer) -> Vec<AuditEntry> {
+        let mut head = read_head(path).unwrap();
+        for i in 0..3 {
+            append(&mut head, AuditEvent::new(Some("w"), "sign", "ok", json!({ "i": i }))).unwrap();
+        }
+        append_checkpoint(&mut head, signer).unwrap();
+        for i in 3..5 {
+            append(&mut head, AuditEvent::new(None, "http", "ok", json!({ "i": i }))).unwrap();
+        }
+        read_file(path).unwrap()
+    }
+
+    #[test]
+    fn chain_and_checkpoints_verify() {
+        let path = temp_log("chain");
+        let signer = signer();
+        let entries = sample_log(&path, &signer);
+
+        let report = verify(&entries, Some(&signer.public)).unwrap();
+        assert_eq!((report.entries, report.checkpoints), (6, 1));
+        assert_eq!(report.last_checkpoint_seq, Some(3));
+        assert_eq!(report.unsigned_tail, 2);
+        assert_eq!(report.head_hash, entries[5].hash);
+        assert_eq!(report.signer_public_hex, Some(hex::encode(&signer.public)));
+        assert_eq!(verify(&entries, None).unwrap().signer_public_hex, report.signer_public_hex);
+        assert_eq!(verify(&[], None).unwrap().head_hash, AUDIT_GENESIS);
+
+        // the head is picked up again from the file
+        let head = read_head(&path).unwrap();
+        assert_eq!((head.seq, head.hash.as_str(), head.since_checkpoint), (6, entries[5].hash.as_str(), 2));
+    }
+
+    #[test]
+    fn tampering_is_caught() {
+        let signer = signer();
+        let entries = sample_log(&temp_log("tamper"), &signer);
+        assert!(verify(&entries, Some(&self::signer().public)).is_err());
+
+        let mut edited = entries.clone();
+        edited[1].event.outcome = "denied".into();
+        assert!(verify(&edited, None).is_err());
+
+        let mut dropped = entries.clone();
+        dropped.remove(1);
+        assert!(verify(&dropped, None).is_err());
+
+        let mut reordered = entries.clone();
+        reordered.swap(0, 1);
+        assert!(verify(&reordered, None).is_err());
+
+        let mut forged = entries.clone();
+        forged[3].event.detail["signed_hex"] = json!(hex::encode(sign_checkpoint(&self::signer().secret, &entries[2].hash).unwrap()));
+        forged[3] = AuditEntry::chain(3, forged[3].prev_hash.clone(), forged[3].event.clone()).unwrap();
+        assert!(verify(&forged[..4], None).is_err());
+
+        // cutting the unsigned tail goes unnoticed, which is what checkpoints bound
+        assert_eq!(verify(&entries[..4], Some(&signer.public)).unwrap().unsigned_tail, 0);
+    }
+
+    #[test]
+    fn damaged_last_line_stops_appends() {
+        let path = temp_log("damaged");
+        sample_log(&path, &signer());
+        let mut text = fs::read_to_string(&path).unwrap();
+        text = text.replacen("\"i\":4", "\"i\":5", 1);
+        fs::write(&path, text).unwrap();
+        assert!(matches!(read_head(&path), Err(CoreError::Tampered(_))));
+        fs::write(&path, "not json\n").unwrap();
+        assert!(read_file(&path).is_err());
+    }
+
+    #[test]
+    fn audit_key_is_sealed_and_pinned() {
+        testing::wallet_dir();
+        ensure_wallet_dir().unwrap();
+        assert!(unlock_key("").is_err());
+
+        // a key written before keys were sealed is sealed on first unlock
+        let kp = generate_dilithium3();
+        let legacy = AuditKey {
+            scheme: SCHEME.into(),
+            public_hex: hex::encode(&kp.public),
+            sealed: None,
+            secret_hex: Some(hex::encode(&kp.secret)),
+            created_at: 0,
+        };
+        write_key(&key_path(), &legacy).unwrap();
+        assert!(key_is_unsealed().unwrap());
+        assert_eq!(unlock_key("Copper-Kettle-Meadow-52").unwrap(), kp.public);
+        assert!(!key_is_unsealed().unwrap());
+        assert!(!fs::read_to_string(key_path()).unwrap().contains(&hex::encode(&kp.secret)));
+
+        assert!(matches!(unlock_key("wrong passphrase"), Err(CoreError::BadPassword)));
+        assert_eq!(unlock_key("Copper-Kettle-Meadow-52").unwrap(), kp.public);
+
+        // swapping the file afterwards does not change the key in use
+        let other = generate_dilithium3();
+        write_key(&key_path(), &AuditKey { public_hex: hex::encode(&other.public), ..legacy }).unwrap();
+        assert_eq!(public_key().unwrap(), kp.public);
+        let checkpoint = checkpoint().unwrap();
+        if let Some(entry) = checkpoint {
+            assert_eq!(entry.event.detail["signer_public_hex"], json!(hex::encode(&kp.public)));
+        }
+    }
+}
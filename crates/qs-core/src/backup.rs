@@ -0,0 +1,322 @@
+//! Whole-wallet-directory backup as one passphrase-encrypted archive.
+//!
+//! The manifest (names, addresses, schemes, payload hash) stays readable so an
+//! archive can be inspected without the passphrase, and is bound to the
+//! ciphertext as associated data so it cannot be edited. Restore checks every
+//! entry before writing anything, builds the restored wallet directory next to
+//! the live one, and swaps it in whole.
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha3::{Digest, Sha3_256};
+use std::{collections::BTreeMap, fs, path::{Path, PathBuf}};
+
+use qs_crypto::{
+    KeyfileError, SealedBox, address_from_pubkey, check_keyfile, decrypt_secret, open_with_password, seal_with_password,
+};
+use qs_utils::{ensure_wallet_dir, write_json};
+
+use crate::registry::{self, RegistryEntry};
+use crate::{audit, invalid, io, keyfile_err, list_wallets, now_secs, password, read_wallet, reseal, validate_name, CoreError, Result, WalletFile};
+
+pub const BACKUP_VERSION: u8 = 1;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub name: String,
+    pub address: String,
+    pub scheme: String,
+    pub watch_only: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct BackupManifest {
+    pub v: u8,
+    pub created_at: u64,
+    pub wallets: Vec<ManifestEntry>,
+    pub registry_entries: usize,
+    pub payload_sha3: String, // hex SHA3-256 of the decrypted payload
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BackupArchive {
+    pub manifest: BackupManifest,
+    pub sealed: SealedBox,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupPayload {
+    wallets: BTreeMap<String, WalletFile>,
+    registry: Vec<RegistryEntry>,
+}
+
+fn manifest_entry(name: &str, file: &WalletFile) -> Result<ManifestEntry> {
+    let public = hex::decode(file.public_hex()).map_err(invalid)?;
+    Ok(ManifestEntry {
+        name: name.to_string(),
+        address: address_from_pubkey(&public),
+        scheme: file.scheme().to_string(),
+        watch_only: file.is_watch_only(),
+    })
+}
+
+fn aad(manifest: &BackupManifest) -> Result<Vec<u8>> {
+    serde_json::to_vec(manifest).map_err(io)
+}
+
+pub fn backup(passphrase: &str) -> Result<BackupArchive> {
+    if passphrase.is_empty() {
+        return Err(invalid("backup passphrase is required"));
+    }
+    let mut wallets = BTreeMap::new();
+    for w in list_wallets()? {
+        let file = read_wallet(&w.name)?;
+        wallets.insert(w.name, file);
+    }
+    let archive = seal(wallets, registry::list()?, passphrase)?;
+    audit::log(None, "backup", "ok", json!({ "wallets": archive.manifest.wallets.len() }))?;
+    Ok(archive)
+}
+
+fn seal(wallets: BTreeMap<String, WalletFile>, registry: Vec<RegistryEntry>, passphrase: &str) -> Result<BackupArchive> {
+    let entries = wallets.iter().map(|(name, file)| manifest_entry(name, file)).collect::<Result<Vec<_>>>()?;
+    let registry_entries = registry.len();
+    let payload = serde_json::to_vec(&BackupPayload { wallets, registry }).map_err(io)?;
+    let manifest = BackupManifest {
+        v: BACKUP_VERSION,
+        created_at: now_secs(),
+        wallets: entries,
+        registry_entries,
+        payload_sha3: hex::encode(Sha3_256::digest(&payload)),
+    };
+    let sealed = seal_with_password(&payload, passphrase, &aad(&manifest)?);
+    Ok(BackupArchive { manifest, sealed })
+}
+
+/// Decrypts and checks an archive without touching the wallet directory.
+/// Every keyfile must be well-formed ([`check_keyfile`]); those named in
+/// `passwords` must also unlock to a secret matching their public key.
+fn open_archive(archive: &BackupArchive, passphrase: &str, passwords: &BTreeMap<String, String>) -> Result<BackupPayload> {
+    let manifest = &archive.manifest;
+    if manifest.v != BACKUP_VERSION {
+        return Err(CoreError::Invalid(format!("unsupported backup version {}", manifest.v)));
+    }
+    let plain = open_with_password(&archive.sealed, passphrase, &aad(manifest)?).ok_or(CoreError::BadPassword)?;
+    if hex::encode(Sha3_256::digest(&plain)) != manifest.payload_sha3 {
+        return Err(invalid("backup integrity hash mismatch"));
+    }
+    let payload: BackupPayload = serde_json::from_slice(&plain).map_err(invalid)?;
+
+    if payload.wallets.len() != manifest.wallets.len() || payload.registry.len() != manifest.registry_entries {
+        return Err(invalid("backup manifest does not match its contents"));
+    }
+    for entry in &manifest.wallets {
+        validate_name(&entry.name)?;
+        let file = payload.wallets.get(&entry.name)
+            .ok_or_else(|| CoreError::Invalid(format!("wallet `{}` missing from backup", entry.name)))?;
+        if &manifest_entry(&entry.name, file)? != entry {
+            return Err(CoreError::Invalid(format!("wallet `{}` does not match the manifest", entry.name)));
+        }
+        let WalletFile::Keyfile(ek) = file else { continue };
+        check_keyfile(ek).map_err(|e| keyfile_err(&entry.name, e))?;
+        if let Some(pw) = passwords.get(&entry.name) {
+            match decrypt_secret(ek, &entry.name, pw) {
+                Ok(_) => {}
+                Err(KeyfileError::BadPassword) => {
+                    return Err(CoreError::Invalid(format!("wallet `{}` does not unlock with the given password", entry.name)));
+                }
+                Err(e) => return Err(keyfile_err(&entry.name, e)),
+            }
+        }
+    }
+    Ok(payload)
+}
+
+/// Restores every wallet in the archive, or none. Existing wallets block the
+/// restore unless `overwrite` is set. Registry entries are merged in.
+///
+/// Wallets named in `new_passwords` are restored re-encrypted under the new
+/// password, which must pass the [`password`](crate::password) rules; their
+/// current password must be in `passwords`.
+pub fn restore(
+    archive: &BackupArchive,
+    passphrase: &str,
+    overwrite: bool,
+    passwords: &BTreeMap<String, String>,
+    new_passwords: &BTreeMap<String, String>,
+) -> Result<Vec<String>> {
+    let dir = ensure_wallet_dir().map_err(io)?;
+    restore_in(&dir, archive, passphrase, overwrite, passwords, new_passwords)
+}
+
+/// [`restore`] into wallet directory `dir`.
+fn restore_in(
+    dir: &Path,
+    archive: &BackupArchive,
+    passphrase: &str,
+    overwrite: bool,
+    passwords: &BTreeMap<String, String>,
+    new_passwords: &BTreeMap<String, String>,
+) -> Result<Vec<String>> {
+    let mut payload = open_archive(archive, passphrase, passwords)?;
+    for (name, new_password) in new_passwords {
+        password::check(name, new_password)?;
+        let old_password = passwords.get(name)
+            .ok_or_else(|| CoreError::Invalid(format!("give the current password of wallet `{name}` to change it")))?;
+        let Some(WalletFile::Keyfile(ek)) = payload.wallets.get(name) else {
+            return Err(CoreError::Invalid(format!("wallet `{name}` is not a keyfile in this backup")));
+        };
+        let secret = decrypt_secret(ek, name, old_password).map_err(|e| keyfile_err(name, e))?;
+        let rekeyed = reseal(name, ek, &secret, old_password, new_password)?;
+        payload.wallets.insert(name.clone(), WalletFile::Keyfile(rekeyed));
+    }
+    if !overwrite {
+        if let Some(name) = payload.wallets.keys().find(|n| dir.join(format!("{n}.json")).exists()) {
+            return Err(CoreError::Exists(name.clone()));
+        }
+    }
+
+    let dir = fs::canonicalize(dir).map_err(io)?;
+    let stamp = format!("{}-{}", now_secs(), std::process::id());
+    let staging = beside(&dir, "restore", &stamp)?;
+    if let Err(e) = stage(&dir, &staging, &payload) {
+        fs::remove_dir_all(&staging).ok();
+        return Err(e);
+    }
+    swap_in(&dir, &staging, &beside(&dir, "replaced", &stamp)?)?;
+
+    let names: Vec<String> = payload.wallets.into_keys().collect();
+    let rekeyed: Vec<&String> = new_passwords.keys().collect();
+    audit::log(None, "restore", "ok", json!({ "wallets": names, "overwrite": overwrite, "rekeyed": rekeyed }))?;
+    Ok(names)
+}
+
+/// `.<dir name>.<what>-<stamp>` next to `dir`, so renames stay on one filesystem.
+fn beside(dir: &Path, what: &str, stamp: &str) -> Result<PathBuf> {
+    let name = dir.file_name().ok_or_else(|| invalid("wallet directory has no name"))?.to_string_lossy();
+    Ok(dir.with_file_name(format!(".{name}.{what}-{stamp}")))
+}
+
+/// Copies the live directory into `staging` and writes the restored wallets
+/// and registry entries over the copy.
+fn stage(dir: &Path, staging: &Path, payload: &BackupPayload) -> Result<()> {
+    copy_dir(dir, staging)?;
+    for (name, file) in &payload.wallets {
+        write_json(staging.join(format!("{name}.json")), file).map_err(io)?;
+    }
+    registry::merge_in(staging, payload.registry.clone())?;
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to).map_err(io)?;
+    for entry in fs::read_dir(from).map_err(io)? {
+        let entry = entry.map_err(io)?;
+        let target = to.join(entry.file_name());
+        if entry.file_type().map_err(io)?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target).map_err(io)?;
+        }
+    }
+    Ok(())
+}
+
+/// Moves `staging` into `dir`'s place, putting the old directory back if that fails.
+fn swap_in(dir: &Path, staging: &Path, aside: &Path) -> Result<()> {
+    if let Err(e) = fs::rename(dir, aside) {
+        fs::remove_dir_all(staging).ok();
+        return Err(io(e));
+    }
+    if let Err(e) = fs::rename(staging, dir) {
+        fs::rename(aside, dir).ok();
+        fs::remove_dir_all(staging).ok();
+        return Err(io(e));
+    }
+    fs::remove_dir_all(aside).ok();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, PASSWORD};
+
+    const PASSPHRASE: &str = "Copper-Fjord-Meadow-42";
+
+    /// An empty directory standing in for the wallet directory.
+    fn target(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("qs-core-restore-{}-{}", std::process::id(), testing::name(tag)));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn archive_of(names: &[&str]) -> BackupArchive {
+        let wallets = names.iter().map(|n| (n.to_string(), read_wallet(n).unwrap())).collect();
+        seal(wallets, registry::list().unwrap(), PASSPHRASE).unwrap()
+    }
+
+    fn none() -> BTreeMap<String, String> { BTreeMap::new() }
+
+    #[test]
+    fn restores_into_the_directory_and_keeps_its_other_files() {
+        let name = testing::wallet("backup");
+        let archive = archive_of(&[&name]);
+        let dir = target("restore");
+        fs::write(dir.join(".marker"), "kept").unwrap();
+        fs::create_dir_all(dir.join("retired")).unwrap();
+
+        assert_eq!(restore_in(&dir, &archive, PASSPHRASE, false, &none(), &none()).unwrap(), vec![name.clone()]);
+        let restored: WalletFile = qs_utils::read_json(dir.join(format!("{name}.json"))).unwrap();
+        assert_eq!(restored.public_hex(), read_wallet(&name).unwrap().public_hex());
+        assert_eq!(fs::read_to_string(dir.join(".marker")).unwrap(), "kept");
+        assert!(dir.join("retired").is_dir());
+
+        // nothing left beside the directory
+        let prefix = format!(".{}.", dir.file_name().unwrap().to_string_lossy());
+        let leftovers = fs::read_dir(dir.parent().unwrap()).unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().starts_with(&prefix))
+            .count();
+        assert_eq!(leftovers, 0);
+
+        // a second restore needs overwrite
+        assert!(matches!(restore_in(&dir, &archive, PASSPHRASE, false, &none(), &none()), Err(CoreError::Exists(_))));
+        restore_in(&dir, &archive, PASSPHRASE, true, &none(), &none()).unwrap();
+    }
+
+    #[test]
+    fn refuses_bad_archives_without_writing() {
+        let name = testing::wallet("backup");
+        let dir = target("refuse");
+        let archive = archive_of(&[&name]);
+        assert!(matches!(restore_in(&dir, &archive, "wrong", false, &none(), &none()), Err(CoreError::BadPassword)));
+
+        let mut edited = archive.clone();
+        edited.manifest.wallets[0].watch_only = true;
+        assert!(restore_in(&dir, &edited, PASSPHRASE, false, &none(), &none()).is_err());
+
+        // a broken keyfile is refused even when no password is given for it
+        let WalletFile::Keyfile(mut ek) = read_wallet(&name).unwrap() else { unreachable!() };
+        ek.nonce_b64 = "AAAA".into();
+        let broken = seal(BTreeMap::from([(name.clone(), WalletFile::Keyfile(ek))]), Vec::new(), PASSPHRASE).unwrap();
+        assert!(restore_in(&dir, &broken, PASSPHRASE, false, &none(), &none()).is_err());
+
+        let wrong = BTreeMap::from([(name.clone(), "not the password".to_string())]);
+        assert!(restore_in(&dir, &archive, PASSPHRASE, false, &wrong, &none()).is_err());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn rekeys_on_restore() {
+        let name = testing::wallet("backup");
+        let dir = target("rekey");
+        let new = "Violet-Canyon-Ember-58".to_string();
+        let passwords = BTreeMap::from([(name.clone(), PASSWORD.to_string())]);
+        let new_passwords = BTreeMap::from([(name.clone(), new.clone())]);
+        assert!(restore_in(&dir, &archive_of(&[&name]), PASSPHRASE, false, &none(), &new_passwords).is_err());
+        restore_in(&dir, &archive_of(&[&name]), PASSPHRASE, false, &passwords, &new_passwords).unwrap();
+
+        let WalletFile::Keyfile(ek) = qs_utils::read_json(dir.join(format!("{name}.json"))).unwrap() else { unreachable!() };
+        assert!(decrypt_secret(&ek, &name, &new).is_ok());
+        assert_eq!(decrypt_secret(&ek, &name, PASSWORD).unwrap_err(), KeyfileError::BadPassword);
+    }
+}
@@ -0,0 +1,271 @@
+//! On-chain addresses linked to wallets, and the last balances seen for them.
+//!
+//! A wallet can be linked to any number of Solana or EVM addresses, each for
+//! the chain's native coin or one token (an SPL mint, an ERC-20 contract).
+//! This crate never talks to a chain: a front-end with RPC access fetches the
+//! balances and hands them to [`record`], and everyone reads them back with
+//! [`balances`], together with when they were fetched and the last failure.
+//! Linking and unlinking take the wallet's password, so only its owner
+//! decides which addresses it shows. Links and cache share `.balances.json`
+//! in the wallet directory.
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{collections::BTreeMap, fmt, path::PathBuf, sync::Mutex};
+
+use qs_crypto::{solana::Pubkey, Amount};
+use qs_utils::{ensure_wallet_dir, read_json, wallet_dir, write_json};
+
+use crate::{audit, io, now_secs, read_wallet, unlock, CoreError, Result};
+
+// serializes read-modify-write of the balances file within this process
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Chain { Solana, Evm }
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self { Chain::Solana => "solana", Chain::Evm => "evm" })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LinkedAddress {
+    pub chain: Chain,
+    pub address: String,
+    /// SPL mint or ERC-20 contract; `None` for SOL / ETH.
+    pub token: Option<String>,
+    pub label: Option<String>,
+    pub added_at: u64,
+}
+
+impl LinkedAddress {
+    fn key(&self) -> String { cache_key(self.chain, &self.address, self.token.as_deref()) }
+
+    fn same(&self, chain: Chain, address: &str, token: Option<&str>) -> bool {
+        self.chain == chain && self.address == address && self.token.as_deref() == token
+    }
+}
+
+/// A linked address with its cached balance.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct AddressBalance {
+    #[serde(flatten)]
+    pub link: LinkedAddress,
+    /// Last balance fetched, in the token's own decimals.
+    pub amount: Option<Amount>,
+    pub fetched_at: Option<u64>,
+    /// Why the latest fetch failed, if it did; `amount` is then older.
+    pub error: Option<String>,
+}
+
+impl AddressBalance {
+    /// Fetched successfully within the last `max_age_secs`.
+    pub fn is_fresh(&self, max_age_secs: u64) -> bool {
+        self.error.is_none() && self.fetched_at.is_some_and(|at| now_secs().saturating_sub(at) < max_age_secs)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct Cached {
+    amount: Option<Amount>,
+    fetched_at: Option<u64>,
+    error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Store {
+    links: BTreeMap<String, Vec<LinkedAddress>>,
+    /// By chain, address and token, so wallets linking the same address share it.
+    cache: BTreeMap<String, Cached>,
+}
+
+fn store_path() -> PathBuf {
+    wallet_dir().join(".balances.json")
+}
+
+fn load() -> Result<Store> {
+    let path = store_path();
+    if !path.exists() {
+        return Ok(Store::default());
+    }
+    read_json(path).map_err(io)
+}
+
+fn save(store: &Store) -> Result<()> {
+    ensure_wallet_dir().map_err(io)?;
+    write_json(store_path(), store).map_err(io)
+}
+
+fn cache_key(chain: Chain, address: &str, token: Option<&str>) -> String {
+    format!("{chain}:{address}:{}", token.unwrap_or(""))
+}
+
+/// Checks an address for `chain` and returns it in canonical form
+/// (EVM addresses lower-cased).
+fn canonical(chain: Chain, address: &str) -> Result<String> {
+    let address = address.trim();
+    match chain {
+        Chain::Solana => address.parse::<Pubkey>().map(|p| p.to_string()).map_err(crate::invalid),
+        Chain::Evm => {
+            let hex = address.strip_prefix("0x").unwrap_or_default();
+            if hex.len() != 40 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(CoreError::Invalid(format!("`{address}` is not an EVM address (0x and 40 hex digits)")));
+            }
+            Ok(format!("0x{}", hex.to_ascii_lowercase()))
+        }
+    }
+}
+
+/// Links `address` on `chain` (and `token`, if any) to wallet `name`.
+/// Linking it again only updates the label.
+pub fn link(
+    name: &str, password: &str, chain: Chain, address: &str, token: Option<&str>, label: Option<String>,
+) -> Result<LinkedAddress> {
+    let address = canonical(chain, address)?;
+    let token = token.map(|t| canonical(chain, t)).transpose()?;
+    unlock(name, password)?;
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    let links = store.links.entry(name.to_string()).or_default();
+    let linked = match links.iter_mut().find(|l| l.same(chain, &address, token.as_deref())) {
+        Some(existing) => {
+            if label.is_some() { existing.label = label; }
+            existing.clone()
+        }
+        None => {
+            let new = LinkedAddress { chain, address, token, label, added_at: now_secs() };
+            links.push(new.clone());
+            new
+        }
+    };
+    save(&store)?;
+    audit::log(Some(name), "link_address", "ok", json!({
+        "chain": linked.chain, "address": linked.address, "token": linked.token,
+    }))?;
+    Ok(linked)
+}
+
+pub fn unlink(name: &str, password: &str, chain: Chain, address: &str, token: Option<&str>) -> Result<()> {
+    let address = canonical(chain, address)?;
+    let token = token.map(|t| canonical(chain, t)).transpose()?;
+    unlock(name, password)?;
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    let not_linked = || CoreError::Invalid(format!("{chain} address `{address}` is not linked to wallet `{name}`"));
+    let links = store.links.get_mut(name).ok_or_else(not_linked)?;
+    let before = links.len();
+    links.retain(|l| !l.same(chain, &address, token.as_deref()));
+    if links.len() == before {
+        return Err(not_linked());
+    }
+    if links.is_empty() {
+        store.links.remove(name);
+    }
+    // drop the cached balance once no wallet links the address any more
+    let key = cache_key(chain, &address, token.as_deref());
+    if !store.links.values().flatten().any(|l| l.key() == key) {
+        store.cache.remove(&key);
+    }
+    save(&store)?;
+    audit::log(Some(name), "unlink_address", "ok", json!({ "chain": chain, "address": address, "token": token }))
+}
+
+pub fn linked(name: &str) -> Result<Vec<LinkedAddress>> {
+    read_wallet(name)?;
+    let _guard = LOCK.lock().map_err(io)?;
+    Ok(load()?.links.remove(name).unwrap_or_default())
+}
+
+/// Wallet `name`'s linked addresses with their cached balances; never touches a chain.
+pub fn balances(name: &str) -> Result<Vec<AddressBalance>> {
+    read_wallet(name)?;
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    let links = store.links.remove(name).unwrap_or_default();
+    Ok(links.into_iter().map(|link| {
+        let Cached { amount, fetched_at, error } = store.cache.get(&link.key()).cloned().unwrap_or_default();
+        AddressBalance { link, amount, fetched_at, error }
+    }).collect())
+}
+
+/// Stores the outcome of fetching `link`'s balance. A failure keeps the
+/// last good amount and records why the refresh failed.
+pub fn record(link: &LinkedAddress, fetched: std::result::Result<Amount, String>) -> Result<()> {
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    let cached = store.cache.entry(link.key()).or_default();
+    match fetched {
+        Ok(amount) => *cached = Cached { amount: Some(amount), fetched_at: Some(now_secs()), error: None },
+        Err(error) => cached.error = Some(error),
+    }
+    save(&store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, PASSWORD};
+
+    const SOL_ADDRESS: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    #[test]
+    fn linking_takes_the_wallet_password() {
+        let name = testing::wallet("link");
+        let err = link(&name, "not-the-password", Chain::Solana, SOL_ADDRESS, None, None).unwrap_err();
+        assert!(matches!(err, CoreError::BadPassword), "{err}");
+        assert!(linked(&name).unwrap().is_empty());
+
+        let evm = link(&name, PASSWORD, Chain::Evm, "0xABCDEF0123456789abcdef0123456789ABCDEF01", None, None).unwrap();
+        assert_eq!(evm.address, "0xabcdef0123456789abcdef0123456789abcdef01");
+        link(&name, PASSWORD, Chain::Solana, SOL_ADDRESS, Some(USDC), None).unwrap();
+        // linking again only relabels
+        let relinked = link(&name, PASSWORD, Chain::Solana, SOL_ADDRESS, Some(USDC), Some("savings".into())).unwrap();
+        assert_eq!(relinked.label.as_deref(), Some("savings"));
+        assert_eq!(linked(&name).unwrap().len(), 2);
+
+        assert!(link(&name, PASSWORD, Chain::Evm, "0x1234", None, None).is_err());
+        assert!(link(&name, PASSWORD, Chain::Solana, "not-base58!", None, None).is_err());
+        assert!(matches!(link("no-such-wallet", PASSWORD, Chain::Solana, SOL_ADDRESS, None, None), Err(CoreError::NotFound(_))));
+    }
+
+    #[test]
+    fn unlinking_takes_the_password_and_drops_unshared_cache() {
+        let (a, b) = (testing::wallet("unlink-a"), testing::wallet("unlink-b"));
+        let shared = link(&a, PASSWORD, Chain::Solana, SOL_ADDRESS, None, None).unwrap();
+        link(&b, PASSWORD, Chain::Solana, SOL_ADDRESS, None, None).unwrap();
+        record(&shared, Ok(Amount::parse("2.000000000").unwrap())).unwrap();
+
+        assert!(matches!(unlink(&a, "not-the-password", Chain::Solana, SOL_ADDRESS, None), Err(CoreError::BadPassword)));
+        unlink(&a, PASSWORD, Chain::Solana, SOL_ADDRESS, None).unwrap();
+        assert!(unlink(&a, PASSWORD, Chain::Solana, SOL_ADDRESS, None).is_err());
+        // still linked to b, which keeps seeing the cached balance
+        assert_eq!(balances(&b).unwrap()[0].amount, Some(Amount::parse("2.000000000").unwrap()));
+
+        unlink(&b, PASSWORD, Chain::Solana, SOL_ADDRESS, None).unwrap();
+        let _guard = LOCK.lock().unwrap();
+        assert!(!load().unwrap().cache.contains_key(&shared.key()));
+    }
+
+    #[test]
+    fn failed_fetches_keep_the_last_amount() {
+        let name = testing::wallet("record");
+        let l = link(&name, PASSWORD, Chain::Solana, SOL_ADDRESS, Some(USDC), None).unwrap();
+        let never = &balances(&name).unwrap()[0];
+        assert_eq!((never.amount, never.fetched_at), (None, None));
+        assert!(!never.is_fresh(60));
+
+        record(&l, Ok(Amount::parse("1.500000").unwrap())).unwrap();
+        let fetched = &balances(&name).unwrap()[0];
+        assert!(fetched.is_fresh(60) && !fetched.is_fresh(0));
+
+        record(&l, Err("solana rpc answered 503".into())).unwrap();
+        let failed = &balances(&name).unwrap()[0];
+        assert_eq!(failed.amount, Some(Amount::parse("1.500000").unwrap()));
+        assert_eq!(failed.fetched_at, fetched.fetched_at);
+        assert_eq!(failed.error.as_deref(), Some("solana rpc answered 503"));
+        assert!(!failed.is_fresh(60));
+    }
+}
@@ -0,0 +1,202 @@
+//! Bridge intents: wallets sign them, the bridge verifies them before moving
+//! anything.
+//!
+//! Verified nonces are kept in `.bridge-intents.json` until their intent
+//! expires, so an intent is honored once even across restarts. Intents go
+//! through the wallet's policy like any `bridge` signature, with the amount
+//! in whole tokens.
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{collections::BTreeMap, path::PathBuf, sync::Mutex};
+
+use qs_crypto::{
+    Amount, BridgeIntent, IntentError, IntentNonces, SignedIntent, address_from_pubkey, check_intent, intent_domain, sign_intent,
+    verify_intent,
+};
+use qs_utils::{ensure_wallet_dir, read_json, wallet_dir, write_json};
+
+use crate::{audit, check_unprotected, invalid, io, now_secs, policy, revocation, unlock, CoreError, Result};
+
+pub const DEFAULT_TTL_SECS: u64 = 10 * 60;
+pub const MAX_TTL_SECS: u64 = 60 * 60;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// What the wallet is asked to sign; source, nonce and times are filled in.
+#[derive(Deserialize, Clone, Debug)]
+pub struct IntentRequest {
+    pub dest_chain: String,
+    pub dest_address: String,
+    pub token: String,
+    /// At the token's decimals, e.g. "1.500000" for 1.5 of a 6-decimal token.
+    pub amount: Amount,
+    pub ttl_secs: Option<u64>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct VerifiedIntent {
+    pub intent: BridgeIntent,
+    pub public_key_hex: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Store { used: BTreeMap<String, u64> }
+
+impl IntentNonces for Store {
+    fn was_used(&self, key: &str, now: u64) -> bool {
+        self.used.get(key).is_some_and(|exp| *exp > now)
+    }
+
+    fn first_use(&mut self, key: &str, expires_at: u64, now: u64) -> bool {
+        self.used.retain(|_, exp| *exp > now);
+        if self.used.contains_key(key) {
+            return false;
+        }
+        self.used.insert(key.to_string(), expires_at);
+        true
+    }
+}
+
+fn store_path() -> PathBuf {
+    wallet_dir().join(".bridge-intents.json")
+}
+
+fn load() -> Result<Store> {
+    let path = store_path();
+    if !path.exists() {
+        return Ok(Store::default());
+    }
+    read_json(path).map_err(io)
+}
+
+fn save(store: &Store) -> Result<()> {
+    ensure_wallet_dir().map_err(io)?;
+    write_json(store_path(), store).map_err(io)
+}
+
+fn rejected(e: IntentError) -> CoreError {
+    match e {
+        IntentError::Malformed(_) => CoreError::Invalid(e.to_string()),
+        _ => CoreError::IntentRejected(e.to_string()),
+    }
+}
+
+/// Signs a fresh intent from wallet `name`.
+pub fn sign(name: &str, password: &str, req: &IntentRequest) -> Result<SignedIntent> {
+    let ttl = req.ttl_secs.unwrap_or(DEFAULT_TTL_SECS).clamp(1, MAX_TTL_SECS);
+    check_unprotected(name)?;
+    let (ek, secret) = unlock(name, password)?;
+    let public = hex::decode(&ek.public_hex).map_err(invalid)?;
+    let intent = BridgeIntent::new(
+        &address_from_pubkey(&public), &req.dest_chain, &req.dest_address, &req.token, req.amount, now_secs(), ttl,
+    ).map_err(rejected)?;
+    let domain = intent_domain();
+    let fields = json!({
+        "amount": req.amount,
+        "destination": intent.dest_address,
+        "dest_chain": intent.dest_chain,
+        "token": intent.token,
+    });
+    policy::enforce(name, &policy::SignContext::typed(&domain, &fields))?;
+    let signed = sign_intent(&public, &secret, &intent).map_err(rejected)?;
+    audit::log(Some(name), "bridge_intent", "signed", json!({
+        "nonce": intent.nonce,
+        "dest_chain": intent.dest_chain,
+        "dest_address": intent.dest_address,
+        "token": intent.token,
+        "amount": req.amount,
+    }))?;
+    Ok(signed)
+}
+
+fn check_not_revoked(signed: &SignedIntent, public: &[u8]) -> Result<()> {
+    if revocation::status(public)?.is_some() {
+        return Err(CoreError::IntentRejected(format!("key {} is revoked", signed.intent.source)));
+    }
+    Ok(())
+}
+
+/// Checks `signed` as [`verify`] does but leaves its nonce unused, so a
+/// bridge can refuse a bad intent before preparing anything for it.
+pub fn check(signed: &SignedIntent) -> Result<VerifiedIntent> {
+    let public = {
+        let _guard = LOCK.lock().map_err(io)?;
+        check_intent(signed, now_secs(), &load()?).map_err(rejected)?
+    };
+    check_not_revoked(signed, &public)?;
+    Ok(VerifiedIntent { intent: signed.intent.clone(), public_key_hex: signed.public_key_hex.clone() })
+}
+
+/// Checks `signed` and uses up its nonce. Intents from revoked keys are refused.
+pub fn verify(signed: &SignedIntent) -> Result<VerifiedIntent> {
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    let now = now_secs();
+    let public = check_intent(signed, now, &store).map_err(rejected)?;
+    // before using the nonce, so a revoked key's intent stays refused, not spent
+    check_not_revoked(signed, &public)?;
+    verify_intent(signed, now, &mut store).map_err(rejected)?;
+    save(&store)?;
+    let intent = &signed.intent;
+    audit::log(None, "bridge_intent", "verified", json!({ "source": intent.source, "nonce": intent.nonce }))?;
+    Ok(VerifiedIntent { intent: intent.clone(), public_key_hex: signed.public_key_hex.clone() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::{BridgeLimits, WalletPolicy};
+    use crate::testing::{self, PASSWORD};
+
+    fn request(amount: &str) -> IntentRequest {
+        IntentRequest {
+            dest_chain: "solana".into(),
+            dest_address: "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM".into(),
+            token: "NOVA".into(),
+            amount: amount.parse().unwrap(),
+            ttl_secs: None,
+        }
+    }
+
+    #[test]
+    fn wallets_sign_intents_the_bridge_honors_once() {
+        let name = testing::wallet("intent");
+        let signed = sign(&name, PASSWORD, &request("1.500000")).unwrap();
+        assert_eq!(signed.intent.expires_at - signed.intent.issued_at, DEFAULT_TTL_SECS);
+        // checking, however often, leaves it usable
+        assert_eq!(check(&signed).unwrap().intent, signed.intent);
+        assert_eq!(check(&signed).unwrap().intent, signed.intent);
+        let verified = verify(&signed).unwrap();
+        assert_eq!(verified.intent, signed.intent);
+        assert!(matches!(verify(&signed), Err(CoreError::IntentRejected(_))));
+        assert!(matches!(check(&signed), Err(CoreError::IntentRejected(_))));
+
+        let mut forged = sign(&name, PASSWORD, &request("1.500000")).unwrap();
+        forged.intent.amount = "150.000000".parse().unwrap();
+        assert!(matches!(check(&forged), Err(CoreError::IntentRejected(_))));
+        assert!(matches!(verify(&forged), Err(CoreError::IntentRejected(_))));
+        assert!(matches!(sign(&name, "wrong password", &request("1.0")), Err(CoreError::BadPassword)));
+    }
+
+    #[test]
+    fn intents_follow_the_wallet_policy() {
+        let name = testing::wallet("intent");
+        let limits = BridgeLimits { min_amount: None, max_amount: Some("10.000000".parse().unwrap()) };
+        let policy = WalletPolicy { bridge: Some([("NOVA".to_string(), limits)].into()), ..Default::default() };
+        policy::set(&name, PASSWORD, Some(policy)).unwrap();
+        assert!(sign(&name, PASSWORD, &request("10.000000")).is_ok());
+        assert!(matches!(sign(&name, PASSWORD, &request("10.000001")), Err(CoreError::PolicyDenied(d)) if d.rule == "bridge"));
+        let mut other = request("1.0");
+        other.token = "OTHER".into();
+        assert!(matches!(sign(&name, PASSWORD, &other), Err(CoreError::PolicyDenied(_))));
+    }
+
+    #[test]
+    fn revoked_keys_cannot_bridge() {
+        let name = testing::wallet("intent");
+        let signed = sign(&name, PASSWORD, &request("1.0")).unwrap();
+        revocation::revoke_with_wallet(&name, PASSWORD, None, "lost").unwrap();
+        assert!(matches!(check(&signed), Err(CoreError::IntentRejected(m)) if m.contains("revoked")));
+        assert!(matches!(verify(&signed), Err(CoreError::IntentRejected(m)) if m.contains("revoked")));
+    }
+}
@@ -0,0 +1,170 @@
+//! Compatibility layer for the routes the warp API used to serve on :8080
+//! (`/balance`, `/sign/:msg`, `/verify/:msg/:sig`, `/generate`).
+//!
+//! Those routes had a single implicit wallet and no password. Here they are
+//! backed by a regular keyfile named [`DEFAULT_WALLET`], unlocked with the
+//! password from `QS_LEGACY_PASSWORD`. Signatures stay base64 as before but
+//! are made under [`legacy_domain`], so they cannot be replayed elsewhere.
+//!
+//! The warp API kept its wallet, a Dilithium5 key, in a sled database at
+//! [`LEGACY_DB`]. That key cannot be carried into a keyfile of this build's
+//! scheme, so while the database is still there the legacy routes refuse to
+//! run instead of quietly signing with a new key: an operator has to retire
+//! it first, knowing that signatures it made will no longer verify here.
+//! `/balance` keeps its `balance` number (the default wallet's native SOL,
+//! as far as it is linked and cached) and adds the exact per-address
+//! `balances` (see [`balances`]). `/sign/:msg` is no longer served: a
+//! password-less GET signing with a stored password can be set off by any page.
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use qs_crypto::{Domain, SCHEME};
+
+use crate::{balances::{self, AddressBalance, Chain}, CoreError, Result, WalletInfo};
+
+pub const DEFAULT_WALLET: &str = "default";
+pub const PASSWORD_ENV: &str = "QS_LEGACY_PASSWORD";
+/// Where the warp API kept its wallet, relative to the directory it ran in.
+pub const LEGACY_DB: &str = "wallet_data/db";
+
+pub fn legacy_domain() -> Domain { Domain::new("legacy", "qs-api") }
+
+#[derive(Serialize, Deserialize)]
+pub struct BalanceRes {
+    pub public_key: String,
+    /// What `/balance` always returned, as a JSON number: the native SOL of
+    /// the linked Solana addresses. Approximate; `balances` is exact.
+    pub balance: f64,
+    pub balances: Vec<AddressBalance>,
+}
+#[derive(Serialize, Deserialize)] pub struct SignRes     { pub message: String, pub signature: String }
+#[derive(Serialize, Deserialize)] pub struct VerifyRes   { pub verified: bool }
+#[derive(Serialize, Deserialize)] pub struct GenerateRes { pub public_key: String }
+
+fn password() -> Result<String> {
+    std::env::var(PASSWORD_ENV)
+        .map_err(|_| CoreError::Invalid(format!("{PASSWORD_ENV} is not set; legacy routes are disabled")))
+}
+
+/// Errors while the old sled wallet is still in place, either where the warp
+/// API kept it or in the wallet directory.
+fn check_retired() -> Result<()> {
+    let in_dir = qs_utils::wallet_dir().join("db");
+    check_retired_at(&[Path::new(LEGACY_DB), &in_dir])
+}
+
+fn check_retired_at(dbs: &[&Path]) -> Result<()> {
+    for db in dbs {
+        if db.exists() {
+            return Err(CoreError::Invalid(format!(
+                "found the warp API's Dilithium5 wallet at {}; it cannot be migrated to a {SCHEME} keyfile and \
+                 its signatures will stop verifying here. Move it aside (e.g. to {}.retired) to use the legacy routes",
+                db.display(), db.display(),
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Creates the default wallet on first use, like the old `ensure_wallet`.
+pub fn ensure_default() -> Result<WalletInfo> {
+    check_retired()?;
+    if crate::wallet_exists(DEFAULT_WALLET) {
+        return Ok(WalletInfo { name: DEFAULT_WALLET.to_string(), address: crate::address(DEFAULT_WALLET)?, watch_only: false });
+    }
+    crate::create_wallet(DEFAULT_WALLET, &password()?)
+}
+
+/// Cached balances, as last fetched by a front-end with chain access.
+pub fn balance() -> Result<BalanceRes> {
+    ensure_default()?;
+    balance_of(balances::balances(DEFAULT_WALLET)?)
+}
+
+/// `/balance` for balances of [`DEFAULT_WALLET`] the caller has already read,
+/// e.g. from a cache it refreshes itself.
+pub fn balance_of(balances: Vec<AddressBalance>) -> Result<BalanceRes> {
+    let public = crate::public_key(DEFAULT_WALLET)?;
+    Ok(BalanceRes { public_key: B64.encode(public), balance: native_sol(&balances), balances })
+}
+
+fn native_sol(balances: &[AddressBalance]) -> f64 {
+    balances.iter()
+        .filter(|b| b.link.chain == Chain::Solana && b.link.token.is_none())
+        .filter_map(|b| b.amount.as_ref()?.to_string().parse::<f64>().ok())
+        .sum()
+}
+
+/// Not served over HTTP any more (see the module docs); kept for callers in-process.
+pub fn sign(msg: &str) -> Result<SignRes> {
+    ensure_default()?;
+    // the legacy domain is reserved, so this is the only way to sign under it
+    crate::check_unprotected(DEFAULT_WALLET)?;
+    let signed = crate::sign_unchecked(DEFAULT_WALLET, &password()?, &legacy_domain(), msg.as_bytes())?;
+    Ok(SignRes { message: msg.to_string(), signature: B64.encode(signed) })
+}
+
+/// Malformed signatures verify as `false` rather than erroring, as before.
+pub fn verify(msg: &str, sig_b64: &str) -> Result<VerifyRes> {
+    ensure_default()?;
+    let Ok(signed) = B64.decode(sig_b64) else { return Ok(VerifyRes { verified: false }) };
+    let opened = crate::verify(DEFAULT_WALLET, &legacy_domain(), &signed)?;
+    Ok(VerifyRes { verified: opened.as_deref() == Some(msg.as_bytes()) })
+}
+
+/// Fresh throwaway public key; the secret is discarded, as `/generate` always did.
+pub fn generate() -> GenerateRes {
+    let kp = qs_crypto::generate_dilithium3();
+    GenerateRes { public_key: B64.encode(kp.public) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_while_the_sled_wallet_is_in_place() {
+        let dir = std::env::temp_dir().join(format!("qs-legacy-db-{}", std::process::id()));
+        let db = dir.join("db");
+        assert!(check_retired_at(&[&db]).is_ok());
+        std::fs::create_dir_all(&db).unwrap();
+        let err = check_retired_at(&[&db]).unwrap_err().to_string();
+        assert!(err.contains("cannot be migrated"), "{err}");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn signatures_verify_under_the_legacy_domain_only() {
+        crate::testing::wallet_dir();
+        std::env::set_var(PASSWORD_ENV, crate::testing::PASSWORD);
+        let signed = sign("hello").unwrap();
+        assert!(verify("hello", &signed.signature).unwrap().verified);
+        assert!(!verify("hullo", &signed.signature).unwrap().verified);
+        assert!(!verify("hello", "not base64!").unwrap().verified);
+
+        let raw = B64.decode(&signed.signature).unwrap();
+        let other = Domain::new("other", "qs-api");
+        assert_eq!(crate::verify(DEFAULT_WALLET, &other, &raw).unwrap(), None);
+    }
+
+    #[test]
+    fn balance_keeps_its_number_next_to_the_exact_balances() {
+        let link = |chain, token: Option<&str>, amount: &str| AddressBalance {
+            link: balances::LinkedAddress { chain, address: "a".into(), token: token.map(str::to_string), label: None, added_at: 0 },
+            amount: Some(amount.parse().unwrap()),
+            fetched_at: Some(0),
+            error: None,
+        };
+        let cached = [
+            link(Chain::Solana, None, "1.500000000"),
+            link(Chain::Solana, None, "0.250000000"),
+            link(Chain::Solana, Some("mint"), "1000.000000"),
+            link(Chain::Evm, None, "2.000000000000000000"),
+        ];
+        assert_eq!(native_sol(&cached), 1.75);
+        assert_eq!(native_sol(&[]), 0.0);
+        let json = serde_json::to_value(BalanceRes { public_key: "pk".into(), balance: 1.75, balances: vec![] }).unwrap();
+        assert_eq!(json, serde_json::json!({ "public_key": "pk", "balance": 1.75, "balances": [] }));
+    }
+}
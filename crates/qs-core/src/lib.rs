@@ -0,0 +1,588 @@
+//! Wallet management shared by every QuantumShield HTTP front-end.
+//!
+//! qs-walletd (axum) serves the canonical routes; the old warp API only
+//! forwards its legacy routes to [`legacy`]. Neither touches keyfiles directly.
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::HashMap, fmt, fs, path::PathBuf};
+
+use qs_crypto::{
+    DilithiumKeypair, Domain, EncryptedKeyfile, KEYFILE_VERSION, KeyfileError, SCHEME, TypedData,
+    address_from_pubkey, attach_kem, decrypt_kem, decrypt_secret, encrypt_secret, generate_dilithium3,
+    sign_domain, sign_typed, verify_domain, verify_typed,
+};
+use qs_utils::{ensure_wallet_dir, read_json, wallet_dir, write_json};
+
+/// Front-ends that only depend on this crate use the same token amounts.
+pub use qs_crypto::{Amount, AmountError};
+
+pub mod approval;
+pub mod audit;
+pub mod backup;
+pub mod balances;
+pub mod intent;
+pub mod legacy;
+pub mod login;
+pub mod multisig;
+pub mod password;
+pub mod policy;
+pub mod qskey;
+pub mod registry;
+pub mod revocation;
+pub mod rotation;
+pub mod solana;
+pub mod throttle;
+
+#[derive(Debug)]
+pub enum CoreError {
+    NotFound(String),
+    UnknownAddress(String),
+    Exists(String),
+    WatchOnly(String),
+    /// The wallet is protected; signing goes through [`approval`].
+    ApprovalRequired(String),
+    UnknownRequest(String),
+    PolicyDenied(policy::PolicyDenial),
+    /// A new password failed the [`password`] rules.
+    WeakPassword(password::WeakPassword),
+    BadPassword,
+    /// Too many wrong passwords; see [`throttle`].
+    Throttled { wallet: String, retry_after_secs: u64 },
+    /// Locked after too many wrong passwords until an operator resets it.
+    LockedOut(String),
+    /// A sign-in answer failed verification (see [`login`]).
+    LoginRejected(String),
+    /// A bridge intent failed verification (see [`intent`]).
+    IntentRejected(String),
+    /// The keyfile opened but its contents are inconsistent (edited or corrupt).
+    Tampered(String),
+    Invalid(String),
+    Io(String),
+}
+
+impl fmt::Display for CoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoreError::NotFound(name) => write!(f, "wallet `{name}` not found"),
+            CoreError::UnknownAddress(addr) => write!(f, "no public key known for address `{addr}`"),
+            CoreError::Exists(name)   => write!(f, "wallet `{name}` already exists"),
+            CoreError::WatchOnly(name) => write!(f, "wallet `{name}` is watch-only and cannot sign"),
+            CoreError::ApprovalRequired(name) => write!(f, "wallet `{name}` requires approval to sign"),
+            CoreError::UnknownRequest(id) => write!(f, "no sign request `{id}`"),
+            CoreError::PolicyDenied(d) => write!(f, "{d}"),
+            CoreError::WeakPassword(w) => write!(f, "{w}"),
+            CoreError::BadPassword    => write!(f, "bad password"),
+            CoreError::Throttled { wallet, retry_after_secs } => {
+                write!(f, "too many failed unlocks for wallet `{wallet}`; retry in {retry_after_secs}s")
+            }
+            CoreError::LoginRejected(msg) => write!(f, "{msg}"),
+            CoreError::IntentRejected(msg) => write!(f, "{msg}"),
+            CoreError::LockedOut(name) => write!(f, "wallet `{name}` is locked after too many failed unlocks"),
+            CoreError::Tampered(msg)  => write!(f, "{msg}"),
+            CoreError::Invalid(msg)   => write!(f, "{msg}"),
+            CoreError::Io(msg)        => write!(f, "{msg}"),
+        }
+    }
+}
+
+impl std::error::Error for CoreError {}
+
+pub type Result<T> = std::result::Result<T, CoreError>;
+
+pub(crate) fn io<E: fmt::Display>(e: E) -> CoreError { CoreError::Io(e.to_string()) }
+pub(crate) fn invalid<E: fmt::Display>(e: E) -> CoreError { CoreError::Invalid(e.to_string()) }
+
+pub(crate) fn now_secs() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WalletInfo {
+    pub name: String,
+    pub address: String,
+    #[serde(default)]
+    pub watch_only: bool,
+}
+
+/// A wallet known only by its public key: it can verify and be displayed or
+/// encrypted to (via the optional KEM key), never sign.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WatchOnlyFile {
+    pub watch_only: bool,
+    pub scheme: String,
+    pub public_hex: String,
+    pub kem_public_hex: Option<String>,
+    pub added_at: u64,
+}
+
+/// On-disk wallet: an encrypted keyfile or a watch-only public key.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum WalletFile {
+    Keyfile(EncryptedKeyfile),
+    WatchOnly(WatchOnlyFile),
+}
+
+impl WalletFile {
+    pub fn public_hex(&self) -> &str {
+        match self {
+            WalletFile::Keyfile(ek) => &ek.public_hex,
+            WalletFile::WatchOnly(w) => &w.public_hex,
+        }
+    }
+
+    /// The scheme a keyfile was written under; v1 keyfiles predate the
+    /// field and were all made for this build's scheme.
+    pub fn scheme(&self) -> &str {
+        match self {
+            WalletFile::Keyfile(ek) if ek.scheme.is_empty() => SCHEME,
+            WalletFile::Keyfile(ek) => &ek.scheme,
+            WalletFile::WatchOnly(w) => &w.scheme,
+        }
+    }
+
+    pub fn is_watch_only(&self) -> bool { matches!(self, WalletFile::WatchOnly(_)) }
+}
+
+/// Names become file names, so keep them to a path-safe alphabet.
+pub fn validate_name(name: &str) -> Result<()> {
+    let ok = !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if ok { Ok(()) } else { Err(CoreError::Invalid(format!("invalid wallet name `{name}`"))) }
+}
+
+pub fn wallet_path(name: &str) -> PathBuf {
+    let mut p = wallet_dir();
+    p.push(format!("{name}.json"));
+    p
+}
+
+pub fn wallet_exists(name: &str) -> bool {
+    validate_name(name).is_ok() && wallet_path(name).exists()
+}
+
+pub fn create_wallet(name: &str, password: &str) -> Result<WalletInfo> {
+    validate_name(name)?;
+    password::check(name, password)?;
+    ensure_wallet_dir().map_err(io)?;
+    if wallet_path(name).exists() {
+        return Err(CoreError::Exists(name.to_string()));
+    }
+    let kp: DilithiumKeypair = generate_dilithium3();
+    let ek: EncryptedKeyfile = encrypt_secret(name, &kp.public, &kp.secret, password);
+    write_json(wallet_path(name), &ek).map_err(io)?;
+    let address = address_from_pubkey(&kp.public);
+    audit::log(Some(name), "create", "ok", json!({ "address": address }))?;
+    Ok(WalletInfo { name: name.to_string(), address, watch_only: false })
+}
+
+pub fn create_watch_only(name: &str, public: &[u8], scheme: &str, kem_public: Option<&[u8]>) -> Result<WalletInfo> {
+    validate_name(name)?;
+    if public.is_empty() || scheme.is_empty() {
+        return Err(invalid("public key and scheme are required"));
+    }
+    ensure_wallet_dir().map_err(io)?;
+    if wallet_path(name).exists() {
+        return Err(CoreError::Exists(name.to_string()));
+    }
+    let file = WatchOnlyFile {
+        watch_only: true,
+        scheme: scheme.to_string(),
+        public_hex: hex::encode(public),
+        kem_public_hex: kem_public.map(hex::encode),
+        added_at: now_secs(),
+    };
+    write_json(wallet_path(name), &file).map_err(io)?;
+    let address = address_from_pubkey(public);
+    audit::log(Some(name), "watch", "ok", json!({ "address": address, "scheme": scheme }))?;
+    Ok(WalletInfo { name: name.to_string(), address, watch_only: true })
+}
+
+pub fn read_wallet(name: &str) -> Result<WalletFile> {
+    if !wallet_exists(name) {
+        return Err(CoreError::NotFound(name.to_string()));
+    }
+    read_json(wallet_path(name)).map_err(io)
+}
+
+/// The encrypted keyfile behind a signing wallet; watch-only wallets have none.
+pub fn load_keyfile(name: &str) -> Result<EncryptedKeyfile> {
+    match read_wallet(name)? {
+        WalletFile::Keyfile(ek) => Ok(ek),
+        WalletFile::WatchOnly(_) => Err(CoreError::WatchOnly(name.to_string())),
+    }
+}
+
+pub fn public_key(name: &str) -> Result<Vec<u8>> {
+    hex::decode(read_wallet(name)?.public_hex()).map_err(invalid)
+}
+
+/// Scheme label and public key of any wallet, signing or watch-only. Needs no password.
+pub fn public_key_info(name: &str) -> Result<(String, Vec<u8>)> {
+    let wallet = read_wallet(name)?;
+    Ok((wallet.scheme().to_string(), hex::decode(wallet.public_hex()).map_err(invalid)?))
+}
+
+/// Public key of a wallet this build can verify for.
+fn verifying_key(name: &str) -> Result<Vec<u8>> {
+    let wallet = read_wallet(name)?;
+    if wallet.scheme() != SCHEME {
+        return Err(CoreError::Invalid(format!("wallet `{name}` uses scheme `{}`, not `{SCHEME}`", wallet.scheme())));
+    }
+    hex::decode(wallet.public_hex()).map_err(invalid)
+}
+
+pub fn address(name: &str) -> Result<String> {
+    Ok(address_from_pubkey(&public_key(name)?))
+}
+
+/// Every wallet in the wallet directory, sorted by name.
+pub fn list_wallets() -> Result<Vec<WalletInfo>> {
+    let mut out = Vec::new();
+    for entry in fs::read_dir(ensure_wallet_dir().map_err(io)?).map_err(io)? {
+        let path = entry.map_err(io)?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") { continue; }
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else { continue };
+        if validate_name(name).is_err() { continue; }
+        // skip files that are not wallets rather than failing the whole listing
+        let Ok(wallet) = read_wallet(name) else { continue };
+        let Ok(public) = hex::decode(wallet.public_hex()) else { continue };
+        out.push(WalletInfo { name: name.to_string(), address: address_from_pubkey(&public), watch_only: wallet.is_watch_only() });
+    }
+    out.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(out)
+}
+
+/// Address -> public key for every local wallet and every registry entry of
+/// this build's scheme; build once and reuse across lookups.
+pub fn address_index() -> Result<HashMap<String, Vec<u8>>> {
+    let mut index = HashMap::new();
+    for e in registry::list()? {
+        if e.scheme == SCHEME {
+            index.insert(e.address.clone(), e.public_key()?);
+        }
+    }
+    // local wallets win over registry entries
+    for w in list_wallets()? {
+        if let Ok(public) = verifying_key(&w.name) {
+            index.insert(w.address, public);
+        }
+    }
+    Ok(index)
+}
+
+/// Resolves a signer given as hex public key or as `QS…` address of a known key.
+pub fn resolve_signer(
+    public_key_hex: Option<&str>,
+    address: Option<&str>,
+    index: &HashMap<String, Vec<u8>>,
+) -> Result<Vec<u8>> {
+    match (public_key_hex, address) {
+        (Some(pk), None) => hex::decode(pk).map_err(invalid),
+        (None, Some(addr)) => index.get(addr).cloned().ok_or_else(|| CoreError::UnknownAddress(addr.to_string())),
+        _ => Err(invalid("give exactly one of public_key_hex or address")),
+    }
+}
+
+pub(crate) fn check_domain(domain: &Domain) -> Result<()> {
+    if domain.is_valid() { Ok(()) } else { Err(CoreError::Invalid(format!("invalid signing domain `{}`", domain.tag))) }
+}
+
+/// [`check_domain`], and refuses the domains reserved for statements the
+/// wallet only makes through their own routes (rotation, revocation, sign-in,
+/// bridge intents, the legacy API).
+pub(crate) fn check_signable(domain: &Domain) -> Result<()> {
+    check_domain(domain)?;
+    if domain.is_reserved() || *domain == legacy::legacy_domain() {
+        return Err(CoreError::Invalid(format!("signing domain `{}`/`{}` is reserved", domain.tag, domain.app_id)));
+    }
+    Ok(())
+}
+
+pub(crate) fn keyfile_err(name: &str, e: KeyfileError) -> CoreError {
+    match e {
+        KeyfileError::BadPassword => CoreError::BadPassword,
+        _ => CoreError::Tampered(format!("keyfile for wallet `{name}` failed its integrity check: {e}")),
+    }
+}
+
+/// Decrypts wallet `name`, checking the secret against the stored public key.
+/// Keyfiles older than v2 are rewritten as v2 under the same password, and
+/// the rewrite is audited.
+/// Every attempt, failed or not, goes to the audit log. Repeated failures
+/// are slowed down and may lock the wallet; see [`throttle`].
+pub fn unlock(name: &str, password: &str) -> Result<(EncryptedKeyfile, Vec<u8>)> {
+    let ek = load_keyfile(name)?;
+    if let Err(e) = throttle::begin(name) {
+        let outcome = if matches!(e, CoreError::LockedOut(_)) { "locked_out" } else { "throttled" };
+        audit::log(Some(name), "unlock", outcome, Value::Null)?;
+        return Err(e);
+    }
+    let secret = match decrypt_secret(&ek, name, password) {
+        Ok(secret) => secret,
+        Err(e) => {
+            let err = keyfile_err(name, e);
+            let outcome = if matches!(err, CoreError::BadPassword) { "bad_password" } else { "tampered" };
+            audit::log(Some(name), "unlock", outcome, Value::Null)?;
+            return Err(err);
+        }
+    };
+    throttle::succeeded(name)?;
+    audit::log(Some(name), "unlock", "ok", Value::Null)?;
+    if ek.v >= KEYFILE_VERSION {
+        return Ok((ek, secret));
+    }
+    let upgraded = reseal(name, &ek, &secret, password, password)?;
+    write_json(wallet_path(name), &upgraded).map_err(io)?;
+    audit::log(Some(name), "upgrade_keyfile", "ok", json!({ "from": ek.v, "to": upgraded.v }))?;
+    Ok((upgraded, secret))
+}
+
+/// A current-version keyfile for `secret` under `new_password`, carrying over
+/// the KEM key (opened with `old_password`) if `ek` has one.
+pub(crate) fn reseal(name: &str, ek: &EncryptedKeyfile, secret: &[u8], old_password: &str, new_password: &str) -> Result<EncryptedKeyfile> {
+    let public = hex::decode(&ek.public_hex).map_err(invalid)?;
+    let mut out = encrypt_secret(name, &public, secret, new_password);
+    if ek.kem.is_some() {
+        let (scheme, kem_public, kem_secret) = decrypt_kem(ek, name, old_password)
+            .ok_or_else(|| keyfile_err(name, KeyfileError::Malformed))?;
+        attach_kem(&mut out, name, &scheme, &kem_public, &kem_secret, new_password)
+            .ok_or_else(|| invalid("could not seal KEM key"))?;
+    }
+    Ok(out)
+}
+
+/// Re-encrypts wallet `name` under `new_password`, which must pass the
+/// [`password`] rules. The key itself does not change.
+pub fn change_password(name: &str, old_password: &str, new_password: &str) -> Result<()> {
+    password::check(name, new_password)?;
+    let (ek, secret) = unlock(name, old_password)?;
+    let rekeyed = reseal(name, &ek, &secret, old_password, new_password)?;
+    write_json(wallet_path(name), &rekeyed).map_err(io)?;
+    audit::log(Some(name), "change_password", "ok", Value::Null)
+}
+
+pub(crate) fn check_unprotected(name: &str) -> Result<()> {
+    if approval::requires_approval(name)? { Err(CoreError::ApprovalRequired(name.to_string())) } else { Ok(()) }
+}
+
+/// Unlocks the wallet and signs `msg` under `domain`; wallets never sign raw bytes.
+/// Protected wallets refuse; queue the request with [`approval::submit`] instead.
+pub fn sign(name: &str, password: &str, domain: &Domain, msg: &[u8]) -> Result<Vec<u8>> {
+    check_signable(domain)?;
+    check_unprotected(name)?;
+    sign_unchecked(name, password, domain, msg)
+}
+
+pub(crate) fn sign_unchecked(name: &str, password: &str, domain: &Domain, msg: &[u8]) -> Result<Vec<u8>> {
+    check_domain(domain)?;
+    let (_, secret) = unlock(name, password)?;
+    policy::enforce(name, &policy::SignContext::raw(domain, msg))?;
+    let signed = sign_domain(&secret, domain, msg).ok_or_else(|| invalid("signing failed"))?;
+    audit::log(Some(name), "sign", "ok", json!({ "domain": domain, "message_len": msg.len() }))?;
+    Ok(signed)
+}
+
+/// Opens `signed` with the wallet's public key; `None` means the signature is
+/// invalid or was made for a different domain.
+pub fn verify(name: &str, domain: &Domain, signed: &[u8]) -> Result<Option<Vec<u8>>> {
+    check_domain(domain)?;
+    Ok(verify_domain(&verifying_key(name)?, domain, signed))
+}
+
+/// Signs structured data; returns the signing hash so callers can show or log it.
+pub fn sign_typed_data(name: &str, password: &str, data: &TypedData) -> Result<([u8; 32], Vec<u8>)> {
+    // reject malformed payloads before paying for the KDF
+    check_signable(&data.domain)?;
+    data.signing_hash().map_err(invalid)?;
+    check_unprotected(name)?;
+    let (_, secret) = unlock(name, password)?;
+    policy::enforce(name, &policy::SignContext::typed(&data.domain, &data.message))?;
+    let (hash, signed) = sign_typed(&secret, data).map_err(invalid)?;
+    audit::log(Some(name), "sign_typed", "ok", json!({ "domain": data.domain, "signing_hash": hex::encode(hash) }))?;
+    Ok((hash, signed))
+}
+
+pub fn verify_typed_data(name: &str, data: &TypedData, signed: &[u8]) -> Result<bool> {
+    verify_typed(&verifying_key(name)?, data, signed).map_err(invalid)
+}
+
+/// Shared by this crate's tests and, through the `testing` feature, the front-ends'.
+#[cfg(any(test, feature = "testing"))]
+pub mod testing {
+    use std::sync::{atomic::{AtomicUsize, Ordering}, Once};
+
+    pub const PASSWORD: &str = "Gl4ss-Orbit-Lantern-77";
+
+    /// Points QS_WALLET_DIR at a fresh directory shared by this test binary.
+    pub fn wallet_dir() {
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            let dir = std::env::temp_dir().join(format!("qs-core-tests-{}", std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::env::set_var(qs_utils::WALLET_DIR_ENV, dir);
+        });
+    }
+
+    /// A wallet name no other test in this binary uses.
+    pub fn name(prefix: &str) -> String {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        format!("{prefix}-{}", NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// A fresh wallet with [`PASSWORD`].
+    pub fn wallet(prefix: &str) -> String {
+        wallet_dir();
+        let name = name(prefix);
+        crate::create_wallet(&name, PASSWORD).expect("create test wallet");
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, PASSWORD};
+
+    #[test]
+    fn create_sign_and_verify() {
+        let name = testing::wallet("core");
+        assert!(matches!(create_wallet(&name, PASSWORD), Err(CoreError::Exists(_))));
+        assert!(list_wallets().unwrap().iter().any(|w| w.name == name && w.address == address(&name).unwrap()));
+
+        let domain = Domain::new("test", "qs-core");
+        let signed = sign(&name, PASSWORD, &domain, b"hello").unwrap();
+        assert_eq!(verify(&name, &domain, &signed).unwrap().as_deref(), Some(&b"hello"[..]));
+        assert_eq!(verify(&name, &Domain::new("test", "other"), &signed).unwrap(), None);
+        assert!(matches!(sign(&name, "wrong password", &domain, b"hello"), Err(CoreError::BadPassword)));
+    }
+
+    #[test]
+    fn generic_signing_refuses_reserved_domains() {
+        let name = testing::wallet("reserved");
+        let reserved = [
+            qs_crypto::rotation_domain(),
+            qs_crypto::revocation_domain(),
+            qs_crypto::recovery_domain(),
+            qs_crypto::audit_domain(),
+            qs_crypto::intent_domain(),
+            Domain::new("sign-in", "example.com"),
+            legacy::legacy_domain(),
+        ];
+        for domain in reserved {
+            let err = sign(&name, PASSWORD, &domain, b"forged").unwrap_err();
+            assert!(err.to_string().contains("reserved"), "{domain:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn resolves_signers_by_key_or_address() {
+        let name = testing::wallet("resolve");
+        let public = verifying_key(&name).unwrap();
+        let addr = address(&name).unwrap();
+
+        // a hex key needs no index
+        let empty = HashMap::new();
+        assert_eq!(resolve_signer(Some(&hex::encode(&public)), None, &empty).unwrap(), public);
+        assert!(matches!(resolve_signer(None, Some(&addr), &empty), Err(CoreError::UnknownAddress(_))));
+
+        let index = address_index().unwrap();
+        assert_eq!(resolve_signer(None, Some(&addr), &index).unwrap(), public);
+        assert!(resolve_signer(Some("zz"), None, &index).is_err());
+        assert!(resolve_signer(Some(&hex::encode(&public)), Some(&addr), &index).is_err());
+        assert!(resolve_signer(None, None, &index).is_err());
+    }
+
+    #[test]
+    fn keyfiles_report_their_own_scheme() {
+        let name = testing::wallet("scheme");
+        assert_eq!(read_wallet(&name).unwrap().scheme(), SCHEME);
+
+        // a keyfile written for another scheme is not verified as this one
+        let mut ek = load_keyfile(&name).unwrap();
+        ek.scheme = "other-scheme".into();
+        write_json(wallet_path(&name), &ek).unwrap();
+        assert_eq!(public_key_info(&name).unwrap().0, "other-scheme");
+        assert!(verify(&name, &Domain::new("test", "qs-core"), b"").is_err());
+
+        // v1 keyfiles have no scheme and were made by this build
+        ek.scheme.clear();
+        write_json(wallet_path(&name), &ek).unwrap();
+        assert_eq!(read_wallet(&name).unwrap().scheme(), SCHEME);
+    }
+
+    #[test]
+    fn watch_only_wallets_verify_but_never_sign() {
+        let signer = testing::wallet("signer");
+        let public = public_key(&signer).unwrap();
+        let name = testing::name("watch");
+        let info = create_watch_only(&name, &public, SCHEME, None).unwrap();
+        assert!(info.watch_only);
+        assert_eq!(info.address, address(&signer).unwrap());
+        assert!(matches!(create_watch_only(&name, &public, SCHEME, None), Err(CoreError::Exists(_))));
+        assert!(create_watch_only(&testing::name("watch"), &[], SCHEME, None).is_err());
+
+        let domain = Domain::new("test", "qs-core");
+        let signed = sign(&signer, PASSWORD, &domain, b"watched").unwrap();
+        assert_eq!(verify(&name, &domain, &signed).unwrap().as_deref(), Some(&b"watched"[..]));
+        assert!(matches!(sign(&name, PASSWORD, &domain, b"x"), Err(CoreError::WatchOnly(_))));
+        assert!(matches!(load_keyfile(&name), Err(CoreError::WatchOnly(_))));
+
+        // keys of another scheme are kept but not verified
+        let foreign = testing::name("watch");
+        create_watch_only(&foreign, &public, "ML-DSA-87", None).unwrap();
+        assert_eq!(public_key_info(&foreign).unwrap(), ("ML-DSA-87".to_string(), public));
+        assert!(verify(&foreign, &domain, &signed).is_err());
+    }
+
+    /// Rewrites wallet `name` as a v1 keyfile: no scheme, no associated data.
+    fn downgrade_to_v1(name: &str) {
+        use aes_gcm::{aead::{Aead, generic_array::GenericArray}, Aes256Gcm, KeyInit};
+        use base64::{engine::general_purpose::STANDARD as B64, Engine};
+
+        let (mut ek, secret) = unlock(name, PASSWORD).unwrap();
+        let salt = B64.decode(&ek.salt_b64).unwrap();
+        let kdf = ek.kdf_params;
+        let params = argon2::Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, None).unwrap();
+        let mut key = [0u8; 32];
+        argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+            .hash_password_into(PASSWORD.as_bytes(), &salt, &mut key)
+            .unwrap();
+        let nonce = B64.decode(&ek.nonce_b64).unwrap();
+        let ct = Aes256Gcm::new(GenericArray::from_slice(&key)).encrypt(GenericArray::from_slice(&nonce), secret.as_slice()).unwrap();
+        ek.ct_b64 = B64.encode(ct);
+        ek.v = 1;
+        ek.scheme.clear();
+        write_json(wallet_path(name), &ek).unwrap();
+    }
+
+    #[test]
+    fn unlock_upgrades_v1_keyfiles_and_audits_it() {
+        let name = testing::wallet("v1");
+        downgrade_to_v1(&name);
+        assert_eq!(load_keyfile(&name).unwrap().v, 1);
+
+        let (ek, _) = unlock(&name, PASSWORD).unwrap();
+        assert_eq!((ek.v, ek.scheme.as_str()), (KEYFILE_VERSION, SCHEME));
+        assert_eq!(load_keyfile(&name).unwrap().v, KEYFILE_VERSION);
+        let upgrades: Vec<_> = audit::read_all().unwrap().into_iter()
+            .filter(|e| e.event.wallet.as_deref() == Some(name.as_str()) && e.event.action == "upgrade_keyfile")
+            .collect();
+        assert_eq!(upgrades.len(), 1);
+        assert_eq!(upgrades[0].event.detail, json!({ "from": 1, "to": KEYFILE_VERSION }));
+
+        // v2 now: the stored name is bound, so a copy under another name does not open
+        let copy = testing::name("v1");
+        std::fs::copy(wallet_path(&name), wallet_path(&copy)).unwrap();
+        assert!(matches!(unlock(&copy, PASSWORD), Err(CoreError::BadPassword)));
+    }
+
+    #[test]
+    fn unknown_and_malformed_names() {
+        testing::wallet_dir();
+        assert!(matches!(read_wallet(&testing::name("missing")), Err(CoreError::NotFound(_))));
+        assert!(validate_name("../escape").is_err());
+        assert!(!wallet_exists("../escape"));
+    }
+}
@@ -0,0 +1,169 @@
+//! Sign-in with QuantumShield: both sides of the challenge-response login.
+//!
+//! As a service, the daemon issues challenges ([`challenge`]) and checks the
+//! answers ([`verify`]); issued nonces are kept in `.login-nonces.json` until
+//! used or expired, so a login works once and survives a restart in between.
+//! As a wallet, [`sign_in`] answers a challenge from any service.
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{collections::BTreeMap, path::PathBuf, sync::Mutex};
+
+use qs_crypto::{LoginChallenge, LoginError, NonceStore, SignedLogin, address_from_pubkey, sign_login, verify_login};
+use qs_utils::{ensure_wallet_dir, read_json, wallet_dir, write_json};
+
+use crate::{audit, check_unprotected, invalid, io, now_secs, policy, revocation, unlock, CoreError, Result};
+
+pub const DEFAULT_TTL_SECS: u64 = 5 * 60;
+pub const MAX_TTL_SECS: u64 = 60 * 60;
+/// Outstanding challenges; issuing more fails until some are used or expire.
+pub const MAX_PENDING: usize = 10_000;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Issued { domain: String, uri: String, expires_at: u64 }
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LoginOutcome {
+    pub address: String,
+    pub public_key_hex: String,
+    pub domain: String,
+    pub uri: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Store { nonces: BTreeMap<String, Issued> }
+
+impl NonceStore for Store {
+    fn consume(&mut self, nonce: &str, now: u64) -> bool {
+        self.nonces.remove(nonce).is_some_and(|i| i.expires_at > now)
+    }
+}
+
+fn store_path() -> PathBuf {
+    wallet_dir().join(".login-nonces.json")
+}
+
+fn load() -> Result<Store> {
+    let path = store_path();
+    if !path.exists() {
+        return Ok(Store::default());
+    }
+    read_json(path).map_err(io)
+}
+
+fn save(store: &Store) -> Result<()> {
+    ensure_wallet_dir().map_err(io)?;
+    write_json(store_path(), store).map_err(io)
+}
+
+fn rejected(e: LoginError) -> CoreError {
+    match e {
+        LoginError::Malformed(_) => CoreError::Invalid(e.to_string()),
+        _ => CoreError::LoginRejected(e.to_string()),
+    }
+}
+
+/// Issues a challenge for logging into `uri` on `domain`.
+pub fn challenge(domain: &str, uri: &str, statement: Option<String>, ttl_secs: Option<u64>) -> Result<LoginChallenge> {
+    let ttl = ttl_secs.unwrap_or(DEFAULT_TTL_SECS).clamp(1, MAX_TTL_SECS);
+    let now = now_secs();
+    let c = LoginChallenge::new(domain, uri, statement, now, ttl).map_err(rejected)?;
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    store.nonces.retain(|_, i| i.expires_at > now);
+    if store.nonces.len() >= MAX_PENDING {
+        return Err(CoreError::Invalid("too many outstanding sign-in challenges; try again later".into()));
+    }
+    store.nonces.insert(c.nonce.clone(), Issued { domain: c.domain.clone(), uri: c.uri.clone(), expires_at: c.expires_at });
+    save(&store)?;
+    Ok(c)
+}
+
+/// Checks a login answering one of our challenges and uses up its nonce.
+/// Logins by revoked keys are refused.
+pub fn verify(login: &SignedLogin) -> Result<LoginOutcome> {
+    let c = &login.challenge;
+    let public = {
+        let _guard = LOCK.lock().map_err(io)?;
+        let mut store = load()?;
+        // the challenge must be one we issued, unchanged
+        let issued = store.nonces.get(&c.nonce).cloned().ok_or_else(|| rejected(LoginError::Replayed))?;
+        if issued.domain != c.domain || issued.uri != c.uri || issued.expires_at != c.expires_at {
+            return Err(CoreError::LoginRejected("challenge does not match the one issued".into()));
+        }
+        let result = verify_login(login, &issued.domain, now_secs(), &mut store);
+        save(&store)?;
+        result.map_err(rejected)?
+    };
+    if revocation::status(&public)?.is_some() {
+        return Err(CoreError::LoginRejected(format!("key {} is revoked", login.address)));
+    }
+    audit::log(None, "login", "ok", json!({ "address": login.address, "domain": c.domain, "uri": c.uri }))?;
+    Ok(LoginOutcome {
+        address: login.address.clone(),
+        public_key_hex: login.public_key_hex.clone(),
+        domain: c.domain.clone(),
+        uri: c.uri.clone(),
+    })
+}
+
+/// Answers a service's `challenge` with wallet `name`.
+pub fn sign_in(name: &str, password: &str, challenge: &LoginChallenge) -> Result<SignedLogin> {
+    challenge.validate().map_err(rejected)?;
+    check_unprotected(name)?;
+    let (ek, secret) = unlock(name, password)?;
+    let public = hex::decode(&ek.public_hex).map_err(invalid)?;
+    let domain = challenge.signing_domain();
+    let message = challenge.message(&address_from_pubkey(&public));
+    policy::enforce(name, &policy::SignContext::raw(&domain, message.as_bytes()))?;
+    let login = sign_login(&public, &secret, challenge, now_secs()).map_err(rejected)?;
+    audit::log(Some(name), "sign_in", "ok", json!({ "domain": challenge.domain, "uri": challenge.uri }))?;
+    Ok(login)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, PASSWORD};
+
+    #[test]
+    fn issued_challenges_log_in_once() {
+        let name = testing::wallet("login");
+        let c = challenge("app.example", "https://app.example/login", None, None).unwrap();
+        assert_eq!(c.expires_at - c.issued_at, DEFAULT_TTL_SECS);
+        let login = sign_in(&name, PASSWORD, &c).unwrap();
+        let outcome = verify(&login).unwrap();
+        assert_eq!(outcome.public_key_hex, hex::encode(crate::public_key(&name).unwrap()));
+        assert_eq!(outcome.uri, "https://app.example/login");
+        assert!(matches!(verify(&login), Err(CoreError::LoginRejected(_))));
+        assert!(matches!(sign_in(&name, "wrong password", &c), Err(CoreError::BadPassword)));
+    }
+
+    #[test]
+    fn only_our_unchanged_challenges_count() {
+        let name = testing::wallet("login");
+        let c = challenge("app.example", "https://app.example/login", None, Some(10 * MAX_TTL_SECS)).unwrap();
+        assert_eq!(c.expires_at - c.issued_at, MAX_TTL_SECS);
+
+        // a longer-lived copy, signed and all, is still not what we issued
+        let mut stretched = c.clone();
+        stretched.expires_at += 60;
+        let forged = sign_in(&name, PASSWORD, &stretched).unwrap();
+        assert!(matches!(verify(&forged), Err(CoreError::LoginRejected(_))));
+
+        let foreign = LoginChallenge::new("app.example", "https://app.example/login", None, now_secs(), 60).unwrap();
+        assert!(matches!(verify(&sign_in(&name, PASSWORD, &foreign).unwrap()), Err(CoreError::LoginRejected(_))));
+
+        assert!(verify(&sign_in(&name, PASSWORD, &c).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn revoked_keys_cannot_log_in() {
+        let name = testing::wallet("login");
+        let c = challenge("app.example", "https://app.example/login", None, None).unwrap();
+        let login = sign_in(&name, PASSWORD, &c).unwrap();
+        revocation::revoke_with_wallet(&name, PASSWORD, None, "lost").unwrap();
+        assert!(matches!(verify(&login), Err(CoreError::LoginRejected(m)) if m.contains("revoked")));
+    }
+}
@@ -0,0 +1,197 @@
+//! M-of-N multisig policies and the partial signatures collected for them.
+//!
+//! Policies and open bundles are kept in `.multisig.json`. A bundle is
+//! identified by its policy, domain and message, so every member signing the
+//! same thing lands in the same bundle. Signatures from revoked keys are kept
+//! but do not count towards the threshold.
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha3::{Digest, Sha3_256};
+use std::{collections::BTreeMap, path::PathBuf, sync::Mutex};
+
+use qs_crypto::{Domain, MultisigBundle, MultisigPolicy, address_from_pubkey, domain_message, sign_multisig};
+use qs_utils::{ensure_wallet_dir, read_json, wallet_dir, write_json};
+
+use crate::{audit, check_domain, check_signable, check_unprotected, invalid, io, now_secs, policy, revocation, unlock, CoreError, Result};
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StoredPolicy {
+    pub address: String,
+    pub policy: MultisigPolicy,
+    pub label: Option<String>,
+    pub created_at: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MultisigStatus {
+    pub policy_address: String,
+    pub bundle_id: String,
+    pub threshold: u16,
+    pub members: usize,
+    pub signers: Vec<String>, // addresses of members whose signatures count
+    pub met: bool,
+    pub bundle: MultisigBundle,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Store {
+    policies: BTreeMap<String, StoredPolicy>,
+    bundles: BTreeMap<String, MultisigBundle>,
+}
+
+fn store_path() -> PathBuf {
+    wallet_dir().join(".multisig.json")
+}
+
+fn load() -> Result<Store> {
+    let path = store_path();
+    if !path.exists() {
+        return Ok(Store::default());
+    }
+    read_json(path).map_err(io)
+}
+
+fn save(store: &Store) -> Result<()> {
+    ensure_wallet_dir().map_err(io)?;
+    write_json(store_path(), store).map_err(io)
+}
+
+/// Stable id of the bundle for `message` under `domain` for policy `address`.
+pub fn bundle_id(address: &str, domain: &Domain, message: &[u8]) -> Result<String> {
+    let signed = domain_message(domain, message).ok_or_else(|| invalid("invalid signing domain"))?;
+    let mut h = Sha3_256::new();
+    h.update(address.as_bytes());
+    h.update(&signed);
+    Ok(hex::encode(h.finalize()))
+}
+
+/// Registers a policy; registering the same members and threshold again
+/// returns the existing one.
+pub fn create_policy(threshold: u16, members: &[Vec<u8>], label: Option<String>) -> Result<StoredPolicy> {
+    let policy = MultisigPolicy::new(threshold, members).map_err(invalid)?;
+    let address = policy.address();
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    if let Some(existing) = store.policies.get(&address) {
+        return Ok(existing.clone());
+    }
+    let stored = StoredPolicy { address: address.clone(), policy, label, created_at: now_secs() };
+    store.policies.insert(address, stored.clone());
+    save(&store)?;
+    Ok(stored)
+}
+
+pub fn list_policies() -> Result<Vec<StoredPolicy>> {
+    let _guard = LOCK.lock().map_err(io)?;
+    Ok(load()?.policies.into_values().collect())
+}
+
+pub fn get_policy(address: &str) -> Result<StoredPolicy> {
+    let _guard = LOCK.lock().map_err(io)?;
+    load()?.policies.remove(address).ok_or_else(|| CoreError::UnknownAddress(address.to_string()))
+}
+
+fn status_of(policy: &MultisigPolicy, bundle: MultisigBundle, id: String) -> Result<MultisigStatus> {
+    let revoked = revocation::revoked()?;
+    let signers: Vec<String> = bundle.valid_signers(policy).map_err(invalid)?
+        .iter()
+        .map(|k| address_from_pubkey(k))
+        .filter(|a| !revoked.contains_key(a))
+        .collect();
+    Ok(MultisigStatus {
+        policy_address: bundle.policy_address.clone(),
+        bundle_id: id,
+        threshold: policy.threshold,
+        members: policy.members_hex.len(),
+        met: signers.len() >= usize::from(policy.threshold),
+        signers,
+        bundle,
+    })
+}
+
+/// Adds a member's partial signature to the bundle for (`domain`, `message`).
+pub fn add_signature(address: &str, domain: &Domain, message: &[u8], public: &[u8], signed: &[u8]) -> Result<MultisigStatus> {
+    check_domain(domain)?;
+    let id = bundle_id(address, domain, message)?;
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    let policy = store.policies.get(address).ok_or_else(|| CoreError::UnknownAddress(address.to_string()))?.policy.clone();
+    let bundle = store.bundles.entry(id.clone()).or_insert_with(|| MultisigBundle::new(&policy, domain.clone(), message));
+    bundle.add(&policy, public, signed).map_err(invalid)?;
+    let bundle = bundle.clone();
+    save(&store)?;
+    status_of(&policy, bundle, id)
+}
+
+/// Signs with local wallet `name` as one of the policy's members.
+pub fn sign_with_wallet(address: &str, name: &str, password: &str, domain: &Domain, message: &[u8]) -> Result<MultisigStatus> {
+    check_signable(domain)?;
+    check_unprotected(name)?;
+    let (ek, secret) = unlock(name, password)?;
+    policy::enforce(name, &policy::SignContext::raw(domain, message))?;
+    let public = hex::decode(&ek.public_hex).map_err(invalid)?;
+    let signed = sign_multisig(&secret, address, domain, message).ok_or_else(|| invalid("signing failed"))?;
+    audit::log(Some(name), "multisig_sign", "ok", json!({ "policy_address": address, "domain": domain }))?;
+    add_signature(address, domain, message, &public, &signed)
+}
+
+/// Progress of the stored bundle for (`domain`, `message`); empty if nobody signed yet.
+pub fn status(address: &str, domain: &Domain, message: &[u8]) -> Result<MultisigStatus> {
+    check_domain(domain)?;
+    let id = bundle_id(address, domain, message)?;
+    let policy = get_policy(address)?.policy;
+    let bundle = {
+        let _guard = LOCK.lock().map_err(io)?;
+        load()?.bundles.remove(&id)
+    };
+    let bundle = bundle.unwrap_or_else(|| MultisigBundle::new(&policy, domain.clone(), message));
+    status_of(&policy, bundle, id)
+}
+
+/// Checks a bundle assembled elsewhere against a policy given with it.
+pub fn check_bundle(policy: &MultisigPolicy, bundle: MultisigBundle) -> Result<MultisigStatus> {
+    policy.validate().map_err(invalid)?;
+    check_domain(&bundle.domain)?;
+    let message = hex::decode(&bundle.message_hex).map_err(invalid)?;
+    let id = bundle_id(&bundle.policy_address, &bundle.domain, &message)?;
+    status_of(policy, bundle, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, PASSWORD};
+    use crate::{public_key, revocation::revoke_with_wallet};
+
+    #[test]
+    fn wallets_reach_the_threshold() {
+        let names: Vec<_> = (0..3).map(|_| testing::wallet("member")).collect();
+        let members: Vec<_> = names.iter().map(|n| public_key(n).unwrap()).collect();
+        let stored = create_policy(2, &members, Some("treasury".into())).unwrap();
+        assert_eq!(create_policy(2, &members, None).unwrap().label.as_deref(), Some("treasury"));
+
+        let domain = Domain::new("payout", "treasury");
+        let first = sign_with_wallet(&stored.address, &names[0], PASSWORD, &domain, b"pay 10").unwrap();
+        assert!(!first.met);
+        let second = sign_with_wallet(&stored.address, &names[1], PASSWORD, &domain, b"pay 10").unwrap();
+        assert!(second.met);
+        assert_eq!(second.bundle_id, first.bundle_id);
+        assert_eq!(status(&stored.address, &domain, b"pay 10").unwrap().signers.len(), 2);
+        assert_eq!(check_bundle(&stored.policy, second.bundle.clone()).unwrap().signers, second.signers);
+
+        // a revoked member stops counting
+        revoke_with_wallet(&names[1], PASSWORD, None, "compromised").unwrap();
+        assert!(!status(&stored.address, &domain, b"pay 10").unwrap().met);
+    }
+
+    #[test]
+    fn members_cannot_sign_reserved_domains() {
+        let name = testing::wallet("member");
+        let stored = create_policy(1, &[public_key(&name).unwrap()], None).unwrap();
+        let err = sign_with_wallet(&stored.address, &name, PASSWORD, &qs_crypto::multisig_domain(), b"x").unwrap_err();
+        assert!(err.to_string().contains("reserved"), "{err}");
+        assert!(matches!(sign_with_wallet("QSnope", &name, PASSWORD, &Domain::new("a", "b"), b"x"), Err(CoreError::UnknownAddress(_))));
+    }
+}
@@ -0,0 +1,147 @@
+//! Password strength rules for every password that will protect a key: new
+//! wallets, imports, password changes and re-keyed restores.
+//!
+//! The rules come from the environment so they cannot be loosened over HTTP:
+//! `QS_PASSWORD_MIN_LENGTH` (characters, default 12),
+//! `QS_PASSWORD_MIN_ENTROPY_BITS` (default 50) and `QS_PASSWORD_ALLOW_COMMON=1`
+//! to skip the bundled list of common passwords.
+use serde::Serialize;
+use std::{collections::HashSet, fmt, sync::OnceLock};
+
+pub const MIN_LENGTH_ENV: &str = "QS_PASSWORD_MIN_LENGTH";
+pub const MIN_ENTROPY_ENV: &str = "QS_PASSWORD_MIN_ENTROPY_BITS";
+pub const ALLOW_COMMON_ENV: &str = "QS_PASSWORD_ALLOW_COMMON";
+
+const COMMON: &str = include_str!("common_passwords.txt");
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub min_entropy_bits: f64,
+    pub reject_common: bool,
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy { min_length: 12, min_entropy_bits: 50.0, reject_common: true }
+    }
+}
+
+/// Every rule a password broke, in words meant for the user.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct WeakPassword { pub problems: Vec<String> }
+
+impl fmt::Display for WeakPassword {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "password too weak: {}", self.problems.join("; "))
+    }
+}
+
+fn common() -> &'static HashSet<&'static str> {
+    static SET: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    SET.get_or_init(|| COMMON.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')).collect())
+}
+
+/// Rough entropy in bits: each character is worth log2 of the alphabet the
+/// password draws from, except that repeats and runs ("aaa", "abc", "321")
+/// are worth a single bit each.
+pub fn estimate_entropy(password: &str) -> f64 {
+    let chars: Vec<char> = password.chars().collect();
+    if chars.is_empty() {
+        return 0.0;
+    }
+    let mut pool = 0u32;
+    if chars.iter().any(char::is_ascii_lowercase) { pool += 26; }
+    if chars.iter().any(char::is_ascii_uppercase) { pool += 26; }
+    if chars.iter().any(char::is_ascii_digit) { pool += 10; }
+    if chars.iter().any(|c| c.is_ascii_punctuation() || *c == ' ') { pool += 33; }
+    if chars.iter().any(|c| !c.is_ascii()) { pool += 100; }
+    let per_char = f64::from(pool.max(1)).log2();
+    chars.iter().enumerate().map(|(i, c)| {
+        let step = i.checked_sub(1).map(|p| (*c as i64) - (chars[p] as i64));
+        if matches!(step, Some(-1..=1)) { 1.0 } else { per_char }
+    }).sum()
+}
+
+impl PasswordPolicy {
+    /// The policy configured in the environment; unset or unparseable values keep the defaults.
+    pub fn from_env() -> Self {
+        let d = PasswordPolicy::default();
+        let var = |k: &str| std::env::var(k).ok();
+        PasswordPolicy {
+            min_length: var(MIN_LENGTH_ENV).and_then(|v| v.parse().ok()).unwrap_or(d.min_length),
+            min_entropy_bits: var(MIN_ENTROPY_ENV).and_then(|v| v.parse().ok()).filter(|b: &f64| b.is_finite()).unwrap_or(d.min_entropy_bits),
+            reject_common: var(ALLOW_COMMON_ENV).is_none_or(|v| v != "1"),
+        }
+    }
+
+    /// Checks `password` for wallet `name` (which it must not contain).
+    pub fn check(&self, name: &str, password: &str) -> Result<(), WeakPassword> {
+        let mut problems = Vec::new();
+        let len = password.chars().count();
+        if len < self.min_length {
+            problems.push(format!("use at least {} characters (this one has {len})", self.min_length));
+        }
+        let lower = password.to_lowercase();
+        let base = lower.trim_end_matches(|c: char| c.is_ascii_digit() || c.is_ascii_punctuation());
+        if self.reject_common && (common().contains(lower.as_str()) || common().contains(base)) {
+            problems.push("it is one of the most common passwords".to_string());
+        }
+        if name.chars().count() >= 3 && lower.contains(&name.to_lowercase()) {
+            problems.push("it contains the wallet name".to_string());
+        }
+        let bits = estimate_entropy(password);
+        if bits < self.min_entropy_bits {
+            problems.push(format!(
+                "it is too predictable (about {bits:.0} bits, need {:.0}); mix cases, digits and symbols or add more words",
+                self.min_entropy_bits
+            ));
+        }
+        if problems.is_empty() { Ok(()) } else { Err(WeakPassword { problems }) }
+    }
+}
+
+/// Checks `password` against the configured policy.
+pub fn check(name: &str, password: &str) -> crate::Result<()> {
+    PasswordPolicy::from_env().check(name, password).map_err(crate::CoreError::WeakPassword)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(name: &str, password: &str) -> Vec<String> {
+        PasswordPolicy::default().check(name, password).err().map(|w| w.problems).unwrap_or_default()
+    }
+
+    #[test]
+    fn entropy_discounts_runs_and_repeats() {
+        assert_eq!(estimate_entropy(""), 0.0);
+        assert_eq!(estimate_entropy("aaaa"), 26f64.log2() + 3.0);
+        assert_eq!(estimate_entropy("abcd"), estimate_entropy("aaaa"));
+        assert_eq!(estimate_entropy("4321"), 10f64.log2() + 3.0);
+        assert!(estimate_entropy("aZ9!") > estimate_entropy("azqm"));
+        assert!(estimate_entropy("Gl4ss-Orbit-Lantern-77") > 100.0);
+    }
+
+    #[test]
+    fn weak_passwords_say_why() {
+        assert!(problems("wallet", "Gl4ss-Orbit-Lantern-77").is_empty());
+        assert!(problems("wallet", "short").iter().any(|p| p.contains("at least 12")));
+        assert!(problems("wallet", "password123!").iter().any(|p| p.contains("common")));
+        assert!(problems("Treasury", "my-treasury-Key-9042").iter().any(|p| p.contains("wallet name")));
+        // names under three characters are not looked for
+        assert!(problems("ab", "Gl4ss-Orbit-Lantern-77ab").is_empty());
+        assert!(problems("wallet", "abcdefghijklmnop").iter().any(|p| p.contains("predictable")));
+        assert_eq!(problems("wallet", "aaa").len(), 2);
+        let err = PasswordPolicy::default().check("wallet", "short").unwrap_err();
+        assert!(err.to_string().starts_with("password too weak: "));
+    }
+
+    #[test]
+    fn looser_policies_allow_more() {
+        let loose = PasswordPolicy { min_length: 4, min_entropy_bits: 10.0, reject_common: false };
+        assert!(loose.check("wallet", "password123!").is_ok());
+        assert!(loose.check("wallet", "abc").is_err());
+    }
+}
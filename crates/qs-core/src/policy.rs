@@ -0,0 +1,436 @@
+//! Declarative per-wallet signing policy, checked after the password and
+//! before anything is signed.
+//!
+//! Amounts, tokens and destinations are read from the message when it is a
+//! JSON object (or from typed data's message): `amount` as a decimal string
+//! (or a whole number), the token as `token` or `mint`, the destination as
+//! `destination` or `to`. Bridge limits are per token and exact: a limit of
+//! "1000.000000" caps a 6-decimal token, and an amount with more decimals
+//! than the token's is refused. Messages under the `bridge` tag must carry
+//! what the bridge rules need, or they are denied.
+//!
+//! Rotating, revoking and exporting a key are checked too, under
+//! `key-rotation`, `key-revocation`/`recovery-key` and `key-export`.
+//! Every denial is written to the [`audit`](crate::audit) log.
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, fmt, path::PathBuf, sync::Mutex};
+
+use qs_crypto::{Amount, Domain};
+use qs_utils::{ensure_wallet_dir, read_json, wallet_dir, write_json};
+
+use crate::{audit, invalid, io, now_secs, read_wallet, unlock, CoreError, Result};
+
+pub const BRIDGE_TAG: &str = "bridge";
+const HOUR_SECS: u64 = 3600;
+const DAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+/// Matches a signing domain; a missing `app_id` matches any app.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DomainRule { pub tag: String, pub app_id: Option<String> }
+
+/// Signing allowed from `start` to `end` (UTC, "HH:MM", end exclusive) on
+/// `days` ("mon".."sun", all days if empty). A window may wrap past midnight;
+/// `start == end` means the whole day.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TimeWindow {
+    #[serde(default)]
+    pub days: Vec<String>,
+    pub start: String,
+    pub end: String,
+}
+
+/// Amounts at the token's decimals, e.g. "0.500000" for a 6-decimal token.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct BridgeLimits { pub min_amount: Option<Amount>, pub max_amount: Option<Amount> }
+
+impl BridgeLimits {
+    fn decimals(&self) -> Option<u8> {
+        self.min_amount.or(self.max_amount).map(|a| a.decimals())
+    }
+}
+
+/// Every rule is optional; an empty policy allows everything.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct WalletPolicy {
+    pub allowed_domains: Option<Vec<DomainRule>>,
+    pub max_signs_per_hour: Option<u32>,
+    /// By token (an intent's `token`, a Solana `mint`); tokens not listed
+    /// cannot be bridged.
+    pub bridge: Option<BTreeMap<String, BridgeLimits>>,
+    pub allowed_destinations: Option<Vec<String>>,
+    pub time_windows: Option<Vec<TimeWindow>>,
+}
+
+/// Which rule refused a signature and why.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PolicyDenial { pub wallet: String, pub rule: String, pub reason: String }
+
+impl fmt::Display for PolicyDenial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "policy for wallet `{}` denied signing ({}): {}", self.wallet, self.rule, self.reason)
+    }
+}
+
+/// What is about to be signed, as far as the rules care.
+pub struct SignContext<'a> {
+    pub domain: &'a Domain,
+    /// The message parsed as JSON, if it is JSON.
+    pub fields: Option<Value>,
+}
+
+impl<'a> SignContext<'a> {
+    pub fn raw(domain: &'a Domain, message: &[u8]) -> Self {
+        SignContext { domain, fields: serde_json::from_slice(message).ok() }
+    }
+
+    pub fn typed(domain: &'a Domain, message: &Value) -> Self {
+        SignContext { domain, fields: Some(message.clone()) }
+    }
+
+    /// For key operations with no caller message: rotating, revoking, exporting.
+    pub fn key_use(domain: &'a Domain) -> Self {
+        SignContext { domain, fields: None }
+    }
+
+    /// Fractional JSON numbers are not read: they may already have been rounded.
+    fn amount(&self) -> Option<Amount> {
+        match self.fields.as_ref()?.get("amount")? {
+            Value::Number(n) => Amount::from_units(n.as_u64()?.into(), 0).ok(),
+            Value::String(s) => s.parse().ok(),
+            _ => None,
+        }
+    }
+
+    fn token(&self) -> Option<&str> {
+        let f = self.fields.as_ref()?;
+        f.get("token").or_else(|| f.get("mint"))?.as_str()
+    }
+
+    fn destination(&self) -> Option<&str> {
+        let f = self.fields.as_ref()?;
+        f.get("destination").or_else(|| f.get("to"))?.as_str()
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Store {
+    policies: BTreeMap<String, WalletPolicy>,
+    /// Recent signing times per wallet, for `max_signs_per_hour`.
+    usage: BTreeMap<String, Vec<u64>>,
+}
+
+fn store_path() -> PathBuf {
+    wallet_dir().join(".policies.json")
+}
+
+fn load() -> Result<Store> {
+    let path = store_path();
+    if !path.exists() {
+        return Ok(Store::default());
+    }
+    read_json(path).map_err(io)
+}
+
+fn save(store: &Store) -> Result<()> {
+    ensure_wallet_dir().map_err(io)?;
+    write_json(store_path(), store).map_err(io)
+}
+
+fn parse_hhmm(s: &str) -> Option<u32> {
+    let (h, m) = s.split_once(':')?;
+    let (h, m): (u32, u32) = (h.parse().ok()?, m.parse().ok()?);
+    (h < 24 && m < 60).then_some(h * 60 + m)
+}
+
+impl WalletPolicy {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_signs_per_hour == Some(0) {
+            return Err(invalid("max_signs_per_hour must be at least 1 (remove the wallet's key to stop signing)"));
+        }
+        for (token, b) in self.bridge.iter().flatten() {
+            if let (Some(min), Some(max)) = (b.min_amount, b.max_amount) {
+                match min.checked_cmp(&max) {
+                    Err(_) => {
+                        return Err(CoreError::Invalid(format!("bridge limits for `{token}` use different decimals")));
+                    }
+                    Ok(std::cmp::Ordering::Greater) => {
+                        return Err(CoreError::Invalid(format!("bridge min_amount for `{token}` is above max_amount")));
+                    }
+                    Ok(_) => {}
+                }
+            }
+        }
+        for w in self.time_windows.iter().flatten() {
+            if parse_hhmm(&w.start).is_none() || parse_hhmm(&w.end).is_none() {
+                return Err(CoreError::Invalid(format!("time window `{}`-`{}` is not HH:MM", w.start, w.end)));
+            }
+            if let Some(d) = w.days.iter().find(|d| !DAYS.contains(&d.as_str())) {
+                return Err(CoreError::Invalid(format!("unknown day `{d}` (use mon..sun)")));
+            }
+        }
+        Ok(())
+    }
+
+    /// The first rule that refuses `ctx` at `now`, as (rule, reason).
+    fn evaluate(&self, ctx: &SignContext, now: u64, recent_signs: usize) -> Option<(&'static str, String)> {
+        if let Some(rules) = &self.allowed_domains {
+            let d = ctx.domain;
+            if !rules.iter().any(|r| r.tag == d.tag && r.app_id.as_ref().is_none_or(|a| *a == d.app_id)) {
+                return Some(("allowed_domains", format!("domain `{}`/`{}` is not allowed", d.tag, d.app_id)));
+            }
+        }
+        if let Some(windows) = &self.time_windows {
+            let minute = ((now % 86_400) / 60) as u32;
+            let day = DAYS[((now / 86_400 + 3) % 7) as usize]; // 1970-01-01 was a Thursday
+            let open = windows.iter().any(|w| {
+                let (Some(start), Some(end)) = (parse_hhmm(&w.start), parse_hhmm(&w.end)) else { return false };
+                let in_time = match start.cmp(&end) {
+                    std::cmp::Ordering::Equal => true,
+                    std::cmp::Ordering::Less => (start..end).contains(&minute),
+                    std::cmp::Ordering::Greater => minute >= start || minute < end,
+                };
+                in_time && (w.days.is_empty() || w.days.iter().any(|d| d == day))
+            });
+            if !open {
+                return Some(("time_windows", "outside every allowed time window".to_string()));
+            }
+        }
+        if let Some(max) = self.max_signs_per_hour {
+            if recent_signs >= max as usize {
+                return Some(("max_signs_per_hour", format!("already signed {recent_signs} times in the last hour (limit {max})")));
+            }
+        }
+        if let (Some(tokens), true) = (&self.bridge, ctx.domain.tag == BRIDGE_TAG) {
+            if let Some(refusal) = bridge_refusal(tokens, ctx) {
+                return Some(("bridge", refusal));
+            }
+        }
+        if let Some(allowed) = &self.allowed_destinations {
+            match ctx.destination() {
+                Some(dest) if !allowed.iter().any(|a| a == dest) => {
+                    return Some(("allowed_destinations", format!("destination `{dest}` is not allowlisted")));
+                }
+                None if ctx.domain.tag == BRIDGE_TAG => {
+                    return Some(("allowed_destinations", "bridge message has no `destination`".to_string()));
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+fn bridge_refusal(tokens: &BTreeMap<String, BridgeLimits>, ctx: &SignContext) -> Option<String> {
+    let Some(token) = ctx.token() else {
+        return Some("bridge message has no `token`".to_string());
+    };
+    let Some(limits) = tokens.get(token) else {
+        return Some(format!("token `{token}` has no bridge limits"));
+    };
+    let Some(amount) = ctx.amount() else {
+        return Some("bridge message has no readable `amount`".to_string());
+    };
+    let decimals = limits.decimals()?;
+    let Ok(amount) = amount.rescale(decimals) else {
+        return Some(format!("amount {amount} has more than the {decimals} decimals of `{token}`"));
+    };
+    let below = limits.min_amount.is_some_and(|min| amount.units() < min.units());
+    let above = limits.max_amount.is_some_and(|max| amount.units() > max.units());
+    (below || above).then(|| format!("amount {amount} is outside the allowed bridge range for `{token}`"))
+}
+
+pub fn get(name: &str) -> Result<Option<WalletPolicy>> {
+    read_wallet(name)?;
+    let _guard = LOCK.lock().map_err(io)?;
+    Ok(load()?.policies.remove(name))
+}
+
+/// Sets (or with `None` removes) the wallet's policy. Needs the password, so
+/// a caller without it cannot loosen the rules.
+pub fn set(name: &str, password: &str, policy: Option<WalletPolicy>) -> Result<()> {
+    if let Some(p) = &policy {
+        p.validate()?;
+    }
+    unlock(name, password)?;
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    match policy {
+        Some(p) => { store.policies.insert(name.to_string(), p); }
+        None => { store.policies.remove(name); store.usage.remove(name); }
+    }
+    save(&store)
+}
+
+fn deny(name: &str, ctx: &SignContext, rule: &str, reason: String) -> Result<()> {
+    let denial = PolicyDenial { wallet: name.to_string(), rule: rule.to_string(), reason };
+    audit::record(&audit::AuditEvent::new(Some(name), "sign", "denied", json!({
+        "rule": denial.rule,
+        "reason": denial.reason,
+        "domain": ctx.domain,
+    })))?;
+    Err(CoreError::PolicyDenied(denial))
+}
+
+/// Checks `ctx` against the wallet's policy without using up any quota.
+pub fn check(name: &str, ctx: &SignContext) -> Result<()> {
+    let now = now_secs();
+    let verdict = {
+        let _guard = LOCK.lock().map_err(io)?;
+        let store = load()?;
+        let Some(policy) = store.policies.get(name) else { return Ok(()) };
+        let recent = store.usage.get(name).map_or(0, |u| u.iter().filter(|t| **t + HOUR_SECS > now).count());
+        policy.evaluate(ctx, now, recent)
+    };
+    match verdict {
+        Some((rule, reason)) => deny(name, ctx, rule, reason),
+        None => Ok(()),
+    }
+}
+
+/// Checks `ctx` and, if allowed, counts one signature against the hourly limit.
+pub fn enforce(name: &str, ctx: &SignContext) -> Result<()> {
+    let now = now_secs();
+    let verdict = {
+        let _guard = LOCK.lock().map_err(io)?;
+        let mut store = load()?;
+        let Some(policy) = store.policies.get(name).cloned() else { return Ok(()) };
+        let usage = store.usage.entry(name.to_string()).or_default();
+        usage.retain(|t| *t + HOUR_SECS > now);
+        let verdict = policy.evaluate(ctx, now, usage.len());
+        if verdict.is_none() && policy.max_signs_per_hour.is_some() {
+            usage.push(now);
+            save(&store)?;
+        }
+        verdict
+    };
+    match verdict {
+        Some((rule, reason)) => deny(name, ctx, rule, reason),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, PASSWORD};
+
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+
+    fn amount(s: &str) -> Option<Amount> { Some(s.parse().unwrap()) }
+
+    fn bridge_policy() -> WalletPolicy {
+        let limits = BridgeLimits { min_amount: amount("0.500000"), max_amount: amount("100.000000") };
+        WalletPolicy { bridge: Some(BTreeMap::from([(USDC.to_string(), limits)])), ..Default::default() }
+    }
+
+    fn refusal(policy: &WalletPolicy, domain: &Domain, fields: Value) -> Option<&'static str> {
+        policy.evaluate(&SignContext::typed(domain, &fields), 0, 0).map(|(rule, _)| rule)
+    }
+
+    #[test]
+    fn validate_refuses_inconsistent_rules() {
+        assert!(WalletPolicy::default().validate().is_ok());
+        assert!(bridge_policy().validate().is_ok());
+        assert!(WalletPolicy { max_signs_per_hour: Some(0), ..Default::default() }.validate().is_err());
+
+        let limits = |min, max| WalletPolicy {
+            bridge: Some(BTreeMap::from([("t".to_string(), BridgeLimits { min_amount: amount(min), max_amount: amount(max) })])),
+            ..Default::default()
+        };
+        assert!(limits("5.00", "1.00").validate().is_err());
+        assert!(limits("1.0", "5.00").validate().is_err());
+        assert!(limits("1.00", "1.00").validate().is_ok());
+
+        let window = |days: &[&str], start: &str, end: &str| WalletPolicy {
+            time_windows: Some(vec![TimeWindow { days: days.iter().map(|d| d.to_string()).collect(), start: start.into(), end: end.into() }]),
+            ..Default::default()
+        };
+        assert!(window(&["mon"], "09:00", "17:00").validate().is_ok());
+        assert!(window(&[], "24:00", "17:00").validate().is_err());
+        assert!(window(&[], "9", "17:00").validate().is_err());
+        assert!(window(&["monday"], "09:00", "17:00").validate().is_err());
+    }
+
+    #[test]
+    fn bridge_limits_are_exact_and_per_token() {
+        let policy = bridge_policy();
+        let bridge = Domain::new(BRIDGE_TAG, "intent");
+        let ok = |v: Value| refusal(&policy, &bridge, v).is_none();
+
+        assert!(ok(json!({ "token": USDC, "amount": "0.500000" })));
+        assert!(ok(json!({ "mint": USDC, "amount": "100.000000" })));
+        // a shorter scale is brought up to the token's, never rounded down
+        assert!(ok(json!({ "token": USDC, "amount": "1.5" })));
+        assert!(ok(json!({ "token": USDC, "amount": 7 })));
+        assert!(!ok(json!({ "token": USDC, "amount": "100.000001" })));
+        assert!(!ok(json!({ "token": USDC, "amount": "0.499999" })));
+        assert!(!ok(json!({ "token": USDC, "amount": "1.0000001" })));
+        assert!(!ok(json!({ "token": USDC, "amount": 1.5 })));
+        assert!(!ok(json!({ "token": USDC, "amount": "-1" })));
+        assert!(!ok(json!({ "token": USDC })));
+        assert!(!ok(json!({ "token": "OTHER", "amount": "1.000000" })));
+        assert!(!ok(json!({ "amount": "1.000000" })));
+        // only the bridge tag is held to the bridge rules
+        assert!(refusal(&policy, &Domain::new("chat", "app"), json!({ "amount": "1e9" })).is_none());
+    }
+
+    #[test]
+    fn domains_windows_and_destinations() {
+        let policy = WalletPolicy {
+            allowed_domains: Some(vec![
+                DomainRule { tag: "chat".into(), app_id: None },
+                DomainRule { tag: BRIDGE_TAG.into(), app_id: Some("intent".into()) },
+            ]),
+            allowed_destinations: Some(vec!["alice".into()]),
+            ..Default::default()
+        };
+        assert_eq!(refusal(&policy, &Domain::new("chat", "any"), json!({})), None);
+        assert_eq!(refusal(&policy, &Domain::new(BRIDGE_TAG, "solana"), json!({})), Some("allowed_domains"));
+        assert_eq!(refusal(&policy, &Domain::new("chat", "any"), json!({ "to": "mallory" })), Some("allowed_destinations"));
+        assert_eq!(refusal(&policy, &Domain::new(BRIDGE_TAG, "intent"), json!({ "destination": "alice" })), None);
+        assert_eq!(refusal(&policy, &Domain::new(BRIDGE_TAG, "intent"), json!({})), Some("allowed_destinations"));
+
+        // 1970-01-01 was a Thursday; the window wraps past midnight
+        let night = WalletPolicy {
+            time_windows: Some(vec![TimeWindow { days: vec!["thu".into()], start: "22:00".into(), end: "06:00".into() }]),
+            ..Default::default()
+        };
+        let chat = Domain::new("chat", "app");
+        let at = |secs| night.evaluate(&SignContext::raw(&chat, b"hi"), secs, 0).map(|(rule, _)| rule);
+        assert_eq!(at(60), None);
+        assert_eq!(at(12 * 3600), Some("time_windows"));
+        assert_eq!(at(86_400 + 60), Some("time_windows"));
+
+        let hourly = WalletPolicy { max_signs_per_hour: Some(2), ..Default::default() };
+        assert!(hourly.evaluate(&SignContext::raw(&chat, b"hi"), 0, 1).is_none());
+        assert_eq!(hourly.evaluate(&SignContext::raw(&chat, b"hi"), 0, 2).map(|(rule, _)| rule), Some("max_signs_per_hour"));
+    }
+
+    #[test]
+    fn wallet_policies_gate_signing_and_key_use() {
+        let name = testing::wallet("policy");
+        let chat = Domain::new("chat", "app");
+        let policy = WalletPolicy {
+            allowed_domains: Some(vec![DomainRule { tag: "chat".into(), app_id: None }]),
+            max_signs_per_hour: Some(1),
+            ..Default::default()
+        };
+        assert!(matches!(set(&name, "wrong password", Some(policy.clone())), Err(CoreError::BadPassword)));
+        set(&name, PASSWORD, Some(policy.clone())).unwrap();
+        assert_eq!(get(&name).unwrap(), Some(policy));
+
+        crate::sign(&name, PASSWORD, &chat, b"one").unwrap();
+        let denied = |r: Result<()>, rule: &str| matches!(r, Err(CoreError::PolicyDenied(d)) if d.rule == rule);
+        assert!(denied(crate::sign(&name, PASSWORD, &chat, b"two").map(drop), "max_signs_per_hour"));
+        assert!(denied(crate::rotation::rotate(&name, PASSWORD, None).map(drop), "allowed_domains"));
+        assert!(denied(crate::revocation::revoke_with_wallet(&name, PASSWORD, None, "").map(drop), "allowed_domains"));
+        assert!(denied(crate::qskey::export(&name, PASSWORD, "Amber-Quill-Harbor-19").map(drop), "allowed_domains"));
+
+        set(&name, PASSWORD, None).unwrap();
+        assert_eq!(get(&name).unwrap(), None);
+        crate::sign(&name, PASSWORD, &chat, b"two").unwrap();
+    }
+}
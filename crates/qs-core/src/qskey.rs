@@ -0,0 +1,148 @@
+//! Moving keys between the web app's `QsKeyFile` (lib/qskey.ts) and daemon keyfiles.
+//!
+//! The daemon signs with its DSA half; a KEM keypair, if the web file has one,
+//! rides along encrypted in the keyfile so an export gives back what came in.
+//! The web app's own keys are ML-DSA-65 (FIPS 204), which is not the
+//! round-3 Dilithium this daemon signs with; those files are refused rather
+//! than imported under the wrong scheme.
+use serde_json::{json, Value};
+
+use qs_crypto::{
+    QsKeyFile, SCHEME, WebKey, attach_kem, build_qskey, decrypt_kem, encrypt_secret, export_domain, keypair_matches,
+    open_qskey,
+};
+use qs_utils::{ensure_wallet_dir, write_json};
+
+use crate::{
+    audit, check_unprotected, io, password, invalid, policy, unlock, validate_name, wallet_path, CoreError, Result, WalletInfo,
+};
+
+fn normalize(label: &str) -> String {
+    label.chars().filter(char::is_ascii_alphanumeric).collect::<String>().to_ascii_lowercase()
+}
+
+/// Scheme labels compare case- and punctuation-insensitively ("Ed25519" == "ed25519").
+pub fn scheme_matches(label: &str) -> bool {
+    normalize(label) == normalize(SCHEME)
+}
+
+/// Refuses DSA keys this daemon cannot sign with, saying why for the web app's own.
+fn check_dsa_scheme(label: &str) -> Result<()> {
+    if scheme_matches(label) {
+        return Ok(());
+    }
+    if normalize(label).starts_with("mldsa") {
+        return Err(CoreError::Invalid(format!(
+            "keyfile holds a `{label}` key: FIPS 204 ML-DSA keys are not interchangeable with `{SCHEME}`, \
+             so this daemon cannot sign with it; keep that key in the web app"
+        )));
+    }
+    Err(CoreError::Invalid(format!("keyfile signs with `{label}`, this daemon with `{SCHEME}`")))
+}
+
+/// Creates wallet `name` from a web keyfile, re-encrypted under `password`.
+pub fn import(name: &str, file: &QsKeyFile, file_password: &str, password: &str) -> Result<WalletInfo> {
+    validate_name(name)?;
+    password::check(name, password)?;
+    ensure_wallet_dir().map_err(io)?;
+    if wallet_path(name).exists() {
+        return Err(CoreError::Exists(name.to_string()));
+    }
+    let (kem, dsa) = open_qskey(file, file_password).ok_or(CoreError::BadPassword)?;
+    check_dsa_scheme(&dsa.scheme)?;
+    if !keypair_matches(&dsa.public, &dsa.secret) {
+        return Err(invalid("keyfile DSA private key does not match dsaPub"));
+    }
+    let mut ek = encrypt_secret(name, &dsa.public, &dsa.secret, password);
+    if let Some(kem) = kem.filter(|k| !k.secret.is_empty()) {
+        attach_kem(&mut ek, name, &kem.scheme, &kem.public, &kem.secret, password).ok_or_else(|| invalid("could not seal KEM key"))?;
+    }
+    write_json(wallet_path(name), &ek).map_err(io)?;
+    let address = qs_crypto::address_from_pubkey(&dsa.public);
+    audit::log(Some(name), "import", "ok", json!({ "address": address }))?;
+    Ok(WalletInfo { name: name.to_string(), address, watch_only: false })
+}
+
+/// Exports wallet `name` as a web keyfile sealed with `export_password`,
+/// which must meet the password policy like any other key password.
+/// Protected wallets never hand out their secret key.
+pub fn export(name: &str, password: &str, export_password: &str) -> Result<QsKeyFile> {
+    check_unprotected(name)?;
+    password::check(name, export_password)?;
+    let (ek, secret) = unlock(name, password)?;
+    policy::enforce(name, &policy::SignContext::key_use(&export_domain()))?;
+    let public = hex::decode(&ek.public_hex).map_err(invalid)?;
+    let kem = match ek.kem {
+        Some(_) => {
+            let (scheme, public, secret) = decrypt_kem(&ek, name, password).ok_or(CoreError::BadPassword)?;
+            Some(WebKey { scheme, public, secret })
+        }
+        None => None,
+    };
+    let dsa = WebKey { scheme: SCHEME.to_string(), public, secret };
+    audit::log(Some(name), "export", "ok", Value::Null)?;
+    Ok(build_qskey(kem.as_ref(), &dsa, export_password))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD as B64, Engine};
+    use crate::testing::{self, PASSWORD};
+
+    const FILE_PASSWORD: &str = "Amber-Quill-Harbor-19";
+
+    fn web_file(dsa_scheme: &str, with_kem: bool) -> QsKeyFile {
+        let kp = qs_crypto::generate_dilithium3();
+        let dsa = WebKey { scheme: dsa_scheme.into(), public: kp.public, secret: kp.secret };
+        let kem = WebKey { scheme: "ML-KEM-768".into(), public: vec![7; 32], secret: vec![9; 64] };
+        build_qskey(with_kem.then_some(&kem), &dsa, FILE_PASSWORD)
+    }
+
+    #[test]
+    fn import_then_export_round_trips() {
+        testing::wallet_dir();
+        for kem in [true, false] {
+            let file = web_file(&SCHEME.to_uppercase(), kem);
+            let name = testing::name("qskey");
+            let info = import(&name, &file, FILE_PASSWORD, PASSWORD).unwrap();
+            assert_eq!(B64.encode(crate::public_key(&name).unwrap()), file.dsa_pub);
+
+            let out = export(&name, PASSWORD, FILE_PASSWORD).unwrap();
+            assert_eq!((&out.dsa_pub, &out.kem_pub), (&file.dsa_pub, &file.kem_pub));
+            let (out_kem, out_dsa) = open_qskey(&out, FILE_PASSWORD).unwrap();
+            assert_eq!(out_dsa.scheme, SCHEME);
+            assert_eq!(out_kem.map(|k| k.secret), kem.then(|| vec![9; 64]));
+
+            // the export reads back into another wallet at the same address
+            let again = testing::name("qskey");
+            assert_eq!(import(&again, &out, FILE_PASSWORD, PASSWORD).unwrap().address, info.address);
+        }
+    }
+
+    #[test]
+    fn refuses_web_ml_dsa_keys() {
+        testing::wallet_dir();
+        let err = import(&testing::name("mldsa"), &web_file("ML-DSA-65", true), FILE_PASSWORD, PASSWORD).unwrap_err();
+        assert!(err.to_string().contains("FIPS 204"), "{err}");
+        let err = import(&testing::name("other"), &web_file("falcon-512", true), FILE_PASSWORD, PASSWORD).unwrap_err();
+        assert!(err.to_string().contains("falcon-512"), "{err}");
+        assert!(matches!(
+            import(&testing::name("wrong"), &web_file(SCHEME, true), "not the password", PASSWORD),
+            Err(CoreError::BadPassword)
+        ));
+    }
+
+    #[test]
+    fn export_password_meets_the_policy() {
+        let name = testing::wallet("export");
+        assert!(matches!(export(&name, PASSWORD, "short"), Err(CoreError::WeakPassword(_))));
+    }
+
+    #[test]
+    fn protected_wallets_do_not_export() {
+        let name = testing::wallet("protected");
+        crate::approval::enable_protection(&name, PASSWORD, "Saffron-Beacon-Tundra-31", None).unwrap();
+        assert!(matches!(export(&name, PASSWORD, FILE_PASSWORD), Err(CoreError::ApprovalRequired(_))));
+    }
+}
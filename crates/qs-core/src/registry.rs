@@ -0,0 +1,197 @@
+//! Address book of known public keys, so callers can name a signer by its
+//! `QS…` address instead of shipping the full key.
+//!
+//! Entries come from explicit import or are recorded the first time a key
+//! produces a valid signature through the daemon. Stored as one JSON file in
+//! the wallet directory; the leading dot keeps it out of wallet listings.
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::{Path, PathBuf}, sync::Mutex};
+
+use qs_crypto::{address_from_pubkey, SCHEME};
+use qs_utils::{ensure_wallet_dir, read_json, wallet_dir, write_json};
+
+use crate::{invalid, io, now_secs, CoreError, Result};
+
+// serializes read-modify-write of the registry file within this process
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource { Import, FirstSeen }
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RegistryEntry {
+    pub address: String,
+    pub scheme: String,
+    pub public_key_hex: String,
+    pub source: KeySource,
+    pub added_at: u64,
+    pub label: Option<String>,
+}
+
+impl RegistryEntry {
+    pub fn public_key(&self) -> Result<Vec<u8>> {
+        hex::decode(&self.public_key_hex).map_err(invalid)
+    }
+}
+
+fn registry_path() -> PathBuf {
+    registry_in(&wallet_dir())
+}
+
+fn registry_in(dir: &Path) -> PathBuf {
+    dir.join(".registry.json")
+}
+
+fn load() -> Result<BTreeMap<String, RegistryEntry>> {
+    load_from(&registry_path())
+}
+
+fn load_from(path: &Path) -> Result<BTreeMap<String, RegistryEntry>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    read_json(path).map_err(io)
+}
+
+fn save(entries: &BTreeMap<String, RegistryEntry>) -> Result<()> {
+    ensure_wallet_dir().map_err(io)?;
+    write_json(registry_path(), entries).map_err(io)
+}
+
+pub fn list() -> Result<Vec<RegistryEntry>> {
+    let _guard = LOCK.lock().map_err(io)?;
+    Ok(load()?.into_values().collect())
+}
+
+pub fn get(address: &str) -> Result<RegistryEntry> {
+    let _guard = LOCK.lock().map_err(io)?;
+    load()?.remove(address).ok_or_else(|| CoreError::UnknownAddress(address.to_string()))
+}
+
+/// Adds or updates an entry. Re-importing a first-seen key upgrades it to an import.
+pub fn import(public: &[u8], scheme: &str, label: Option<String>) -> Result<RegistryEntry> {
+    if public.is_empty() || scheme.is_empty() {
+        return Err(invalid("public key and scheme are required"));
+    }
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut entries = load()?;
+    let address = address_from_pubkey(public);
+    let entry = RegistryEntry {
+        address: address.clone(),
+        scheme: scheme.to_string(),
+        public_key_hex: hex::encode(public),
+        source: KeySource::Import,
+        added_at: entries.get(&address).map_or_else(now_secs, |e| e.added_at),
+        label: label.or_else(|| entries.get(&address).and_then(|e| e.label.clone())),
+    };
+    entries.insert(address, entry.clone());
+    save(&entries)?;
+    Ok(entry)
+}
+
+/// Records keys that just produced a valid signature under this build's scheme.
+/// Known addresses are left untouched. Returns how many were new.
+pub fn observe<'a>(keys: impl IntoIterator<Item = &'a [u8]>) -> Result<usize> {
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut entries = load()?;
+    let mut added = 0;
+    for public in keys {
+        let address = address_from_pubkey(public);
+        if entries.contains_key(&address) { continue; }
+        entries.insert(address.clone(), RegistryEntry {
+            address,
+            scheme: SCHEME.to_string(),
+            public_key_hex: hex::encode(public),
+            source: KeySource::FirstSeen,
+            added_at: now_secs(),
+            label: None,
+        });
+        added += 1;
+    }
+    if added > 0 {
+        save(&entries)?;
+    }
+    Ok(added)
+}
+
+/// Adds entries (e.g. from a backup) whose addresses are not known yet.
+pub fn merge(incoming: Vec<RegistryEntry>) -> Result<usize> {
+    ensure_wallet_dir().map_err(io)?;
+    merge_in(&wallet_dir(), incoming)
+}
+
+/// [`merge`] into the registry of wallet directory `dir`, which need not be
+/// the live one (backup restore stages a whole directory first).
+pub(crate) fn merge_in(dir: &Path, incoming: Vec<RegistryEntry>) -> Result<usize> {
+    let _guard = LOCK.lock().map_err(io)?;
+    let path = registry_in(dir);
+    let mut entries = load_from(&path)?;
+    let mut added = 0;
+    for e in incoming {
+        if entries.contains_key(&e.address) { continue; }
+        entries.insert(e.address.clone(), e);
+        added += 1;
+    }
+    if added > 0 {
+        write_json(path, &entries).map_err(io)?;
+    }
+    Ok(added)
+}
+
+pub fn remove(address: &str) -> Result<()> {
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut entries = load()?;
+    entries.remove(address).ok_or_else(|| CoreError::UnknownAddress(address.to_string()))?;
+    save(&entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    #[test]
+    fn import_observe_and_remove() {
+        testing::wallet_dir();
+        let imported = qs_crypto::generate_dilithium3().public;
+        let seen = qs_crypto::generate_dilithium3().public;
+
+        let entry = import(&imported, SCHEME, Some("alice".into())).unwrap();
+        assert_eq!(entry.address, address_from_pubkey(&imported));
+        assert_eq!(entry.source, KeySource::Import);
+        assert_eq!(get(&entry.address).unwrap().public_key().unwrap(), imported);
+
+        // already known keys are not counted, nor downgraded to first-seen
+        assert_eq!(observe([imported.as_slice(), seen.as_slice()]).unwrap(), 1);
+        assert_eq!(observe([seen.as_slice()]).unwrap(), 0);
+        assert_eq!(get(&entry.address).unwrap().source, KeySource::Import);
+
+        // re-importing a first-seen key upgrades it and keeps its first timestamp
+        let seen_addr = address_from_pubkey(&seen);
+        let first = get(&seen_addr).unwrap();
+        assert_eq!(first.source, KeySource::FirstSeen);
+        let upgraded = import(&seen, SCHEME, None).unwrap();
+        assert_eq!((upgraded.source, upgraded.added_at), (KeySource::Import, first.added_at));
+
+        remove(&seen_addr).unwrap();
+        assert!(matches!(get(&seen_addr), Err(CoreError::UnknownAddress(_))));
+        assert!(matches!(remove(&seen_addr), Err(CoreError::UnknownAddress(_))));
+        assert!(import(&[], SCHEME, None).is_err());
+    }
+
+    #[test]
+    fn merge_keeps_known_entries() {
+        testing::wallet_dir();
+        let public = qs_crypto::generate_dilithium3().public;
+        let known = import(&public, SCHEME, Some("mine".into())).unwrap();
+        let fresh = qs_crypto::generate_dilithium3().public;
+        let incoming = vec![
+            RegistryEntry { label: Some("theirs".into()), ..known.clone() },
+            RegistryEntry { address: address_from_pubkey(&fresh), public_key_hex: hex::encode(&fresh), ..known.clone() },
+        ];
+        assert_eq!(merge(incoming).unwrap(), 1);
+        assert_eq!(get(&known.address).unwrap().label.as_deref(), Some("mine"));
+        assert_eq!(get(&address_from_pubkey(&fresh)).unwrap().public_key().unwrap(), fresh);
+    }
+}
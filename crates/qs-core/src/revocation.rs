@@ -0,0 +1,179 @@
+//! Local revocation store: revoked keys and the recovery keys allowed to revoke them.
+//!
+//! Everything stored here was verified on the way in. Kept as one JSON file
+//! in the wallet directory, keyed by the `QS…` address of the affected key.
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{collections::BTreeMap, path::PathBuf, sync::Mutex};
+
+use qs_crypto::{
+    RecoveryDesignation, Revocation, address_from_pubkey, designate_recovery, issue_revocation, recovery_domain,
+    revocation_domain,
+};
+use qs_utils::{ensure_wallet_dir, read_json, wallet_dir, write_json};
+
+use crate::{audit, check_unprotected, invalid, io, now_secs, policy, unlock, CoreError, Result};
+
+/// How far in the future a revocation may be dated, for clock differences.
+pub const MAX_CLOCK_SKEW_SECS: u64 = 300;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Default)]
+struct Store {
+    revoked: BTreeMap<String, Revocation>,
+    recovery: BTreeMap<String, RecoveryDesignation>,
+}
+
+fn store_path() -> PathBuf {
+    wallet_dir().join(".revocations.json")
+}
+
+fn load() -> Result<Store> {
+    let path = store_path();
+    if !path.exists() {
+        return Ok(Store::default());
+    }
+    read_json(path).map_err(io)
+}
+
+fn save(store: &Store) -> Result<()> {
+    ensure_wallet_dir().map_err(io)?;
+    write_json(store_path(), store).map_err(io)
+}
+
+pub fn list() -> Result<Vec<Revocation>> {
+    let _guard = LOCK.lock().map_err(io)?;
+    Ok(load()?.revoked.into_values().collect())
+}
+
+/// All revocations by address, for checking many keys at once.
+pub fn revoked() -> Result<BTreeMap<String, Revocation>> {
+    let _guard = LOCK.lock().map_err(io)?;
+    Ok(load()?.revoked)
+}
+
+/// The revocation for `public`, if it has been revoked.
+pub fn status(public: &[u8]) -> Result<Option<Revocation>> {
+    Ok(revoked()?.remove(&address_from_pubkey(public)))
+}
+
+pub fn recovery_key(address: &str) -> Result<Option<RecoveryDesignation>> {
+    let _guard = LOCK.lock().map_err(io)?;
+    Ok(load()?.recovery.remove(address))
+}
+
+/// Stores a designation after checking it. A newer designation replaces an
+/// older one; a revoked key can no longer designate.
+pub fn add_designation(d: RecoveryDesignation) -> Result<()> {
+    d.verify().map_err(invalid)?;
+    let address = address_from_pubkey(&hex::decode(&d.public_hex).map_err(invalid)?);
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    if store.revoked.contains_key(&address) {
+        return Err(CoreError::Invalid(format!("key `{address}` is revoked")));
+    }
+    if store.recovery.get(&address).is_some_and(|old| old.issued_at > d.issued_at) {
+        return Err(invalid("a newer recovery key designation is already stored"));
+    }
+    store.recovery.insert(address, d);
+    save(&store)
+}
+
+/// Stores a revocation after checking its signature. Revoking an already
+/// revoked key keeps whichever revocation is dated earlier.
+pub fn add_revocation(r: Revocation) -> Result<Revocation> {
+    if r.revoked_at > now_secs() + MAX_CLOCK_SKEW_SECS {
+        return Err(invalid("revocation is dated in the future"));
+    }
+    let address = address_from_pubkey(&r.public().map_err(invalid)?);
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    r.verify(store.recovery.get(&address)).map_err(invalid)?;
+    if let Some(existing) = store.revoked.get(&address) {
+        if existing.revoked_at <= r.revoked_at {
+            return Ok(existing.clone());
+        }
+    }
+    store.revoked.insert(address.clone(), r.clone());
+    save(&store)?;
+    audit::log(None, "revoke", "ok", json!({ "address": address, "reason": r.reason }))?;
+    Ok(r)
+}
+
+/// Designates `recovery` as the recovery key of wallet `name`. Protected
+/// wallets refuse, as for every other statement signed with their key.
+pub fn designate_wallet_recovery(name: &str, password: &str, recovery: &[u8]) -> Result<RecoveryDesignation> {
+    check_unprotected(name)?;
+    let (ek, secret) = unlock(name, password)?;
+    policy::enforce(name, &policy::SignContext::key_use(&recovery_domain()))?;
+    let public = hex::decode(&ek.public_hex).map_err(invalid)?;
+    let d = designate_recovery(&public, &secret, recovery, now_secs()).ok_or_else(|| invalid("could not sign designation"))?;
+    add_designation(d.clone())?;
+    Ok(d)
+}
+
+/// Revokes `target` (or, if `None`, the wallet's own key) with wallet `name`
+/// as signer. Protected wallets refuse.
+pub fn revoke_with_wallet(name: &str, password: &str, target: Option<&[u8]>, reason: &str) -> Result<Revocation> {
+    check_unprotected(name)?;
+    let (ek, secret) = unlock(name, password)?;
+    policy::enforce(name, &policy::SignContext::key_use(&revocation_domain()))?;
+    let public = hex::decode(&ek.public_hex).map_err(invalid)?;
+    let revoked = target.unwrap_or(&public);
+    let r = issue_revocation(revoked, &public, &secret, now_secs(), reason).ok_or_else(|| invalid("could not sign revocation"))?;
+    add_revocation(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, PASSWORD};
+    use crate::public_key;
+
+    #[test]
+    fn wallet_revokes_itself_once() {
+        let name = testing::wallet("revoke");
+        let public = public_key(&name).unwrap();
+        assert!(status(&public).unwrap().is_none());
+
+        let r = revoke_with_wallet(&name, PASSWORD, None, "compromised").unwrap();
+        assert_eq!(status(&public).unwrap(), Some(r.clone()));
+        assert!(list().unwrap().contains(&r));
+
+        // a later revocation keeps the earlier date
+        let secret = crate::unlock(&name, PASSWORD).unwrap().1;
+        let later = issue_revocation(&public, &public, &secret, r.revoked_at + 10, "again").unwrap();
+        assert_eq!(add_revocation(later).unwrap(), r);
+        // and a revoked key can no longer designate
+        let recovery = testing::wallet("recovery");
+        assert!(designate_wallet_recovery(&name, PASSWORD, &public_key(&recovery).unwrap()).is_err());
+    }
+
+    #[test]
+    fn recovery_wallet_revokes_a_lost_key() {
+        let lost = testing::wallet("lost");
+        let recovery = testing::wallet("recovery");
+        let lost_public = public_key(&lost).unwrap();
+
+        assert!(revoke_with_wallet(&recovery, PASSWORD, Some(&lost_public), "lost").is_err());
+        let d = designate_wallet_recovery(&lost, PASSWORD, &public_key(&recovery).unwrap()).unwrap();
+        assert_eq!(recovery_key(&address_from_pubkey(&lost_public)).unwrap(), Some(d));
+        let r = revoke_with_wallet(&recovery, PASSWORD, Some(&lost_public), "lost").unwrap();
+        assert_eq!(r.public().unwrap(), lost_public);
+        assert!(status(&public_key(&recovery).unwrap()).unwrap().is_none());
+    }
+
+    #[test]
+    fn refuses_future_dates_and_bad_signatures() {
+        let name = testing::wallet("revoke");
+        let public = public_key(&name).unwrap();
+        let secret = crate::unlock(&name, PASSWORD).unwrap().1;
+        let future = issue_revocation(&public, &public, &secret, now_secs() + MAX_CLOCK_SKEW_SECS + 60, "").unwrap();
+        assert!(add_revocation(future).is_err());
+        let mut forged = issue_revocation(&public, &public, &secret, now_secs(), "").unwrap();
+        forged.reason = "edited".into();
+        assert!(add_revocation(forged).is_err());
+        assert!(status(&public).unwrap().is_none());
+    }
+}
@@ -0,0 +1,112 @@
+//! Key rotation: replace a wallet's signing key and keep a certificate chain
+//! that lets counterparties follow the wallet from its first key to the current one.
+//!
+//! Certificates live in `.rotations.json` keyed by wallet name; retired
+//! keyfiles are kept in `.rotated/` (still encrypted, still bound to the
+//! wallet name) so old signatures can be re-made or audited if needed.
+use serde_json::json;
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Mutex};
+
+use qs_crypto::{RotationCert, address_from_pubkey, encrypt_secret, generate_dilithium3, issue_rotation, rotation_domain, verify_rotation_chain};
+use qs_utils::{ensure_wallet_dir, read_json, wallet_dir, write_json};
+
+use crate::{audit, check_unprotected, invalid, io, now_secs, policy, unlock, wallet_path, Result, WalletInfo};
+
+// serializes rotations so two requests cannot fork a wallet's chain
+static LOCK: Mutex<()> = Mutex::new(());
+
+fn chains_path() -> PathBuf {
+    wallet_dir().join(".rotations.json")
+}
+
+fn load_chains() -> Result<BTreeMap<String, Vec<RotationCert>>> {
+    let path = chains_path();
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    read_json(path).map_err(io)
+}
+
+/// Certificates for wallet `name`, oldest first; empty if it was never rotated.
+pub fn chain(name: &str) -> Result<Vec<RotationCert>> {
+    let _guard = LOCK.lock().map_err(io)?;
+    Ok(load_chains()?.remove(name).unwrap_or_default())
+}
+
+/// Generates a new key for wallet `name` under the same password, retires the
+/// old keyfile and records a certificate signed by both keys. Protected
+/// wallets refuse; turn protection off first.
+pub fn rotate(name: &str, password: &str, reason: Option<String>) -> Result<(WalletInfo, RotationCert)> {
+    check_unprotected(name)?;
+    let _guard = LOCK.lock().map_err(io)?;
+    let (old_ek, old_secret) = unlock(name, password)?;
+    policy::enforce(name, &policy::SignContext::key_use(&rotation_domain()))?;
+    let old_public = hex::decode(&old_ek.public_hex).map_err(invalid)?;
+    let kp = generate_dilithium3();
+    let cert = issue_rotation(&old_public, &old_secret, &kp.public, &kp.secret, now_secs(), reason)
+        .ok_or_else(|| invalid("could not sign rotation certificate"))?;
+
+    let dir = ensure_wallet_dir().map_err(io)?;
+    let retired = dir.join(".rotated");
+    fs::create_dir_all(&retired).map_err(io)?;
+    write_json(retired.join(format!("{name}-{}.json", address_from_pubkey(&old_public))), &old_ek).map_err(io)?;
+
+    // write the new keyfile aside first so the chain never names a key we failed to store
+    let staged = dir.join(format!(".{name}.rotating"));
+    write_json(&staged, &encrypt_secret(name, &kp.public, &kp.secret, password)).map_err(io)?;
+    let mut chains = load_chains()?;
+    chains.entry(name.to_string()).or_default().push(cert.clone());
+    write_json(chains_path(), &chains).map_err(io)?;
+    fs::rename(&staged, wallet_path(name)).map_err(io)?;
+    audit::log(Some(name), "rotate", "ok", json!({
+        "old_address": address_from_pubkey(&old_public),
+        "new_address": address_from_pubkey(&kp.public),
+    }))?;
+
+    Ok((WalletInfo { name: name.to_string(), address: address_from_pubkey(&kp.public), watch_only: false }, cert))
+}
+
+/// Verifies `certs` (oldest first), optionally from a known `start` key, and
+/// returns the key they end at.
+pub fn follow(certs: &[RotationCert], start: Option<&[u8]>) -> Result<Vec<u8>> {
+    verify_rotation_chain(certs, start).map_err(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, PASSWORD};
+    use crate::{address, public_key, sign, verify, CoreError};
+    use qs_crypto::Domain;
+
+    #[test]
+    fn rotate_twice_and_follow() {
+        let name = testing::wallet("rotate");
+        let first = public_key(&name).unwrap();
+        let (info, cert) = rotate(&name, PASSWORD, Some("scheduled".into())).unwrap();
+        assert_eq!(info.address, address(&name).unwrap());
+        assert_eq!(cert.old_public().unwrap(), first);
+        rotate(&name, PASSWORD, None).unwrap();
+
+        let certs = chain(&name).unwrap();
+        assert_eq!(certs.len(), 2);
+        assert_eq!(follow(&certs, Some(&first)).unwrap(), public_key(&name).unwrap());
+
+        // the old keyfile is kept, and the new key signs under the same password
+        let retired = wallet_dir().join(".rotated").join(format!("{name}-{}.json", address_from_pubkey(&first)));
+        assert!(retired.exists());
+        let domain = Domain::new("test", "rotation");
+        let signed = sign(&name, PASSWORD, &domain, b"after").unwrap();
+        assert!(verify(&name, &domain, &signed).unwrap().is_some());
+        assert!(qs_crypto::verify_domain(&first, &domain, &signed).is_none());
+    }
+
+    #[test]
+    fn rotation_needs_the_password() {
+        let name = testing::wallet("rotate");
+        let before = public_key(&name).unwrap();
+        assert!(matches!(rotate(&name, "wrong password", None), Err(CoreError::BadPassword)));
+        assert_eq!(public_key(&name).unwrap(), before);
+        assert!(chain(&name).unwrap().is_empty());
+    }
+}
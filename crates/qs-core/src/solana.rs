@@ -0,0 +1,84 @@
+//! SPL token transfers signed by Ed25519 wallets.
+//!
+//! An Ed25519 wallet's public key is its Solana address. The daemon builds
+//! the transfer itself from a [`SplTransfer`] and the caller's recent
+//! blockhash, so a wallet only ever signs messages it compiled and the policy
+//! sees what is being sent: transfers are checked as `bridge`/`solana`
+//! signatures with the [`Amount`](qs_crypto::Amount) in whole tokens and the
+//! recipient as destination. Fetching the blockhash and submitting are up to the caller.
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use serde::Serialize;
+use serde_json::json;
+
+use qs_crypto::{
+    Domain, SCHEME,
+    solana::{Pubkey, SolanaError, SplTransfer, bs58_decode, bs58_encode, sign_solana_message, single_signer_transaction},
+};
+
+use crate::{audit, check_unprotected, invalid, policy, read_wallet, unlock, CoreError, Result};
+
+pub const SOLANA_APP_ID: &str = "solana";
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SignedTransfer {
+    pub from: Pubkey,
+    pub source_account: Pubkey,
+    pub destination_account: Pubkey,
+    /// The transaction id: the fee payer's signature in base58.
+    pub signature: String,
+    /// Wire-format transaction for `sendTransaction` with `encoding: "base64"`.
+    pub transaction_base64: String,
+}
+
+fn solana_err(e: SolanaError) -> CoreError { CoreError::Invalid(e.to_string()) }
+
+/// The wallet's Solana address. Needs no password.
+pub fn address(name: &str) -> Result<Pubkey> {
+    let wallet = read_wallet(name)?;
+    if wallet.scheme() != "ed25519" {
+        return Err(CoreError::Invalid(format!("wallet `{name}` uses scheme `{}`; Solana needs ed25519", wallet.scheme())));
+    }
+    let public = hex::decode(wallet.public_hex()).map_err(invalid)?;
+    Pubkey::from_bytes(&public).ok_or_else(|| invalid("ed25519 public key is not 32 bytes"))
+}
+
+/// Builds and signs `transfer` from wallet `name` against `recent_blockhash` (base58).
+pub fn sign_spl_transfer(name: &str, password: &str, transfer: &SplTransfer, recent_blockhash: &str) -> Result<SignedTransfer> {
+    if SCHEME != "ed25519" {
+        return Err(solana_err(SolanaError::NotEd25519));
+    }
+    let blockhash: [u8; 32] = bs58_decode(recent_blockhash)
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| CoreError::Invalid(format!("recent blockhash `{recent_blockhash}` is not base58 32 bytes")))?;
+    if transfer.amount.is_zero() {
+        return Err(CoreError::Invalid("amount must be positive".into()));
+    }
+    let from = address(name)?;
+    let (source_account, destination_account) = transfer.token_accounts(&from).map_err(solana_err)?;
+    let message = transfer.message(&from, &blockhash).map_err(solana_err)?;
+
+    check_unprotected(name)?;
+    let (_, secret) = unlock(name, password)?;
+    let domain = Domain::new(policy::BRIDGE_TAG, SOLANA_APP_ID);
+    let fields = json!({
+        "amount": transfer.amount,
+        "destination": transfer.to,
+        "mint": transfer.mint,
+    });
+    policy::enforce(name, &policy::SignContext::typed(&domain, &fields))?;
+    let signature = sign_solana_message(&secret, &message).map_err(solana_err)?;
+    let signature_b58 = bs58_encode(&signature);
+    audit::log(Some(name), "solana_transfer", "ok", json!({
+        "signature": signature_b58,
+        "mint": transfer.mint,
+        "to": transfer.to,
+        "amount": transfer.amount,
+    }))?;
+    Ok(SignedTransfer {
+        from,
+        source_account,
+        destination_account,
+        signature: signature_b58,
+        transaction_base64: B64.encode(single_signer_transaction(&signature, &message)),
+    })
+}
@@ -0,0 +1,200 @@
+//! Brute-force protection for wallet passwords.
+//!
+//! Every unlock attempt counts as a failure until it succeeds, so parallel
+//! guesses cannot slip past the counter. After [`FREE_ATTEMPTS`] failures in a
+//! row each further attempt must wait twice as long as the one before (up to
+//! [`MAX_DELAY_SECS`]). A wallet can also opt into lockout: after
+//! `lockout_after` failures it refuses every attempt until an operator clears
+//! it with [`reset`]. Counters live in `.lockouts.json` so restarting the
+//! daemon does not reset them.
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf, sync::Mutex};
+
+use qs_utils::{ensure_wallet_dir, read_json, wallet_dir, write_json};
+
+use crate::{io, load_keyfile, now_secs, unlock, CoreError, Result};
+
+pub const FREE_ATTEMPTS: u32 = 3;
+pub const MAX_DELAY_SECS: u64 = 3600;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+struct Counter {
+    failures: u32,
+    next_attempt_at: u64,
+    locked: bool,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Store {
+    counters: BTreeMap<String, Counter>,
+    /// Wallets that opted into lockout, with their failure limit.
+    lockout_after: BTreeMap<String, u32>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ThrottleStatus {
+    pub failures: u32,
+    /// Seconds until the next attempt is accepted; 0 if it would be now.
+    pub retry_after_secs: u64,
+    pub locked: bool,
+    pub lockout_after: Option<u32>,
+}
+
+fn store_path() -> PathBuf {
+    wallet_dir().join(".lockouts.json")
+}
+
+fn load() -> Result<Store> {
+    let path = store_path();
+    if !path.exists() {
+        return Ok(Store::default());
+    }
+    read_json(path).map_err(io)
+}
+
+fn save(store: &Store) -> Result<()> {
+    ensure_wallet_dir().map_err(io)?;
+    write_json(store_path(), store).map_err(io)
+}
+
+/// Wait imposed after `failures` failures in a row.
+fn delay(failures: u32) -> u64 {
+    if failures < FREE_ATTEMPTS {
+        return 0;
+    }
+    (1u64 << (failures - FREE_ATTEMPTS).min(20)).min(MAX_DELAY_SECS)
+}
+
+/// Admits one unlock attempt for `name`, counting it as failed until
+/// [`succeeded`] says otherwise.
+pub(crate) fn begin(name: &str) -> Result<()> {
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    let limit = store.lockout_after.get(name).copied();
+    let now = now_secs();
+    let c = store.counters.entry(name.to_string()).or_default();
+    if c.locked {
+        return Err(CoreError::LockedOut(name.to_string()));
+    }
+    if now < c.next_attempt_at {
+        return Err(CoreError::Throttled { wallet: name.to_string(), retry_after_secs: c.next_attempt_at - now });
+    }
+    c.failures += 1;
+    c.next_attempt_at = now + delay(c.failures);
+    c.locked = limit.is_some_and(|l| c.failures >= l);
+    save(&store)
+}
+
+/// Clears the counter after a correct password.
+pub(crate) fn succeeded(name: &str) -> Result<()> {
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    if store.counters.remove(name).is_some() {
+        save(&store)?;
+    }
+    Ok(())
+}
+
+pub fn status(name: &str) -> Result<ThrottleStatus> {
+    load_keyfile(name)?;
+    let _guard = LOCK.lock().map_err(io)?;
+    let store = load()?;
+    let c = store.counters.get(name).cloned().unwrap_or_default();
+    Ok(ThrottleStatus {
+        failures: c.failures,
+        retry_after_secs: c.next_attempt_at.saturating_sub(now_secs()),
+        locked: c.locked,
+        lockout_after: store.lockout_after.get(name).copied(),
+    })
+}
+
+/// Turns lockout on after `after` failures in a row, or off with `None`.
+pub fn set_lockout(name: &str, password: &str, after: Option<u32>) -> Result<ThrottleStatus> {
+    if after.is_some_and(|a| a <= FREE_ATTEMPTS) {
+        return Err(CoreError::Invalid(format!("lockout_after must be above {FREE_ATTEMPTS}")));
+    }
+    unlock(name, password)?;
+    {
+        let _guard = LOCK.lock().map_err(io)?;
+        let mut store = load()?;
+        match after {
+            Some(a) => { store.lockout_after.insert(name.to_string(), a); }
+            None => { store.lockout_after.remove(name); }
+        }
+        save(&store)?;
+    }
+    status(name)
+}
+
+/// Clears the failure counter and any lockout. Meant for the operator (it
+/// needs no password), so it is not exposed over HTTP.
+pub fn reset(name: &str) -> Result<()> {
+    load_keyfile(name)?;
+    let _guard = LOCK.lock().map_err(io)?;
+    let mut store = load()?;
+    store.counters.remove(name);
+    save(&store)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, PASSWORD};
+
+    /// Lets the next attempt through now, as if the wait had passed.
+    fn skip_wait(name: &str) {
+        let _guard = LOCK.lock().unwrap();
+        let mut store = load().unwrap();
+        if let Some(c) = store.counters.get_mut(name) {
+            c.next_attempt_at = 0;
+        }
+        save(&store).unwrap();
+    }
+
+    #[test]
+    fn delays_double_up_to_the_cap() {
+        assert_eq!((0..FREE_ATTEMPTS).map(delay).max(), Some(0));
+        assert_eq!(delay(FREE_ATTEMPTS), 1);
+        assert_eq!(delay(FREE_ATTEMPTS + 3), 8);
+        assert_eq!(delay(FREE_ATTEMPTS + 40), MAX_DELAY_SECS);
+    }
+
+    #[test]
+    fn failures_back_off_until_a_correct_password() {
+        let name = testing::wallet("throttle");
+        for _ in 0..FREE_ATTEMPTS {
+            assert!(matches!(unlock(&name, "wrong password"), Err(CoreError::BadPassword)));
+        }
+        assert_eq!(status(&name).unwrap().failures, FREE_ATTEMPTS);
+        // even the right password waits its turn
+        assert!(matches!(unlock(&name, PASSWORD), Err(CoreError::Throttled { retry_after_secs: 1, .. })));
+        skip_wait(&name);
+        unlock(&name, PASSWORD).unwrap();
+        let s = status(&name).unwrap();
+        assert_eq!((s.failures, s.retry_after_secs, s.locked), (0, 0, false));
+    }
+
+    #[test]
+    fn lockout_holds_until_reset() {
+        let name = testing::wallet("throttle");
+        assert!(set_lockout(&name, PASSWORD, Some(FREE_ATTEMPTS)).is_err());
+        assert!(matches!(set_lockout(&name, "wrong password", Some(5)), Err(CoreError::BadPassword)));
+        skip_wait(&name);
+        assert_eq!(set_lockout(&name, PASSWORD, Some(FREE_ATTEMPTS + 1)).unwrap().lockout_after, Some(FREE_ATTEMPTS + 1));
+
+        for _ in 0..=FREE_ATTEMPTS {
+            skip_wait(&name);
+            assert!(matches!(unlock(&name, "wrong password"), Err(CoreError::BadPassword)));
+        }
+        assert!(status(&name).unwrap().locked);
+        skip_wait(&name);
+        assert!(matches!(unlock(&name, PASSWORD), Err(CoreError::LockedOut(_))));
+
+        reset(&name).unwrap();
+        unlock(&name, PASSWORD).unwrap();
+        assert_eq!(set_lockout(&name, PASSWORD, None).unwrap().lockout_after, None);
+        assert!(reset("no-such-wallet").is_err());
+    }
+}
@@ -0,0 +1,257 @@
+// -------- token amounts --------
+//
+// Exact token amounts: an integer number of base units and the token's
+// decimals, so 1.5 of a 6-decimal token is 1_500_000 units. They travel as
+// decimal strings carrying every fractional digit ("1.500000"); the number
+// of digits after the point is the decimals, so a string never loses
+// precision or scale. A shorter string ("1.5") parses at its own scale and
+// can be brought to the token's with `rescale`. Equality and hashing go by
+// value ("1.5" == "1.50"); the scale only shows in `decimals` and the text.
+// Arithmetic is checked: overflow, underflow and mixing scales are errors,
+// not silent wraps.
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{cmp::Ordering, fmt, hash::{Hash, Hasher}, str::FromStr};
+
+/// u128 holds 38 full decimal digits.
+pub const MAX_DECIMALS: u8 = 38;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Amount { units: u128, decimals: u8 }
+
+impl Amount {
+    /// The same value with trailing fractional zeros dropped: 1.50 is (15, 1), 0.00 is (0, 0).
+    fn reduced(&self) -> (u128, u8) {
+        let (mut units, mut decimals) = (self.units, self.decimals);
+        while decimals > 0 && units % 10 == 0 {
+            units /= 10;
+            decimals -= 1;
+        }
+        (units, decimals)
+    }
+}
+
+impl PartialEq for Amount {
+    fn eq(&self, other: &Amount) -> bool { self.reduced() == other.reduced() }
+}
+
+impl Eq for Amount {}
+
+impl Hash for Amount {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.reduced().hash(state) }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AmountError {
+    Malformed(String),
+    TooManyDecimals(u8),
+    Overflow,
+    /// Subtracting more than there is.
+    Negative,
+    /// Two amounts of different scale, e.g. of different tokens.
+    DecimalsMismatch(u8, u8),
+    /// Rescaling would drop non-zero digits.
+    PrecisionLoss { decimals: u8 },
+}
+
+impl fmt::Display for AmountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AmountError::Malformed(s)           => write!(f, "amount: `{s}` is not a decimal number"),
+            AmountError::TooManyDecimals(d)     => write!(f, "amount: {d} decimals is more than {MAX_DECIMALS}"),
+            AmountError::Overflow               => write!(f, "amount: overflow"),
+            AmountError::Negative               => write!(f, "amount: result would be negative"),
+            AmountError::DecimalsMismatch(a, b) => write!(f, "amount: cannot mix {a} and {b} decimals"),
+            AmountError::PrecisionLoss { decimals } => write!(f, "amount: has more than {decimals} decimals"),
+        }
+    }
+}
+
+impl std::error::Error for AmountError {}
+
+fn pow10(exp: u8) -> Result<u128, AmountError> { 10u128.checked_pow(exp.into()).ok_or(AmountError::Overflow) }
+
+impl Amount {
+    pub fn from_units(units: u128, decimals: u8) -> Result<Self, AmountError> {
+        if decimals > MAX_DECIMALS {
+            return Err(AmountError::TooManyDecimals(decimals));
+        }
+        Ok(Amount { units, decimals })
+    }
+
+    pub fn zero(decimals: u8) -> Result<Self, AmountError> { Amount::from_units(0, decimals) }
+
+    pub fn units(&self) -> u128 { self.units }
+    pub fn decimals(&self) -> u8 { self.decimals }
+    pub fn is_zero(&self) -> bool { self.units == 0 }
+
+    /// Parses "12", "12.5" or ".5" exactly; the scale is the number of fractional digits.
+    /// Signs, exponents and separators are refused.
+    pub fn parse(s: &str) -> Result<Self, AmountError> {
+        let malformed = || AmountError::Malformed(s.to_string());
+        let (whole, frac) = s.split_once('.').unwrap_or((s, ""));
+        let digits_only = whole.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit());
+        if !digits_only || (whole.is_empty() && frac.is_empty()) || (s.contains('.') && frac.is_empty()) {
+            return Err(malformed());
+        }
+        let decimals = u8::try_from(frac.len()).map_err(|_| AmountError::TooManyDecimals(u8::MAX))?;
+        if decimals > MAX_DECIMALS {
+            return Err(AmountError::TooManyDecimals(decimals));
+        }
+        let digits = format!("{whole}{frac}");
+        let units = if digits.is_empty() { 0 } else { digits.parse().map_err(|_| AmountError::Overflow)? };
+        Amount::from_units(units, decimals)
+    }
+
+    /// Parses `s` and brings it to `decimals`, e.g. "1.5" at 6 decimals is 1_500_000 units.
+    pub fn parse_with_decimals(s: &str, decimals: u8) -> Result<Self, AmountError> {
+        Amount::parse(s)?.rescale(decimals)
+    }
+
+    /// The same value at another scale; fails rather than rounding.
+    pub fn rescale(self, decimals: u8) -> Result<Self, AmountError> {
+        match decimals.cmp(&self.decimals) {
+            Ordering::Equal => Ok(self),
+            Ordering::Greater => {
+                let units = self.units.checked_mul(pow10(decimals - self.decimals)?).ok_or(AmountError::Overflow)?;
+                Amount::from_units(units, decimals)
+            }
+            Ordering::Less => {
+                let factor = pow10(self.decimals - decimals)?;
+                if !self.units.is_multiple_of(factor) {
+                    return Err(AmountError::PrecisionLoss { decimals });
+                }
+                Amount::from_units(self.units / factor, decimals)
+            }
+        }
+    }
+
+    fn same_scale(&self, other: &Amount) -> Result<(), AmountError> {
+        if self.decimals == other.decimals { Ok(()) } else { Err(AmountError::DecimalsMismatch(self.decimals, other.decimals)) }
+    }
+
+    pub fn checked_add(self, other: Amount) -> Result<Self, AmountError> {
+        self.same_scale(&other)?;
+        Ok(Amount { units: self.units.checked_add(other.units).ok_or(AmountError::Overflow)?, ..self })
+    }
+
+    pub fn checked_sub(self, other: Amount) -> Result<Self, AmountError> {
+        self.same_scale(&other)?;
+        Ok(Amount { units: self.units.checked_sub(other.units).ok_or(AmountError::Negative)?, ..self })
+    }
+
+    pub fn checked_mul(self, factor: u128) -> Result<Self, AmountError> {
+        Ok(Amount { units: self.units.checked_mul(factor).ok_or(AmountError::Overflow)?, ..self })
+    }
+
+    /// Compares two amounts of the same scale.
+    pub fn checked_cmp(&self, other: &Amount) -> Result<Ordering, AmountError> {
+        self.same_scale(other)?;
+        Ok(self.units.cmp(&other.units))
+    }
+}
+
+/// Every fractional digit is written: 1_500_000 units at 6 decimals is "1.500000".
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let d = self.decimals as usize;
+        let digits = format!("{:0>width$}", self.units, width = d + 1);
+        let (whole, frac) = digits.split_at(digits.len() - d);
+        if frac.is_empty() { f.write_str(whole) } else { write!(f, "{whole}.{frac}") }
+    }
+}
+
+impl FromStr for Amount {
+    type Err = AmountError;
+    fn from_str(s: &str) -> Result<Self, AmountError> { Amount::parse(s) }
+}
+
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> { s.collect_str(self) }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(s: &str) -> Amount { Amount::parse(s).unwrap() }
+
+    fn exact(a: Amount) -> (u128, u8) { (a.units(), a.decimals()) }
+
+    #[test]
+    fn parses_exactly_at_its_own_scale() {
+        assert_eq!(exact(amount("12")), (12, 0));
+        assert_eq!(exact(amount("12.5")), (125, 1));
+        assert_eq!(exact(amount("12.50")), (1250, 2));
+        assert_eq!(exact(amount(".5")), (5, 1));
+        assert_eq!(exact(amount("0.000001")), (1, 6));
+        assert_eq!(exact(amount("340282366920938463463374607431768211455")), (u128::MAX, 0));
+        for bad in ["", ".", "1.", "-1", "+1", "1e6", "1,5", "1.2.3", " 1", "0x10"] {
+            assert!(matches!(Amount::parse(bad), Err(AmountError::Malformed(_))), "{bad:?}");
+        }
+        assert_eq!(Amount::parse("340282366920938463463374607431768211456"), Err(AmountError::Overflow));
+        assert_eq!(Amount::parse(&format!("0.{}", "0".repeat(39))), Err(AmountError::TooManyDecimals(39)));
+        assert_eq!(Amount::from_units(1, 39), Err(AmountError::TooManyDecimals(39)));
+    }
+
+    #[test]
+    fn rescales_without_rounding() {
+        assert_eq!(exact(amount("1.5").rescale(6).unwrap()), (1_500_000, 6));
+        assert_eq!(exact(amount("1.500000").rescale(1).unwrap()), (15, 1));
+        assert_eq!(exact(amount("1.5").rescale(1).unwrap()), (15, 1));
+        assert_eq!(amount("1.25").rescale(1), Err(AmountError::PrecisionLoss { decimals: 1 }));
+        assert_eq!(amount("1.000001").rescale(0), Err(AmountError::PrecisionLoss { decimals: 0 }));
+        assert_eq!(Amount::from_units(u128::MAX, 0).unwrap().rescale(1), Err(AmountError::Overflow));
+        assert_eq!(Amount::parse_with_decimals("2", 9).unwrap().units(), 2_000_000_000);
+    }
+
+    #[test]
+    fn display_writes_every_digit_and_parses_back() {
+        for (units, decimals, text) in [
+            (0, 0, "0"), (0, 6, "0.000000"), (1, 6, "0.000001"), (1_500_000, 6, "1.500000"),
+            (42, 0, "42"), (u128::MAX, 38, "3.40282366920938463463374607431768211455"),
+        ] {
+            let a = Amount::from_units(units, decimals).unwrap();
+            assert_eq!(a.to_string(), text);
+            assert_eq!(exact(text.parse::<Amount>().unwrap()), exact(a));
+            let json = serde_json::to_string(&a).unwrap();
+            assert_eq!(json, format!("\"{text}\""));
+            assert_eq!(serde_json::from_str::<Amount>(&json).unwrap(), a);
+        }
+        // numbers are refused: they may already have lost precision
+        assert!(serde_json::from_str::<Amount>("1.5").is_err());
+    }
+
+    #[test]
+    fn arithmetic_is_checked_and_keeps_scales_apart() {
+        let (a, b) = (amount("1.50"), amount("0.25"));
+        assert_eq!(a.checked_add(b).unwrap(), amount("1.75"));
+        assert_eq!(a.checked_sub(b).unwrap(), amount("1.25"));
+        assert_eq!(b.checked_sub(a), Err(AmountError::Negative));
+        assert_eq!(a.checked_mul(3).unwrap(), amount("4.50"));
+        assert_eq!(a.checked_cmp(&b), Ok(Ordering::Greater));
+        assert_eq!(a.checked_add(amount("1.5")), Err(AmountError::DecimalsMismatch(2, 1)));
+        assert_eq!(a.checked_cmp(&amount("1.5")), Err(AmountError::DecimalsMismatch(2, 1)));
+        assert_eq!(Amount::from_units(u128::MAX, 0).unwrap().checked_add(amount("1")), Err(AmountError::Overflow));
+    }
+
+    #[test]
+    fn equal_values_are_equal_whatever_the_scale() {
+        use std::collections::HashSet;
+        assert_eq!(amount("1.5"), amount("1.50"));
+        assert_eq!(amount("1.5"), amount("1.500000"));
+        assert_eq!(amount("0"), amount("0.000"));
+        assert_eq!(amount("2"), amount("2.0"));
+        assert_ne!(amount("1.5"), amount("1.05"));
+        assert_ne!(amount("15"), amount("1.5"));
+        // the text still carries the scale
+        assert_eq!((amount("1.5").to_string(), amount("1.50").to_string()), ("1.5".to_string(), "1.50".to_string()));
+        let set: HashSet<Amount> = ["1.5", "1.50", "1.500000", "0", "0.00"].into_iter().map(amount).collect();
+        assert_eq!(set.len(), 2);
+    }
+}
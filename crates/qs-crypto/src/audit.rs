@@ -0,0 +1,36 @@
+// -------- hash-chained audit log --------
+//
+// Every entry commits to the one before it:
+//   hash = SHA3-256("QuantumShield audit" || u8 v || u64be seq || lp(prev hash) || lp(body))
+// with an all-zero `prev` for the first entry. Editing, dropping or reordering
+// an entry changes every hash after it. A checkpoint is a signature by the
+// daemon's audit key over the chain head, so an attacker who can rewrite the
+// file still cannot produce a consistent, signed history without that key.
+use sha3::{Digest, Sha3_256};
+
+use crate::{Domain, put_field, sign_domain, verify_domain};
+
+pub const AUDIT_VERSION: u8 = 1;
+pub const AUDIT_GENESIS: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+const ENTRY_TAG: &[u8] = b"QuantumShield audit";
+
+pub fn audit_domain() -> Domain { Domain::new("audit-checkpoint", "quantumshield") }
+
+/// Hex hash of entry `seq` with serialized `body`, chained to `prev_hex`.
+pub fn audit_entry_hash(seq: u64, prev_hex: &str, body: &[u8]) -> String {
+    let mut buf = ENTRY_TAG.to_vec();
+    buf.push(AUDIT_VERSION);
+    buf.extend_from_slice(&seq.to_be_bytes());
+    put_field(&mut buf, prev_hex.as_bytes());
+    put_field(&mut buf, body);
+    hex::encode(Sha3_256::digest(&buf))
+}
+
+/// Signs the chain head `head_hex` (the hash of the last entry covered).
+pub fn sign_checkpoint(secret: &[u8], head_hex: &str) -> Option<Vec<u8>> {
+    sign_domain(secret, &audit_domain(), head_hex.as_bytes())
+}
+
+pub fn verify_checkpoint(public: &[u8], head_hex: &str, signed: &[u8]) -> bool {
+    verify_domain(public, &audit_domain(), signed).as_deref() == Some(head_hex.as_bytes())
+}
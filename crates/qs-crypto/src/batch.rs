@@ -0,0 +1,79 @@
+// -------- batch verification --------
+use crate::{Domain, verify_domain};
+
+/// Below this many items threads cost more than they save.
+const PARALLEL_THRESHOLD: usize = 16;
+
+#[derive(Clone, Debug)]
+pub struct BatchItem {
+    pub public: Vec<u8>,
+    pub domain: Domain,
+    pub message: Vec<u8>,
+    pub signed: Vec<u8>,
+}
+
+impl BatchItem {
+    /// Valid only if `signed` opens under `domain` to exactly `message`.
+    pub fn verify(&self) -> bool {
+        verify_domain(&self.public, &self.domain, &self.signed).as_deref() == Some(self.message.as_slice())
+    }
+}
+
+/// Verifies every item, spreading the work across available cores.
+/// Results are in input order.
+pub fn verify_batch(items: &[BatchItem]) -> Vec<bool> {
+    let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    if items.len() < PARALLEL_THRESHOLD || threads == 1 {
+        return items.iter().map(BatchItem::verify).collect();
+    }
+    let chunk = items.len().div_ceil(threads);
+    let mut out = vec![false; items.len()];
+    std::thread::scope(|s| {
+        for (src, dst) in items.chunks(chunk).zip(out.chunks_mut(chunk)) {
+            s.spawn(move || {
+                for (item, ok) in src.iter().zip(dst) {
+                    *ok = item.verify();
+                }
+            });
+        }
+    });
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_dilithium3, sign_domain};
+
+    fn items(n: usize) -> Vec<BatchItem> {
+        let kp = generate_dilithium3();
+        let domain = Domain::new("batch", "test");
+        (0..n).map(|i| {
+            let message = format!("message {i}").into_bytes();
+            let signed = sign_domain(&kp.secret, &domain, &message).unwrap();
+            BatchItem { public: kp.public.clone(), domain: domain.clone(), message, signed }
+        }).collect()
+    }
+
+    #[test]
+    fn results_keep_input_order() {
+        // enough items to take the threaded path
+        let mut batch = items(PARALLEL_THRESHOLD * 3 + 1);
+        batch[5].message = b"swapped".to_vec();
+        batch[20].domain = Domain::new("batch", "other");
+        let last = batch.len() - 1;
+        batch[last].signed[0] ^= 1;
+        let results = verify_batch(&batch);
+        for (i, ok) in results.iter().enumerate() {
+            assert_eq!(*ok, ![5, 20, last].contains(&i), "item {i}");
+        }
+    }
+
+    #[test]
+    fn small_and_empty_batches() {
+        assert!(verify_batch(&[]).is_empty());
+        let mut batch = items(3);
+        batch[1].public = generate_dilithium3().public;
+        assert_eq!(verify_batch(&batch), vec![true, false, true]);
+    }
+}
@@ -0,0 +1,127 @@
+// -------- domain-separated signing --------
+//
+// Modeled on EIP-191 personal_sign: the signed bytes are
+//   "\x19QuantumShield Signed Message:\n" || u8 len || tag || u8 len || app_id || u64be len || msg
+// so a signature made for one domain never opens under another.
+use serde::{Serialize, Deserialize};
+
+use crate::{sign_message, verify_message};
+
+pub const DOMAIN_PREFIX: &[u8] = b"\x19QuantumShield Signed Message:\n";
+pub const MAX_TAG_LEN: usize = 64;
+pub const MAX_APP_ID_LEN: usize = 128;
+
+/// Tags this crate signs its own statements under (rotation certificates,
+/// revocations, audit checkpoints, sign-in answers, typed-data digests,
+/// multisig partials). A wallet asked to sign
+/// caller-supplied bytes must refuse them, or the caller could mint those
+/// statements without the checks that go with them.
+pub const RESERVED_TAGS: &[&str] = &[
+    "key-rotation", "key-revocation", "recovery-key", "audit-checkpoint", crate::login::LOGIN_TAG,
+    crate::typed::TYPED_DATA_TAG, crate::multisig::MULTISIG_TAG, crate::qskey::EXPORT_TAG,
+];
+
+/// What a signature is for (`tag`, e.g. "chat" or "bridge") and where it is
+/// valid (`app_id`, a chain or application identifier).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Domain { pub tag: String, pub app_id: String }
+
+impl Domain {
+    pub fn new(tag: impl Into<String>, app_id: impl Into<String>) -> Self {
+        Domain { tag: tag.into(), app_id: app_id.into() }
+    }
+
+    /// Tags are non-empty printable ASCII; both fields must fit their length byte.
+    pub fn is_valid(&self) -> bool {
+        !self.tag.is_empty()
+            && self.tag.len() <= MAX_TAG_LEN
+            && self.tag.bytes().all(|b| b.is_ascii_graphic())
+            && self.app_id.len() <= MAX_APP_ID_LEN
+    }
+
+    /// True for domains only this crate's own statements may be signed under:
+    /// every [`RESERVED_TAGS`] tag, and bridge intents.
+    pub fn is_reserved(&self) -> bool {
+        RESERVED_TAGS.contains(&self.tag.as_str()) || *self == crate::intent_domain()
+    }
+
+    fn header(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(DOMAIN_PREFIX.len() + 2 + self.tag.len() + self.app_id.len());
+        out.extend_from_slice(DOMAIN_PREFIX);
+        out.push(self.tag.len() as u8);
+        out.extend_from_slice(self.tag.as_bytes());
+        out.push(self.app_id.len() as u8);
+        out.extend_from_slice(self.app_id.as_bytes());
+        out
+    }
+}
+
+/// The exact bytes that get signed for `msg` under `domain`; `None` if the domain is invalid.
+pub fn domain_message(domain: &Domain, msg: &[u8]) -> Option<Vec<u8>> {
+    if !domain.is_valid() { return None; }
+    let mut out = domain.header();
+    out.extend_from_slice(&(msg.len() as u64).to_be_bytes());
+    out.extend_from_slice(msg);
+    Some(out)
+}
+
+pub fn sign_domain(secret: &[u8], domain: &Domain, msg: &[u8]) -> Option<Vec<u8>> {
+    Some(sign_message(secret, &domain_message(domain, msg)?))
+}
+
+/// Opens `signed` and returns the original message, only if it was signed under `domain`.
+pub fn verify_domain(public: &[u8], domain: &Domain, signed: &[u8]) -> Option<Vec<u8>> {
+    if !domain.is_valid() { return None; }
+    let opened = verify_message(public, signed)?;
+    let rest = opened.strip_prefix(domain.header().as_slice())?;
+    if rest.len() < 8 { return None; }
+    let (len, msg) = rest.split_at(8);
+    if u64::from_be_bytes(len.try_into().ok()?) != msg.len() as u64 { return None; }
+    Some(msg.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_dilithium3;
+
+    #[test]
+    fn signed_bytes_follow_the_documented_layout() {
+        let bytes = domain_message(&Domain::new("chat", "app"), b"hi").unwrap();
+        let mut expected = DOMAIN_PREFIX.to_vec();
+        expected.extend_from_slice(b"\x04chat\x03app");
+        expected.extend_from_slice(&2u64.to_be_bytes());
+        expected.extend_from_slice(b"hi");
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn opens_only_under_the_signing_domain() {
+        let kp = generate_dilithium3();
+        let domain = Domain::new("chat", "app");
+        let signed = sign_domain(&kp.secret, &domain, b"hello").unwrap();
+        assert_eq!(verify_domain(&kp.public, &domain, &signed).as_deref(), Some(&b"hello"[..]));
+        assert_eq!(verify_domain(&kp.public, &Domain::new("chat", "other"), &signed), None);
+        assert_eq!(verify_domain(&kp.public, &Domain::new("chats", "app"), &signed), None);
+        // raw signatures never open as domain-separated ones
+        assert_eq!(verify_domain(&kp.public, &domain, &crate::sign_message(&kp.secret, b"hello")), None);
+    }
+
+    #[test]
+    fn rejects_invalid_domains() {
+        assert!(domain_message(&Domain::new("", "app"), b"x").is_none());
+        assert!(domain_message(&Domain::new("has space", "app"), b"x").is_none());
+        assert!(domain_message(&Domain::new("t".repeat(MAX_TAG_LEN + 1), "app"), b"x").is_none());
+        assert!(domain_message(&Domain::new("chat", "a".repeat(MAX_APP_ID_LEN + 1)), b"x").is_none());
+    }
+
+    #[test]
+    fn reserved_domains() {
+        for tag in RESERVED_TAGS {
+            assert!(Domain::new(*tag, "anything").is_reserved(), "{tag}");
+        }
+        assert!(crate::intent_domain().is_reserved());
+        assert!(!Domain::new("bridge", "solana").is_reserved());
+        assert!(!Domain::new("chat", "quantumshield").is_reserved());
+    }
+}
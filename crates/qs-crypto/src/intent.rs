@@ -0,0 +1,366 @@
+// -------- bridge intents --------
+//
+// A wallet's signed request to move tokens across the bridge. The signature
+// covers a canonical encoding rather than JSON text, so re-serializing an
+// intent never changes what was signed:
+//
+//   "QuantumShield bridge intent" || u8 v || lp(source) || lp(dest_chain)
+//     || lp(dest_address) || lp(token) || u128be amount units || u8 decimals
+//     || lp(nonce) || u64be issued_at || u64be expires_at
+//
+// (lp = u32be length || bytes) signed under the domain ("bridge", "intent").
+// The signer picks the nonce; a verifier remembers (source, nonce) until the
+// intent expires so each intent is acted on once.
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::{Amount, Domain, address_from_pubkey, put_field, sign_domain, verify_domain};
+
+/// 2: `amount` is one decimal string carrying its scale. Version 1 sent base
+/// units and `decimals` as separate fields and is no longer accepted.
+pub const INTENT_VERSION: u8 = 2;
+const INTENT_TAG: &[u8] = b"QuantumShield bridge intent";
+/// How far a verifier's clock may be behind the signer's.
+pub const INTENT_CLOCK_SKEW_SECS: u64 = 60;
+const MAX_FIELD_LEN: usize = 256;
+const MIN_NONCE_LEN: usize = 16;
+
+pub fn intent_domain() -> Domain { Domain::new("bridge", "intent") }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BridgeIntent {
+    pub v: u8,
+    /// QS address of the signing wallet.
+    pub source: String,
+    /// Chain the tokens go to, e.g. "solana" or "evm:1".
+    pub dest_chain: String,
+    pub dest_address: String,
+    /// Token identifier on the destination chain (mint or contract address).
+    pub token: String,
+    /// Written at the token's decimals ("1.500000" for a 6-decimal mint). The
+    /// scale is signed, so bridges refuse any other spelling of the value.
+    pub amount: Amount,
+    pub nonce: String,
+    pub issued_at: u64,
+    pub expires_at: u64,
+}
+
+/// An intent with the signer's public key and signature.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SignedIntent {
+    pub intent: BridgeIntent,
+    pub public_key_hex: String,
+    pub signed_hex: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntentError {
+    Malformed(String),
+    NotYetValid,
+    Expired,
+    BadSignature,
+    /// This (source, nonce) was already used.
+    Replayed,
+}
+
+impl fmt::Display for IntentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntentError::Malformed(m) => write!(f, "bridge intent: malformed: {m}"),
+            IntentError::NotYetValid  => write!(f, "bridge intent: not valid yet"),
+            IntentError::Expired      => write!(f, "bridge intent: expired"),
+            IntentError::BadSignature => write!(f, "bridge intent: signature does not verify"),
+            IntentError::Replayed     => write!(f, "bridge intent: nonce already used"),
+        }
+    }
+}
+
+impl std::error::Error for IntentError {}
+
+/// Remembers used intent nonces.
+pub trait IntentNonces {
+    /// Whether `key` was used by an intent still unexpired at `now`.
+    fn was_used(&self, key: &str, now: u64) -> bool;
+    /// Records `key` as used until `expires_at`; false if it was already used.
+    fn first_use(&mut self, key: &str, expires_at: u64, now: u64) -> bool;
+}
+
+fn malformed<T>(msg: impl Into<String>) -> Result<T, IntentError> { Err(IntentError::Malformed(msg.into())) }
+
+fn single_line(field: &str, value: &str) -> Result<(), IntentError> {
+    if value.is_empty() || value.len() > MAX_FIELD_LEN {
+        return malformed(format!("{field} must be 1 to {MAX_FIELD_LEN} bytes"));
+    }
+    if value.chars().any(char::is_control) {
+        return malformed(format!("{field} must be a single line"));
+    }
+    Ok(())
+}
+
+impl BridgeIntent {
+    /// An intent from `source` with a random nonce, valid for `ttl_secs` from `now`.
+    pub fn new(
+        source: &str, dest_chain: &str, dest_address: &str, token: &str, amount: Amount, now: u64, ttl_secs: u64,
+    ) -> Result<Self, IntentError> {
+        let mut nonce = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let intent = BridgeIntent {
+            v: INTENT_VERSION,
+            source: source.to_string(),
+            dest_chain: dest_chain.to_string(),
+            dest_address: dest_address.to_string(),
+            token: token.to_string(),
+            amount,
+            nonce: hex::encode(nonce),
+            issued_at: now,
+            expires_at: now.saturating_add(ttl_secs),
+        };
+        intent.validate()?;
+        Ok(intent)
+    }
+
+    pub fn validate(&self) -> Result<(), IntentError> {
+        if self.v == 1 {
+            return malformed("version 1 intents are no longer accepted; sign the intent again");
+        }
+        if self.v != INTENT_VERSION {
+            return malformed(format!("unsupported version {}", self.v));
+        }
+        for (field, value) in [
+            ("source", &self.source), ("dest_chain", &self.dest_chain),
+            ("dest_address", &self.dest_address), ("token", &self.token),
+        ] {
+            single_line(field, value)?;
+        }
+        if self.amount.is_zero() {
+            return malformed("amount must be positive");
+        }
+        if self.nonce.len() < MIN_NONCE_LEN || self.nonce.len() > MAX_FIELD_LEN || !self.nonce.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return malformed(format!("nonce must be at least {MIN_NONCE_LEN} letters or digits"));
+        }
+        if self.expires_at <= self.issued_at {
+            return malformed("expires_at must be after issued_at");
+        }
+        Ok(())
+    }
+
+    /// The bytes that get signed.
+    pub fn canonical_bytes(&self) -> Vec<u8> {
+        let mut out = INTENT_TAG.to_vec();
+        out.push(self.v);
+        for field in [&self.source, &self.dest_chain, &self.dest_address, &self.token] {
+            put_field(&mut out, field.as_bytes());
+        }
+        out.extend_from_slice(&self.amount.units().to_be_bytes());
+        out.push(self.amount.decimals());
+        put_field(&mut out, self.nonce.as_bytes());
+        out.extend_from_slice(&self.issued_at.to_be_bytes());
+        out.extend_from_slice(&self.expires_at.to_be_bytes());
+        out
+    }
+
+    /// Key under which a verifier remembers this intent's nonce.
+    pub fn replay_key(&self) -> String { format!("{}:{}", self.source, self.nonce) }
+
+    fn check_time(&self, now: u64) -> Result<(), IntentError> {
+        if now.saturating_add(INTENT_CLOCK_SKEW_SECS) < self.issued_at {
+            return Err(IntentError::NotYetValid);
+        }
+        if now >= self.expires_at {
+            return Err(IntentError::Expired);
+        }
+        Ok(())
+    }
+}
+
+/// Signs `intent` with the key (`public`, `secret`), which must be its source.
+pub fn sign_intent(public: &[u8], secret: &[u8], intent: &BridgeIntent) -> Result<SignedIntent, IntentError> {
+    intent.validate()?;
+    if address_from_pubkey(public) != intent.source {
+        return malformed("source is not the signing key's address");
+    }
+    let signed = sign_domain(secret, &intent_domain(), &intent.canonical_bytes())
+        .ok_or_else(|| IntentError::Malformed("signing failed".into()))?;
+    Ok(SignedIntent { intent: intent.clone(), public_key_hex: hex::encode(public), signed_hex: hex::encode(signed) })
+}
+
+/// Checks an intent at `now` as [`verify_intent`] does, without recording
+/// its nonce: for deciding whether to act on it before committing to.
+pub fn check_intent(signed: &SignedIntent, now: u64, nonces: &impl IntentNonces) -> Result<Vec<u8>, IntentError> {
+    let public = check_signed(signed, now)?;
+    if nonces.was_used(&signed.intent.replay_key(), now) {
+        return Err(IntentError::Replayed);
+    }
+    Ok(public)
+}
+
+/// Checks an intent at `now` and records its nonce; returns the signer's public key.
+pub fn verify_intent(signed: &SignedIntent, now: u64, nonces: &mut impl IntentNonces) -> Result<Vec<u8>, IntentError> {
+    let public = check_signed(signed, now)?;
+    // last, so a forged intent cannot use up someone else's nonce
+    let intent = &signed.intent;
+    if !nonces.first_use(&intent.replay_key(), intent.expires_at, now) {
+        return Err(IntentError::Replayed);
+    }
+    Ok(public)
+}
+
+/// Everything but the nonce: shape, time, key and signature.
+fn check_signed(signed: &SignedIntent, now: u64) -> Result<Vec<u8>, IntentError> {
+    let intent = &signed.intent;
+    intent.validate()?;
+    intent.check_time(now)?;
+    let public = hex::decode(&signed.public_key_hex).or_else(|_| malformed("public_key_hex is not hex"))?;
+    if address_from_pubkey(&public) != intent.source {
+        return malformed("source does not belong to public_key_hex");
+    }
+    let sig = hex::decode(&signed.signed_hex).or_else(|_| malformed("signed_hex is not hex"))?;
+    if verify_domain(&public, &intent_domain(), &sig).as_deref() != Some(intent.canonical_bytes().as_slice()) {
+        return Err(IntentError::BadSignature);
+    }
+    Ok(public)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const NOW: u64 = 1_700_000_000;
+
+    #[derive(Default)]
+    struct Used(HashMap<String, u64>);
+
+    impl IntentNonces for Used {
+        fn was_used(&self, key: &str, now: u64) -> bool {
+            self.0.get(key).is_some_and(|exp| *exp > now)
+        }
+
+        fn first_use(&mut self, key: &str, expires_at: u64, now: u64) -> bool {
+            self.0.retain(|_, exp| *exp > now);
+            self.0.insert(key.to_string(), expires_at).is_none()
+        }
+    }
+
+    fn intent(source: &str, amount: &str) -> BridgeIntent {
+        BridgeIntent::new(source, "solana", "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM", "NOVA", Amount::parse(amount).unwrap(), NOW, 600).unwrap()
+    }
+
+    #[test]
+    fn intents_verify_once() {
+        let kp = crate::generate_dilithium3();
+        let signed = sign_intent(&kp.public, &kp.secret, &intent(&address_from_pubkey(&kp.public), "1.500000")).unwrap();
+        let mut used = Used::default();
+        assert_eq!(verify_intent(&signed, NOW + 1, &mut used).unwrap(), kp.public);
+        assert_eq!(verify_intent(&signed, NOW + 1, &mut used), Err(IntentError::Replayed));
+        assert_eq!(verify_intent(&signed, NOW + 600, &mut used), Err(IntentError::Expired));
+        assert_eq!(verify_intent(&signed, NOW - INTENT_CLOCK_SKEW_SECS - 1, &mut Used::default()), Err(IntentError::NotYetValid));
+    }
+
+    #[test]
+    fn every_field_is_signed() {
+        let kp = crate::generate_dilithium3();
+        let signed = sign_intent(&kp.public, &kp.secret, &intent(&address_from_pubkey(&kp.public), "1.500000")).unwrap();
+        let edits: [fn(&mut BridgeIntent); 7] = [
+            |i| i.dest_chain = "evm:1".into(),
+            |i| i.dest_address = "attacker".into(),
+            |i| i.token = "OTHER".into(),
+            |i| i.amount = Amount::parse("15.00000").unwrap(),
+            |i| i.nonce = "ffffffffffffffffffffffffffffffff".into(),
+            |i| i.issued_at -= 1,
+            |i| i.expires_at += 1,
+        ];
+        let mut used = Used::default();
+        for edit in edits {
+            let mut forged = signed.clone();
+            edit(&mut forged.intent);
+            assert_eq!(verify_intent(&forged, NOW, &mut used), Err(IntentError::BadSignature));
+        }
+        // forgeries did not use up the nonce
+        assert!(verify_intent(&signed, NOW, &mut used).is_ok());
+    }
+
+    #[test]
+    fn only_the_source_can_sign() {
+        let (kp, other) = (crate::generate_dilithium3(), crate::generate_dilithium3());
+        let theirs = intent(&address_from_pubkey(&other.public), "1.0");
+        assert!(matches!(sign_intent(&kp.public, &kp.secret, &theirs), Err(IntentError::Malformed(_))));
+
+        let mut signed = sign_intent(&kp.public, &kp.secret, &intent(&address_from_pubkey(&kp.public), "1.0")).unwrap();
+        signed.public_key_hex = hex::encode(&other.public);
+        assert!(matches!(verify_intent(&signed, NOW, &mut Used::default()), Err(IntentError::Malformed(_))));
+    }
+
+    #[test]
+    fn canonical_bytes_carry_amount_and_scale() {
+        let i = intent("QSsource", "1.5");
+        let bytes = i.canonical_bytes();
+        assert!(bytes.starts_with(b"QuantumShield bridge intent"));
+        assert_eq!(bytes[INTENT_TAG.len()], INTENT_VERSION);
+        let mut amount = 15u128.to_be_bytes().to_vec();
+        amount.push(1);
+        assert!(bytes.windows(17).any(|w| w == amount.as_slice()));
+        // the same value at another scale signs differently, which is why
+        // bridges only take the mint's scale
+        let rescaled = BridgeIntent { amount: Amount::parse("1.50").unwrap(), ..i.clone() };
+        assert_ne!(rescaled.canonical_bytes(), bytes);
+        assert_eq!(i.replay_key(), format!("QSsource:{}", i.nonce));
+    }
+
+    #[test]
+    fn malformed_intents_are_refused() {
+        let ok = intent("QSsource", "1.0");
+        assert!(ok.validate().is_ok());
+        let broken: [fn(&mut BridgeIntent); 8] = [
+            |i| i.v = 1,
+            |i| i.v = INTENT_VERSION + 1,
+            |i| i.source = String::new(),
+            |i| i.dest_address = "line\nbreak".into(),
+            |i| i.token = "x".repeat(MAX_FIELD_LEN + 1),
+            |i| i.amount = Amount::zero(6).unwrap(),
+            |i| i.nonce = "short".into(),
+            |i| i.expires_at = i.issued_at,
+        ];
+        for edit in broken {
+            let mut i = ok.clone();
+            edit(&mut i);
+            assert!(matches!(i.validate(), Err(IntentError::Malformed(_))), "{i:?}");
+        }
+    }
+
+    #[test]
+    fn version_1_intents_are_refused_without_using_the_nonce() {
+        // v1 sent base units and decimals apart; read as v2 the amount would be 1_500_000 whole tokens
+        let v1: SignedIntent = serde_json::from_value(serde_json::json!({
+            "intent": {
+                "v": 1, "source": "QSsource", "dest_chain": "solana", "dest_address": "x", "token": "NOVA",
+                "amount": "1500000", "decimals": 6, "nonce": "00112233445566778899aabbccddeeff",
+                "issued_at": NOW, "expires_at": NOW + 600,
+            },
+            "public_key_hex": "00",
+            "signed_hex": "00",
+        }))
+        .unwrap();
+        let mut used = Used::default();
+        let err = verify_intent(&v1, NOW, &mut used).unwrap_err();
+        assert!(err.to_string().contains("version 1"), "{err}");
+        assert!(used.0.is_empty());
+    }
+
+    #[test]
+    fn checking_leaves_the_nonce_unused() {
+        let kp = crate::generate_dilithium3();
+        let signed = sign_intent(&kp.public, &kp.secret, &intent(&address_from_pubkey(&kp.public), "1.500000")).unwrap();
+        let mut used = Used::default();
+        assert_eq!(check_intent(&signed, NOW, &used).unwrap(), kp.public);
+        assert_eq!(check_intent(&signed, NOW, &used).unwrap(), kp.public);
+        let mut forged = signed.clone();
+        forged.intent.amount = Amount::parse("150.000000").unwrap();
+        assert_eq!(check_intent(&forged, NOW, &used), Err(IntentError::BadSignature));
+        assert_eq!(check_intent(&signed, NOW + 600, &used), Err(IntentError::Expired));
+
+        verify_intent(&signed, NOW, &mut used).unwrap();
+        assert_eq!(check_intent(&signed, NOW, &used), Err(IntentError::Replayed));
+    }
+}
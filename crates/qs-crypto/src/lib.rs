@@ -4,20 +4,75 @@ use rand::RngCore;
 use zeroize::Zeroize;
 
 // --- encryption bits ---
-use aes_gcm::{Aes256Gcm, KeyInit, aead::{Aead, OsRng, generic_array::GenericArray}};
-use argon2::{Argon2, password_hash::SaltString, Algorithm, Params, Version};
+use aes_gcm::{Aes256Gcm, KeyInit, aead::{Aead, OsRng, Payload, generic_array::GenericArray}};
+use argon2::{Argon2, Algorithm, Params, Version};
 use aead::AeadCore;
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+
+mod domain;
+pub use domain::{Domain, DOMAIN_PREFIX, domain_message, sign_domain, verify_domain};
+mod typed;
+pub use typed::{TypedData, TypedDataError, TypedField, sign_typed, typed_data_domain, verify_typed};
+mod batch;
+pub use batch::{BatchItem, verify_batch};
+mod qskey;
+pub use qskey::{EXPORT_TAG, EncBlob, QsKeyFile, WebKey, build_qskey, export_domain, open_qskey};
+mod spki;
+pub use spki::{scheme_oid, spki_der, spki_pem};
+mod rotation;
+pub use rotation::{RotationCert, RotationError, issue_rotation, rotation_domain, verify_rotation_chain};
+mod multisig;
+pub use multisig::{
+    MAX_MULTISIG_MEMBERS, MULTISIG_TAG, MultisigBundle, MultisigError, MultisigPolicy, PartialSig, multisig_domain,
+    multisig_message, sign_multisig,
+};
+mod revocation;
+pub use revocation::{
+    RecoveryDesignation, Revocation, RevocationError, designate_recovery, issue_revocation, recovery_domain,
+    revocation_domain,
+};
+mod audit;
+pub use audit::{AUDIT_GENESIS, audit_domain, audit_entry_hash, sign_checkpoint, verify_checkpoint};
+mod login;
+pub use login::{
+    LOGIN_CLOCK_SKEW_SECS, LoginChallenge, LoginError, NonceStore, SignedLogin, sign_login, verify_login,
+};
+mod amount;
+pub use amount::{Amount, AmountError, MAX_DECIMALS};
+mod intent;
+pub use intent::{
+    INTENT_CLOCK_SKEW_SECS, BridgeIntent, IntentError, IntentNonces, SignedIntent, check_intent, intent_domain, sign_intent, verify_intent,
+};
+pub mod solana;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct DilithiumKeypair { pub public: Vec<u8>, pub secret: Vec<u8> }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct EncryptedKeyfile {
+    #[serde(default = "keyfile_v1")]
+    pub v: u8,              // absent in files written before v2
     pub kdf: String,        // "argon2id"
+    #[serde(default)]
+    pub kdf_params: KdfParams,
+    #[serde(default)]
+    pub scheme: String,     // empty in v1 files
     pub salt_b64: String,
     pub nonce_b64: String,
     pub ct_b64: String,     // ciphertext of secret key bytes
-    pub public_hex: String, // convenience
+    pub public_hex: String, // authenticated in v2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kem: Option<EncryptedKem>, // KEM keypair carried over from a web keyfile
+}
+
+fn keyfile_v1() -> u8 { 1 }
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EncryptedKem {
+    pub scheme: String,
+    pub public_b64: String,
+    pub nonce_b64: String,
+    pub ct_b64: String,     // ciphertext of KEM secret key bytes
 }
 
 // ---------------- PQ (Dilithium) ----------------
@@ -30,17 +85,18 @@ mod pq_impl {
         PublicKey as PublicKeyTrait,
         SecretKey as SecretKeyTrait,
         SignedMessage as SignedMessageTrait,
-        Message as MessageTrait,
     };
 }
 #[cfg(feature = "pq")]
 use pq_impl::*;
 
 // ---------------- Ed25519 fallback ----------------
-#[cfg(feature = "ed25519")]
+// Used only when `pq` is off, so a workspace build that unifies both
+// features still has exactly one scheme.
+#[cfg(all(feature = "ed25519", not(feature = "pq")))]
 mod ed_impl {
-    use ed25519_dalek::{SigningKey, VerifyingKey, Signature, Signer, Verifier};
-    use rand_core::OsRng;
+    use ed25519_dalek::{SigningKey, VerifyingKey, Signature, Signer};
+    use rand::rngs::OsRng;
 
     pub fn keypair() -> (Vec<u8>, Vec<u8>) {
         let sk = SigningKey::generate(&mut OsRng);
@@ -62,17 +118,30 @@ mod ed_impl {
         vk.verify(msg, &sig).ok()?;
         Some(msg.to_vec())
     }
+    pub fn matches(pk_bytes: &[u8], sk_bytes: &[u8]) -> bool {
+        let Ok(sk) = <[u8; 32]>::try_from(sk_bytes) else { return false };
+        SigningKey::from_bytes(&sk).verifying_key().to_bytes().as_slice() == pk_bytes
+    }
+    pub fn valid_public(pk_bytes: &[u8]) -> bool {
+        <[u8; 32]>::try_from(pk_bytes).is_ok_and(|pk| VerifyingKey::from_bytes(&pk).is_ok())
+    }
 }
-#[cfg(feature = "ed25519")]
+#[cfg(all(feature = "ed25519", not(feature = "pq")))]
 use ed_impl::*;
 
+/// Name of the signature scheme this build signs and verifies with.
+#[cfg(feature = "pq")]
+pub const SCHEME: &str = "dilithium3";
+#[cfg(all(feature = "ed25519", not(feature = "pq")))]
+pub const SCHEME: &str = "ed25519";
+
 pub fn generate_dilithium3() -> DilithiumKeypair {
     #[cfg(feature = "pq")]
     {
         let (pk, sk) = keypair();
         DilithiumKeypair { public: pk.as_bytes().to_vec(), secret: sk.as_bytes().to_vec() }
     }
-    #[cfg(feature = "ed25519")]
+    #[cfg(all(feature = "ed25519", not(feature = "pq")))]
     {
         let (pk, sk) = keypair();
         DilithiumKeypair { public: pk, secret: sk }
@@ -92,11 +161,36 @@ pub fn sign_message(secret: &[u8], msg: &[u8]) -> Vec<u8> {
     {
         let sk = SecretKey::from_bytes(secret).expect("invalid secret key");
         let sm: SignedMessage = sign(msg, &sk);
-        return sm.as_bytes().to_vec();
+        sm.as_bytes().to_vec()
     }
-    #[cfg(feature = "ed25519")]
+    #[cfg(all(feature = "ed25519", not(feature = "pq")))]
     {
-        return ed_impl::sign(msg, secret);
+        ed_impl::sign(msg, secret)
+    }
+}
+
+/// True if `secret` is well-formed and is the signing half of `public`.
+/// Never panics on malformed input, unlike `sign_message`.
+#[cfg(feature = "pq")]
+pub fn keypair_matches(public: &[u8], secret: &[u8]) -> bool {
+    let (Ok(pk), Ok(sk)) = (PublicKey::from_bytes(public), SecretKey::from_bytes(secret)) else { return false };
+    let sm = sign(b"keypair-check", &sk);
+    open(&sm, &pk).is_ok()
+}
+#[cfg(all(feature = "ed25519", not(feature = "pq")))]
+pub fn keypair_matches(public: &[u8], secret: &[u8]) -> bool {
+    ed_impl::matches(public, secret)
+}
+
+/// True if `public` decodes as a public key of this build's scheme.
+pub fn public_key_valid(public: &[u8]) -> bool {
+    #[cfg(feature = "pq")]
+    {
+        PublicKey::from_bytes(public).is_ok()
+    }
+    #[cfg(all(feature = "ed25519", not(feature = "pq")))]
+    {
+        ed_impl::valid_public(public)
     }
 }
 
@@ -105,49 +199,331 @@ pub fn verify_message(public: &[u8], signed: &[u8]) -> Option<Vec<u8>> {
     {
         let pk = PublicKey::from_bytes(public).ok()?;
         let sm = SignedMessage::from_bytes(signed).ok()?;
-        let m = open(&sm, &pk).ok()?;
-        return Some(m.as_bytes().to_vec());
+        open(&sm, &pk).ok()
     }
-    #[cfg(feature = "ed25519")]
+    #[cfg(all(feature = "ed25519", not(feature = "pq")))]
     {
-        return ed_impl::open(signed, public);
+        ed_impl::open(signed, public)
     }
 }
 
 // -------- keystore helpers (common) --------
-fn derive_key(password: &str, salt: &[u8]) -> [u8; 32] {
-    let params = Params::new(32, 3, 1, None).expect("argon2 params");
+//
+// v2 keyfiles authenticate everything stored in the clear (wallet name,
+// scheme, public key, KDF parameters) as AES-GCM associated data, so editing
+// any of them makes the file fail to open. v1 files predate this and are only
+// protected by the keypair check in `decrypt_secret`.
+
+/// Current keyfile format version.
+pub const KEYFILE_VERSION: u8 = 2;
+
+const KEYFILE_AAD_TAG: &[u8] = b"QuantumShield keyfile v2";
+// refuse files asking for more work than this; the params come from the file
+const MAX_ARGON2_M_COST: u32 = 1 << 20; // KiB
+const MAX_ARGON2_T_COST: u32 = 16;
+const MAX_ARGON2_P_COST: u32 = 16;
+
+/// Argon2id cost parameters; the defaults are what every keyfile so far used.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KdfParams { pub m_cost: u32, pub t_cost: u32, pub p_cost: u32 }
+
+impl Default for KdfParams {
+    fn default() -> Self { KdfParams { m_cost: 32, t_cost: 3, p_cost: 1 } }
+}
+
+/// Why a keyfile did not open. A wrong password and an edited v2 file are
+/// indistinguishable to AES-GCM, so both surface as `BadPassword`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyfileError {
+    BadPassword,
+    /// Fields could not be decoded, or the format is unsupported.
+    Malformed,
+    /// Decrypted fine, but the secret is not the signing half of `public_hex`.
+    KeyMismatch,
+}
+
+impl std::fmt::Display for KeyfileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            KeyfileError::BadPassword => "bad password or modified keyfile",
+            KeyfileError::Malformed   => "malformed keyfile",
+            KeyfileError::KeyMismatch => "keyfile secret does not match its public key",
+        })
+    }
+}
+
+impl std::error::Error for KeyfileError {}
+
+/// Params argon2 accepts and that stay within the caps above.
+fn kdf_params_ok(kdf: &KdfParams) -> bool {
+    kdf.m_cost <= MAX_ARGON2_M_COST
+        && kdf.t_cost <= MAX_ARGON2_T_COST
+        && kdf.p_cost <= MAX_ARGON2_P_COST
+        && Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, None).is_ok()
+}
+
+fn derive_key_with(password: &str, salt: &[u8], kdf: &KdfParams) -> Option<[u8; 32]> {
+    if !kdf_params_ok(kdf) { return None; }
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, None).ok()?;
     let argon = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
     let mut out = [0u8; 32];
-    argon.hash_password_into(password.as_bytes(), salt, &mut out).expect("kdf");
-    out
+    argon.hash_password_into(password.as_bytes(), salt, &mut out).ok()?;
+    Some(out)
 }
 
-pub fn encrypt_secret(public: &[u8], secret: &[u8], password: &str) -> EncryptedKeyfile {
+fn derive_key(password: &str, salt: &[u8]) -> [u8; 32] {
+    derive_key_with(password, salt, &KdfParams::default()).expect("kdf")
+}
+
+/// Appends `field` as u32be length || bytes; shared by every signed or
+/// authenticated statement in this crate.
+pub(crate) fn put_field(out: &mut Vec<u8>, field: &[u8]) {
+    out.extend_from_slice(&(field.len() as u32).to_be_bytes());
+    out.extend_from_slice(field);
+}
+
+/// Associated data for a v2 keyfile stored as `name`.
+fn keyfile_aad(ek: &EncryptedKeyfile, name: &str) -> Vec<u8> {
+    let mut aad = KEYFILE_AAD_TAG.to_vec();
+    for field in [name.as_bytes(), ek.scheme.as_bytes(), ek.public_hex.as_bytes(), ek.kdf.as_bytes()] {
+        put_field(&mut aad, field);
+    }
+    for cost in [ek.kdf_params.m_cost, ek.kdf_params.t_cost, ek.kdf_params.p_cost] {
+        aad.extend_from_slice(&cost.to_be_bytes());
+    }
+    aad
+}
+
+/// Associated data for the KEM half: the keyfile's own AAD plus the KEM's clear fields.
+fn kem_aad(ek: &EncryptedKeyfile, kem: &EncryptedKem, name: &str) -> Vec<u8> {
+    let mut aad = keyfile_aad(ek, name);
+    put_field(&mut aad, kem.scheme.as_bytes());
+    put_field(&mut aad, kem.public_b64.as_bytes());
+    aad
+}
+
+/// Salt and key for opening `ek`, checking the salt and params it asks for.
+fn keyfile_key(ek: &EncryptedKeyfile, password: &str) -> Result<[u8; 32], KeyfileError> {
+    if ek.kdf != "argon2id" || ek.v == 0 || ek.v > KEYFILE_VERSION { return Err(KeyfileError::Malformed); }
+    let salt = B64.decode(&ek.salt_b64).map_err(|_| KeyfileError::Malformed)?;
+    // argon2 rejects salts under 8 bytes
+    if salt.len() < 8 { return Err(KeyfileError::Malformed); }
+    derive_key_with(password, &salt, &ek.kdf_params).ok_or(KeyfileError::Malformed)
+}
+
+fn open_aead(key: &[u8; 32], nonce_b64: &str, ct_b64: &str, aad: &[u8]) -> Result<Vec<u8>, KeyfileError> {
+    let nonce = B64.decode(nonce_b64).map_err(|_| KeyfileError::Malformed)?;
+    let ct = B64.decode(ct_b64).map_err(|_| KeyfileError::Malformed)?;
+    if nonce.len() != 12 { return Err(KeyfileError::Malformed); }
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(key));
+    cipher.decrypt(GenericArray::from_slice(&nonce), Payload { msg: &ct, aad }).map_err(|_| KeyfileError::BadPassword)
+}
+
+/// Encrypts `secret` into a v2 keyfile that only opens under the same `name`.
+pub fn encrypt_secret(name: &str, public: &[u8], secret: &[u8], password: &str) -> EncryptedKeyfile {
     let mut salt = [0u8; 16]; OsRng.fill_bytes(&mut salt);
+    let mut ek = EncryptedKeyfile {
+        v: KEYFILE_VERSION,
+        kdf: "argon2id".to_string(),
+        kdf_params: KdfParams::default(),
+        scheme: SCHEME.to_string(),
+        salt_b64: B64.encode(salt),
+        nonce_b64: String::new(),
+        ct_b64: String::new(),
+        public_hex: hex::encode(public),
+        kem: None,
+    };
     let key = derive_key(password, &salt);
     let cipher = Aes256Gcm::new(GenericArray::from_slice(&key));
     let nonce = Aes256Gcm::generate_nonce(&mut OsRng); // 12 bytes
-    let ct = cipher.encrypt(&nonce, secret).expect("encrypt");
+    let aad = keyfile_aad(&ek, name);
+    let ct = cipher.encrypt(&nonce, Payload { msg: secret, aad: &aad }).expect("encrypt");
+    ek.nonce_b64 = B64.encode(nonce);
+    ek.ct_b64 = B64.encode(ct);
+    let mut key_z = key; key_z.zeroize();
+    ek
+}
 
-    let ek = EncryptedKeyfile {
-        kdf: "argon2id".to_string(),
-        salt_b64: base64::encode(salt),
-        nonce_b64: base64::encode(nonce),
-        ct_b64: base64::encode(ct),
-        public_hex: hex::encode(public),
+/// Decrypts the signing secret of keyfile `name` and checks it against `public_hex`.
+pub fn decrypt_secret(ek: &EncryptedKeyfile, name: &str, password: &str) -> Result<Vec<u8>, KeyfileError> {
+    let key = keyfile_key(ek, password)?;
+    let aad = if ek.v >= 2 { keyfile_aad(ek, name) } else { Vec::new() };
+    let pt = open_aead(&key, &ek.nonce_b64, &ek.ct_b64, &aad);
+    let mut key_z = key; key_z.zeroize();
+    let pt = pt?;
+    let public = hex::decode(&ek.public_hex).map_err(|_| KeyfileError::Malformed)?;
+    if !keypair_matches(&public, &pt) {
+        return Err(KeyfileError::KeyMismatch);
+    }
+    Ok(pt)
+}
+
+/// Checks what can be checked without the password: format and KDF
+/// parameters, that every field decodes, and that `public_hex` is a key of
+/// this build's scheme when the file claims it.
+pub fn check_keyfile(ek: &EncryptedKeyfile) -> Result<(), KeyfileError> {
+    let require = |ok: bool| if ok { Ok(()) } else { Err(KeyfileError::Malformed) };
+    let decode = |b64: &str| B64.decode(b64).map_err(|_| KeyfileError::Malformed);
+    // AES-GCM appends a 16-byte tag to every ciphertext
+    let sealed = |nonce_b64: &str, ct_b64: &str| -> Result<(), KeyfileError> {
+        require(decode(nonce_b64)?.len() == 12 && decode(ct_b64)?.len() > 16)
     };
+    require(ek.kdf == "argon2id" && ek.v != 0 && ek.v <= KEYFILE_VERSION && kdf_params_ok(&ek.kdf_params))?;
+    require(decode(&ek.salt_b64)?.len() >= 8)?;
+    sealed(&ek.nonce_b64, &ek.ct_b64)?;
+    let public = hex::decode(&ek.public_hex).map_err(|_| KeyfileError::Malformed)?;
+    if ek.scheme.is_empty() || ek.scheme == SCHEME {
+        require(public_key_valid(&public))?;
+    }
+    if let Some(kem) = &ek.kem {
+        decode(&kem.public_b64)?;
+        sealed(&kem.nonce_b64, &kem.ct_b64)?;
+    }
+    Ok(())
+}
+
+/// Seals a KEM keypair into `ek` under the same password-derived key as the
+/// signing secret. `None` if the keyfile is malformed.
+pub fn attach_kem(ek: &mut EncryptedKeyfile, name: &str, scheme: &str, public: &[u8], secret: &[u8], password: &str) -> Option<()> {
+    let key = keyfile_key(ek, password).ok()?;
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(&key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let mut kem = EncryptedKem {
+        scheme: scheme.to_string(),
+        public_b64: B64.encode(public),
+        nonce_b64: B64.encode(nonce),
+        ct_b64: String::new(),
+    };
+    let aad = if ek.v >= 2 { kem_aad(ek, &kem, name) } else { Vec::new() };
+    let ct = cipher.encrypt(&nonce, Payload { msg: secret, aad: &aad }).ok();
     let mut key_z = key; key_z.zeroize();
-    ek
+    kem.ct_b64 = B64.encode(ct?);
+    ek.kem = Some(kem);
+    Some(())
+}
+
+/// Returns (scheme, public, secret) of the attached KEM keypair, if any.
+pub fn decrypt_kem(ek: &EncryptedKeyfile, name: &str, password: &str) -> Option<(String, Vec<u8>, Vec<u8>)> {
+    let kem = ek.kem.as_ref()?;
+    let key = keyfile_key(ek, password).ok()?;
+    let aad = if ek.v >= 2 { kem_aad(ek, kem, name) } else { Vec::new() };
+    let pt = open_aead(&key, &kem.nonce_b64, &kem.ct_b64, &aad).ok();
+    let mut key_z = key; key_z.zeroize();
+    Some((kem.scheme.clone(), B64.decode(&kem.public_b64).ok()?, pt?))
 }
 
-pub fn decrypt_secret(ek: &EncryptedKeyfile, password: &str) -> Option<Vec<u8>> {
-    let salt = base64::decode(&ek.salt_b64).ok()?;
-    let nonce = base64::decode(&ek.nonce_b64).ok()?;
-    let ct = base64::decode(&ek.ct_b64).ok()?;
+/// Password-encrypted blob with the same KDF and cipher as keyfiles, for
+/// payloads other than a single secret key (e.g. wallet backups).
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SealedBox {
+    pub kdf: String,        // "argon2id"
+    pub salt_b64: String,
+    pub nonce_b64: String,
+    pub ct_b64: String,
+}
+
+/// Encrypts `plaintext`; `aad` is authenticated but not encrypted and must be
+/// presented again to open.
+pub fn seal_with_password(plaintext: &[u8], password: &str, aad: &[u8]) -> SealedBox {
+    let mut salt = [0u8; 16]; OsRng.fill_bytes(&mut salt);
+    let key = derive_key(password, &salt);
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(&key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ct = cipher.encrypt(&nonce, Payload { msg: plaintext, aad }).expect("encrypt");
+    let mut key_z = key; key_z.zeroize();
+    SealedBox {
+        kdf: "argon2id".to_string(),
+        salt_b64: B64.encode(salt),
+        nonce_b64: B64.encode(nonce),
+        ct_b64: B64.encode(ct),
+    }
+}
+
+pub fn open_with_password(sealed: &SealedBox, password: &str, aad: &[u8]) -> Option<Vec<u8>> {
+    if sealed.kdf != "argon2id" { return None; }
+    let salt = B64.decode(&sealed.salt_b64).ok()?;
+    let nonce = B64.decode(&sealed.nonce_b64).ok()?;
+    let ct = B64.decode(&sealed.ct_b64).ok()?;
+    // argon2 rejects (and derive_key panics on) salts under 8 bytes
+    if nonce.len() != 12 || salt.len() < 8 { return None; }
     let key = derive_key(password, &salt);
     let cipher = Aes256Gcm::new(GenericArray::from_slice(&key));
-    let pt = cipher.decrypt(GenericArray::from_slice(&nonce), ct.as_ref()).ok()?;
+    let pt = cipher.decrypt(GenericArray::from_slice(&nonce), Payload { msg: &ct, aad }).ok();
     let mut key_z = key; key_z.zeroize();
-    Some(pt)
+    pt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyfile() -> (EncryptedKeyfile, DilithiumKeypair) {
+        let kp = generate_dilithium3();
+        (encrypt_secret("w", &kp.public, &kp.secret, "pw"), kp)
+    }
+
+    #[test]
+    fn keyfiles_round_trip_under_their_name() {
+        let (ek, kp) = keyfile();
+        assert_eq!(decrypt_secret(&ek, "w", "pw").unwrap(), kp.secret);
+        assert_eq!(decrypt_secret(&ek, "w", "other").unwrap_err(), KeyfileError::BadPassword);
+        assert_eq!(decrypt_secret(&ek, "renamed", "pw").unwrap_err(), KeyfileError::BadPassword);
+    }
+
+    #[test]
+    fn refuses_costly_kdf_params_before_deriving() {
+        let (ek, _) = keyfile();
+        for params in [
+            KdfParams { t_cost: u32::MAX, ..KdfParams::default() },
+            KdfParams { p_cost: MAX_ARGON2_P_COST + 1, ..KdfParams::default() },
+            KdfParams { m_cost: u32::MAX, ..KdfParams::default() },
+        ] {
+            let edited = EncryptedKeyfile { kdf_params: params, ..ek.clone() };
+            // would take forever if the params were honoured
+            assert_eq!(decrypt_secret(&edited, "w", "pw").unwrap_err(), KeyfileError::Malformed);
+        }
+    }
+
+    #[test]
+    fn v1_keyfiles_still_open() {
+        let kp = generate_dilithium3();
+        let mut ek = encrypt_secret("w", &kp.public, &kp.secret, "pw");
+        let key = derive_key("pw", &B64.decode(&ek.salt_b64).unwrap());
+        let nonce = B64.decode(&ek.nonce_b64).unwrap();
+        let cipher = Aes256Gcm::new(GenericArray::from_slice(&key));
+        ek.ct_b64 = B64.encode(cipher.encrypt(GenericArray::from_slice(&nonce), kp.secret.as_slice()).unwrap());
+        ek.v = 1;
+        ek.scheme.clear();
+        assert_eq!(decrypt_secret(&ek, "any name", "pw").unwrap(), kp.secret);
+        // v1 has no AAD, so only the keypair check catches a swapped public key
+        ek.public_hex = hex::encode(generate_dilithium3().public);
+        assert_eq!(decrypt_secret(&ek, "w", "pw").unwrap_err(), KeyfileError::KeyMismatch);
+    }
+
+    #[test]
+    fn check_keyfile_needs_no_password() {
+        let (ek, kp) = keyfile();
+        assert_eq!(check_keyfile(&ek), Ok(()));
+        let broken: [fn(&mut EncryptedKeyfile); 8] = [
+            |ek| ek.kdf = "scrypt".into(),
+            |ek| ek.v = KEYFILE_VERSION + 1,
+            |ek| ek.kdf_params.m_cost = MAX_ARGON2_M_COST + 1,
+            |ek| ek.kdf_params.t_cost = MAX_ARGON2_T_COST + 1,
+            |ek| ek.kdf_params.p_cost = MAX_ARGON2_P_COST + 1,
+            |ek| ek.salt_b64 = "AAAA".into(),
+            |ek| ek.nonce_b64 = "not base64!".into(),
+            |ek| ek.public_hex = "abcd".into(),
+        ];
+        for edit in broken {
+            let mut bad = ek.clone();
+            edit(&mut bad);
+            assert_eq!(check_keyfile(&bad), Err(KeyfileError::Malformed));
+        }
+        // keys of other schemes are kept as they are
+        let mut foreign = ek.clone();
+        foreign.scheme = "other".into();
+        foreign.public_hex = "abcd".into();
+        assert_eq!(check_keyfile(&foreign), Ok(()));
+        assert!(public_key_valid(&kp.public));
+    }
 }
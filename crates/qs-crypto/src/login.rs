@@ -0,0 +1,300 @@
+// -------- Sign-in with QuantumShield --------
+//
+// Challenge-response login modeled on EIP-4361 (Sign-In with Ethereum). A
+// service issues a challenge (its domain, the URI being logged into, a
+// single-use nonce and a validity window); the wallet adds its address and
+// signs this text under the domain ("sign-in", service domain):
+//
+//   {domain} wants you to sign in with your QuantumShield account:
+//   {address}
+//
+//   {statement}            (line and blank line omitted if there is none)
+//
+//   URI: {uri}
+//   Version: 1
+//   Nonce: {nonce}
+//   Issued At: {issued_at}
+//   Expiration Time: {expires_at}
+//
+// Times are unix seconds. The text is what a wallet shows its user, so no
+// field may contain a line break. The verifier checks domain, freshness and
+// signature, then burns the nonce so the same login cannot be replayed.
+use rand::RngCore;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+use crate::{Domain, address_from_pubkey, domain::MAX_APP_ID_LEN, sign_domain, verify_domain};
+
+pub const LOGIN_VERSION: u8 = 1;
+pub const LOGIN_TAG: &str = "sign-in";
+/// How far a verifier's clock may be behind the issuer's.
+pub const LOGIN_CLOCK_SKEW_SECS: u64 = 60;
+pub const MAX_STATEMENT_LEN: usize = 512;
+const MAX_URI_LEN: usize = 2048;
+const MIN_NONCE_LEN: usize = 16;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LoginChallenge {
+    pub v: u8,
+    pub domain: String,
+    pub uri: String,
+    pub statement: Option<String>,
+    pub nonce: String,
+    pub issued_at: u64,
+    pub expires_at: u64,
+}
+
+/// A challenge answered by a wallet.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SignedLogin {
+    pub challenge: LoginChallenge,
+    pub address: String,
+    pub public_key_hex: String,
+    pub signed_hex: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoginError {
+    Malformed(String),
+    WrongDomain,
+    NotYetValid,
+    Expired,
+    BadSignature,
+    /// The nonce was never issued, already used, or has expired.
+    Replayed,
+}
+
+impl fmt::Display for LoginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoginError::Malformed(m) => write!(f, "sign-in: malformed login: {m}"),
+            LoginError::WrongDomain  => write!(f, "sign-in: challenge is for a different domain"),
+            LoginError::NotYetValid  => write!(f, "sign-in: challenge is not valid yet"),
+            LoginError::Expired      => write!(f, "sign-in: challenge has expired"),
+            LoginError::BadSignature => write!(f, "sign-in: signature does not verify"),
+            LoginError::Replayed     => write!(f, "sign-in: nonce is unknown or already used"),
+        }
+    }
+}
+
+impl std::error::Error for LoginError {}
+
+/// Remembers issued nonces so each can be used for one login only.
+pub trait NonceStore {
+    /// Forgets `nonce` and returns whether it was issued and still unused at `now`.
+    fn consume(&mut self, nonce: &str, now: u64) -> bool;
+}
+
+fn malformed<T>(msg: impl Into<String>) -> Result<T, LoginError> { Err(LoginError::Malformed(msg.into())) }
+
+fn single_line(field: &str, value: &str, max: usize) -> Result<(), LoginError> {
+    if value.len() > max {
+        return malformed(format!("{field} is longer than {max} bytes"));
+    }
+    if value.chars().any(char::is_control) {
+        return malformed(format!("{field} must be a single line"));
+    }
+    Ok(())
+}
+
+impl LoginChallenge {
+    /// A fresh challenge with a random nonce, valid for `ttl_secs` from `now`.
+    pub fn new(domain: &str, uri: &str, statement: Option<String>, now: u64, ttl_secs: u64) -> Result<Self, LoginError> {
+        let mut nonce = [0u8; 16];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let c = LoginChallenge {
+            v: LOGIN_VERSION,
+            domain: domain.to_string(),
+            uri: uri.to_string(),
+            statement,
+            nonce: hex::encode(nonce),
+            issued_at: now,
+            expires_at: now.saturating_add(ttl_secs),
+        };
+        c.validate()?;
+        Ok(c)
+    }
+
+    pub fn validate(&self) -> Result<(), LoginError> {
+        if self.v != LOGIN_VERSION {
+            return malformed(format!("unsupported version {}", self.v));
+        }
+        if self.domain.is_empty() || self.uri.is_empty() {
+            return malformed("domain and uri are required");
+        }
+        single_line("domain", &self.domain, MAX_APP_ID_LEN)?;
+        single_line("uri", &self.uri, MAX_URI_LEN)?;
+        if let Some(s) = &self.statement {
+            single_line("statement", s, MAX_STATEMENT_LEN)?;
+        }
+        if self.nonce.len() < MIN_NONCE_LEN || !self.nonce.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return malformed(format!("nonce must be at least {MIN_NONCE_LEN} letters or digits"));
+        }
+        if self.expires_at <= self.issued_at {
+            return malformed("expires_at must be after issued_at");
+        }
+        Ok(())
+    }
+
+    pub fn signing_domain(&self) -> Domain { Domain::new(LOGIN_TAG, self.domain.clone()) }
+
+    /// The text the wallet signs for `address`.
+    pub fn message(&self, address: &str) -> String {
+        let mut out = format!("{} wants you to sign in with your QuantumShield account:\n{address}\n\n", self.domain);
+        if let Some(s) = &self.statement {
+            out.push_str(s);
+            out.push_str("\n\n");
+        }
+        out.push_str(&format!(
+            "URI: {}\nVersion: {}\nNonce: {}\nIssued At: {}\nExpiration Time: {}",
+            self.uri, self.v, self.nonce, self.issued_at, self.expires_at
+        ));
+        out
+    }
+
+    fn check_time(&self, now: u64) -> Result<(), LoginError> {
+        if now.saturating_add(LOGIN_CLOCK_SKEW_SECS) < self.issued_at {
+            return Err(LoginError::NotYetValid);
+        }
+        if now >= self.expires_at {
+            return Err(LoginError::Expired);
+        }
+        Ok(())
+    }
+}
+
+/// Answers `challenge` with the key (`public`, `secret`). Refuses challenges
+/// that are malformed or not valid at `now`.
+pub fn sign_login(public: &[u8], secret: &[u8], challenge: &LoginChallenge, now: u64) -> Result<SignedLogin, LoginError> {
+    challenge.validate()?;
+    challenge.check_time(now)?;
+    let address = address_from_pubkey(public);
+    let signed = sign_domain(secret, &challenge.signing_domain(), challenge.message(&address).as_bytes())
+        .ok_or_else(|| LoginError::Malformed("service domain is not a valid signing domain".into()))?;
+    Ok(SignedLogin {
+        challenge: challenge.clone(),
+        address,
+        public_key_hex: hex::encode(public),
+        signed_hex: hex::encode(signed),
+    })
+}
+
+/// Checks a login for `expected_domain` at `now` and burns its nonce;
+/// returns the signer's public key.
+pub fn verify_login(
+    login: &SignedLogin, expected_domain: &str, now: u64, nonces: &mut impl NonceStore,
+) -> Result<Vec<u8>, LoginError> {
+    let c = &login.challenge;
+    c.validate()?;
+    if c.domain != expected_domain {
+        return Err(LoginError::WrongDomain);
+    }
+    c.check_time(now)?;
+    let public = hex::decode(&login.public_key_hex).or_else(|_| malformed("public_key_hex is not hex"))?;
+    if address_from_pubkey(&public) != login.address {
+        return malformed("address does not belong to public_key_hex");
+    }
+    let signed = hex::decode(&login.signed_hex).or_else(|_| malformed("signed_hex is not hex"))?;
+    if verify_domain(&public, &c.signing_domain(), &signed).as_deref() != Some(c.message(&login.address).as_bytes()) {
+        return Err(LoginError::BadSignature);
+    }
+    // last, so a forged login cannot burn someone else's nonce
+    if !nonces.consume(&c.nonce, now) {
+        return Err(LoginError::Replayed);
+    }
+    Ok(public)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const NOW: u64 = 1_700_000_000;
+
+    /// Issued nonces and their expiry.
+    #[derive(Default)]
+    struct Issued(HashMap<String, u64>);
+
+    impl NonceStore for Issued {
+        fn consume(&mut self, nonce: &str, now: u64) -> bool {
+            self.0.remove(nonce).is_some_and(|exp| exp > now)
+        }
+    }
+
+    fn issue(store: &mut Issued) -> LoginChallenge {
+        let c = LoginChallenge::new("example.com", "https://example.com/login", Some("Welcome back".into()), NOW, 300).unwrap();
+        store.0.insert(c.nonce.clone(), c.expires_at);
+        c
+    }
+
+    #[test]
+    fn message_reads_like_eip_4361() {
+        let mut c = issue(&mut Issued::default());
+        c.nonce = "0123456789abcdef".into();
+        assert_eq!(c.message("QSaddr"), format!(
+            "example.com wants you to sign in with your QuantumShield account:\nQSaddr\n\nWelcome back\n\n\
+             URI: https://example.com/login\nVersion: 1\nNonce: 0123456789abcdef\nIssued At: {NOW}\nExpiration Time: {}",
+            NOW + 300
+        ));
+        c.statement = None;
+        assert!(c.message("QSaddr").starts_with("example.com wants you to sign in with your QuantumShield account:\nQSaddr\n\nURI: "));
+        assert_eq!(c.signing_domain(), Domain::new(LOGIN_TAG, "example.com"));
+    }
+
+    #[test]
+    fn logins_verify_once() {
+        let kp = crate::generate_dilithium3();
+        let mut store = Issued::default();
+        let c = issue(&mut store);
+        let login = sign_login(&kp.public, &kp.secret, &c, NOW + 1).unwrap();
+        assert_eq!(login.address, address_from_pubkey(&kp.public));
+        assert_eq!(verify_login(&login, "example.com", NOW + 2, &mut store).unwrap(), kp.public);
+        assert_eq!(verify_login(&login, "example.com", NOW + 2, &mut store), Err(LoginError::Replayed));
+    }
+
+    #[test]
+    fn bad_logins_keep_the_nonce() {
+        let kp = crate::generate_dilithium3();
+        let mut store = Issued::default();
+        let c = issue(&mut store);
+        let login = sign_login(&kp.public, &kp.secret, &c, NOW).unwrap();
+
+        assert_eq!(verify_login(&login, "evil.com", NOW, &mut store), Err(LoginError::WrongDomain));
+        assert_eq!(verify_login(&login, "example.com", NOW + 300, &mut store), Err(LoginError::Expired));
+        assert_eq!(verify_login(&login, "example.com", NOW - LOGIN_CLOCK_SKEW_SECS - 1, &mut store), Err(LoginError::NotYetValid));
+
+        let mut changed = login.clone();
+        changed.challenge.uri = "https://example.com/admin".into();
+        assert_eq!(verify_login(&changed, "example.com", NOW, &mut store), Err(LoginError::BadSignature));
+
+        let other = crate::generate_dilithium3();
+        let mut impostor = login.clone();
+        impostor.public_key_hex = hex::encode(&other.public);
+        assert!(matches!(verify_login(&impostor, "example.com", NOW, &mut store), Err(LoginError::Malformed(_))));
+        impostor.address = address_from_pubkey(&other.public);
+        assert_eq!(verify_login(&impostor, "example.com", NOW, &mut store), Err(LoginError::BadSignature));
+
+        // none of the above used up the nonce
+        assert!(verify_login(&login, "example.com", NOW, &mut store).is_ok());
+        assert!(sign_login(&kp.public, &kp.secret, &c, NOW + 300).is_err());
+    }
+
+    #[test]
+    fn challenges_are_single_line_and_well_formed() {
+        assert!(LoginChallenge::new("example.com", "", None, NOW, 60).is_err());
+        assert!(LoginChallenge::new("example.com\nURI: x", "https://a", None, NOW, 60).is_err());
+        assert!(LoginChallenge::new("example.com", "https://a", Some("line\nbreak".into()), NOW, 60).is_err());
+        assert!(LoginChallenge::new("example.com", "https://a", Some("x".repeat(MAX_STATEMENT_LEN + 1)), NOW, 60).is_err());
+        assert!(LoginChallenge::new("example.com", "https://a", None, NOW, 0).is_err());
+
+        let mut c = LoginChallenge::new("example.com", "https://a", None, NOW, 60).unwrap();
+        assert_eq!(c.nonce.len(), 32);
+        assert_ne!(c.nonce, LoginChallenge::new("example.com", "https://a", None, NOW, 60).unwrap().nonce);
+        c.nonce = "short".into();
+        assert!(c.validate().is_err());
+        c.nonce = "0123456789abcdef".into();
+        c.v = 2;
+        assert!(c.validate().is_err());
+    }
+}
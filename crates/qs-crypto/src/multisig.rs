@@ -0,0 +1,266 @@
+// -------- M-of-N multisignature policies --------
+//
+// A policy is a threshold plus a sorted, de-duplicated set of member keys.
+// Its address is `address_from_pubkey` over the canonical encoding
+//   "QuantumShield multisig" || u8 v || lp(scheme) || u16be threshold || u16be n || lp(member)*
+// so the same members and threshold always give the same address.
+//
+// Members sign, under the fixed `multisig_domain()`,
+//   u8 v || lp(policy address) || lp(tag) || lp(app_id) || message
+// where (tag, app_id) is the caller's domain. The fixed domain keeps a partial
+// from ever passing as a plain signature, and the policy address makes it
+// count for one policy only. A bundle collects partials for one
+// (policy, domain, message) until the threshold is met.
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+use crate::{Domain, SCHEME, address_from_pubkey, put_field, sign_domain, verify_domain};
+
+pub const MULTISIG_VERSION: u8 = 1;
+pub const MAX_MULTISIG_MEMBERS: usize = 64;
+const POLICY_TAG: &[u8] = b"QuantumShield multisig";
+/// Domain tag every partial signature is made under.
+pub const MULTISIG_TAG: &str = "multisig";
+
+pub fn multisig_domain() -> Domain { Domain::new(MULTISIG_TAG, "quantumshield") }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MultisigPolicy {
+    pub v: u8,
+    pub scheme: String,
+    pub threshold: u16,
+    pub members_hex: Vec<String>, // sorted by key bytes
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PartialSig { pub signer_public_hex: String, pub signed_hex: String }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MultisigBundle {
+    pub policy_address: String,
+    pub domain: Domain,
+    pub message_hex: String,
+    pub signatures: Vec<PartialSig>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultisigError(pub String);
+
+impl fmt::Display for MultisigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "multisig: {}", self.0) }
+}
+
+impl std::error::Error for MultisigError {}
+
+fn err<T>(msg: impl Into<String>) -> Result<T, MultisigError> { Err(MultisigError(msg.into())) }
+
+/// What a member actually signs, under [`multisig_domain`], for `message`
+/// in `domain` on behalf of `policy_address`.
+pub fn multisig_message(policy_address: &str, domain: &Domain, message: &[u8]) -> Vec<u8> {
+    let mut out = vec![MULTISIG_VERSION];
+    put_field(&mut out, policy_address.as_bytes());
+    put_field(&mut out, domain.tag.as_bytes());
+    put_field(&mut out, domain.app_id.as_bytes());
+    out.extend_from_slice(message);
+    out
+}
+
+/// A member's partial signature over `message` in `domain` for `policy_address`.
+pub fn sign_multisig(secret: &[u8], policy_address: &str, domain: &Domain, message: &[u8]) -> Option<Vec<u8>> {
+    if !domain.is_valid() { return None; }
+    sign_domain(secret, &multisig_domain(), &multisig_message(policy_address, domain, message))
+}
+
+impl MultisigPolicy {
+    /// Members are sorted and de-duplicated; `1 <= threshold <= members`.
+    pub fn new(threshold: u16, members: &[Vec<u8>]) -> Result<Self, MultisigError> {
+        let mut keys: Vec<&Vec<u8>> = members.iter().collect();
+        keys.sort();
+        keys.dedup();
+        if keys.iter().any(|k| k.is_empty()) {
+            return err("empty member key");
+        }
+        if keys.len() > MAX_MULTISIG_MEMBERS {
+            return err(format!("too many members ({} > {MAX_MULTISIG_MEMBERS})", keys.len()));
+        }
+        if threshold == 0 || usize::from(threshold) > keys.len() {
+            return err(format!("threshold {threshold} out of range for {} members", keys.len()));
+        }
+        Ok(MultisigPolicy {
+            v: MULTISIG_VERSION,
+            scheme: SCHEME.to_string(),
+            threshold,
+            members_hex: keys.into_iter().map(hex::encode).collect(),
+        })
+    }
+
+    pub fn members(&self) -> Result<Vec<Vec<u8>>, MultisigError> {
+        self.members_hex.iter().map(|m| hex::decode(m).or_else(|_| err("member key is not hex"))).collect()
+    }
+
+    /// Re-checks a policy that came from outside: it must be exactly what `new` builds.
+    pub fn validate(&self) -> Result<(), MultisigError> {
+        if self.v != MULTISIG_VERSION {
+            return err(format!("unsupported policy version {}", self.v));
+        }
+        if self.scheme != SCHEME {
+            return err(format!("cannot verify `{}` signatures, this build uses `{SCHEME}`", self.scheme));
+        }
+        if MultisigPolicy::new(self.threshold, &self.members()?)? != *self {
+            return err("members must be sorted and unique");
+        }
+        Ok(())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = POLICY_TAG.to_vec();
+        out.push(self.v);
+        put_field(&mut out, self.scheme.as_bytes());
+        out.extend_from_slice(&self.threshold.to_be_bytes());
+        out.extend_from_slice(&(self.members_hex.len() as u16).to_be_bytes());
+        for m in &self.members_hex {
+            put_field(&mut out, &hex::decode(m).unwrap_or_default());
+        }
+        out
+    }
+
+    pub fn address(&self) -> String { address_from_pubkey(&self.encode()) }
+
+    pub fn is_member(&self, public: &[u8]) -> bool {
+        self.members_hex.iter().any(|m| hex::decode(m).is_ok_and(|k| k == public))
+    }
+}
+
+impl MultisigBundle {
+    pub fn new(policy: &MultisigPolicy, domain: Domain, message: &[u8]) -> Self {
+        MultisigBundle { policy_address: policy.address(), domain, message_hex: hex::encode(message), signatures: Vec::new() }
+    }
+
+    fn message(&self) -> Result<Vec<u8>, MultisigError> {
+        hex::decode(&self.message_hex).or_else(|_| err("message_hex is not hex"))
+    }
+
+    fn check_policy(&self, policy: &MultisigPolicy) -> Result<(), MultisigError> {
+        if policy.address() != self.policy_address {
+            return err("bundle belongs to a different policy");
+        }
+        Ok(())
+    }
+
+    fn partial_valid(&self, policy: &MultisigPolicy, public: &[u8], signed: &[u8]) -> Result<bool, MultisigError> {
+        let expected = multisig_message(&self.policy_address, &self.domain, &self.message()?);
+        Ok(policy.is_member(public) && verify_domain(public, &multisig_domain(), signed).as_deref() == Some(expected.as_slice()))
+    }
+
+    /// Adds a member's partial signature after checking it, replacing any
+    /// earlier one from the same member.
+    pub fn add(&mut self, policy: &MultisigPolicy, public: &[u8], signed: &[u8]) -> Result<(), MultisigError> {
+        self.check_policy(policy)?;
+        if !policy.is_member(public) {
+            return err(format!("{} is not a member of this policy", address_from_pubkey(public)));
+        }
+        if !self.partial_valid(policy, public, signed)? {
+            return err("partial signature does not verify");
+        }
+        let signer_public_hex = hex::encode(public);
+        self.signatures.retain(|s| s.signer_public_hex != signer_public_hex);
+        self.signatures.push(PartialSig { signer_public_hex, signed_hex: hex::encode(signed) });
+        Ok(())
+    }
+
+    /// Distinct members whose partial signatures verify, in policy order.
+    pub fn valid_signers(&self, policy: &MultisigPolicy) -> Result<Vec<Vec<u8>>, MultisigError> {
+        self.check_policy(policy)?;
+        let mut out = Vec::new();
+        for member in policy.members()? {
+            let member_hex = hex::encode(&member);
+            let ok = self.signatures.iter()
+                .filter(|s| s.signer_public_hex == member_hex)
+                .filter_map(|s| hex::decode(&s.signed_hex).ok())
+                .any(|signed| self.partial_valid(policy, &member, &signed).unwrap_or(false));
+            if ok {
+                out.push(member);
+            }
+        }
+        Ok(out)
+    }
+
+    pub fn meets_threshold(&self, policy: &MultisigPolicy) -> Result<bool, MultisigError> {
+        Ok(self.valid_signers(policy)?.len() >= usize::from(policy.threshold))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DilithiumKeypair, generate_dilithium3};
+
+    fn setup(threshold: u16, n: usize) -> (Vec<DilithiumKeypair>, MultisigPolicy) {
+        let keys: Vec<_> = (0..n).map(|_| generate_dilithium3()).collect();
+        let policy = MultisigPolicy::new(threshold, &keys.iter().map(|k| k.public.clone()).collect::<Vec<_>>()).unwrap();
+        (keys, policy)
+    }
+
+    #[test]
+    fn policy_address_ignores_member_order() {
+        let (keys, policy) = setup(2, 3);
+        let mut reversed: Vec<_> = keys.iter().rev().map(|k| k.public.clone()).collect();
+        reversed.push(keys[0].public.clone());
+        assert_eq!(MultisigPolicy::new(2, &reversed).unwrap().address(), policy.address());
+        assert_ne!(MultisigPolicy::new(3, &reversed).unwrap().address(), policy.address());
+        policy.validate().unwrap();
+
+        assert!(MultisigPolicy::new(0, &reversed).is_err());
+        assert!(MultisigPolicy::new(4, &reversed).is_err());
+        let mut unsorted = policy.clone();
+        unsorted.members_hex.reverse();
+        assert!(unsorted.validate().is_err());
+    }
+
+    #[test]
+    fn threshold_counts_distinct_valid_members() {
+        let (keys, policy) = setup(2, 3);
+        let domain = Domain::new("payout", "treasury");
+        let mut bundle = MultisigBundle::new(&policy, domain.clone(), b"pay 10");
+        let partial = |k: &DilithiumKeypair| sign_multisig(&k.secret, &policy.address(), &domain, b"pay 10").unwrap();
+
+        bundle.add(&policy, &keys[0].public, &partial(&keys[0])).unwrap();
+        bundle.add(&policy, &keys[0].public, &partial(&keys[0])).unwrap();
+        assert!(!bundle.meets_threshold(&policy).unwrap());
+        bundle.add(&policy, &keys[2].public, &partial(&keys[2])).unwrap();
+        assert!(bundle.meets_threshold(&policy).unwrap());
+        assert_eq!(bundle.valid_signers(&policy).unwrap().len(), 2);
+
+        let outsider = generate_dilithium3();
+        assert!(bundle.add(&policy, &outsider.public, &partial(&outsider)).is_err());
+        // another member's signature under a member's name
+        assert!(bundle.add(&policy, &keys[1].public, &partial(&keys[0])).is_err());
+    }
+
+    #[test]
+    fn partials_are_not_plain_signatures() {
+        let (keys, policy) = setup(1, 2);
+        let domain = Domain::new("payout", "treasury");
+        let mut bundle = MultisigBundle::new(&policy, domain.clone(), b"pay 10");
+
+        // a plain signature, even over the exact bytes a partial covers, does not count
+        let statement = multisig_message(&policy.address(), &domain, b"pay 10");
+        for plain in [
+            sign_domain(&keys[0].secret, &domain, b"pay 10").unwrap(),
+            sign_domain(&keys[0].secret, &domain, &statement).unwrap(),
+        ] {
+            assert!(bundle.add(&policy, &keys[0].public, &plain).is_err());
+        }
+
+        // and a partial does not open as a plain signature in the caller's domain
+        let partial = sign_multisig(&keys[0].secret, &policy.address(), &domain, b"pay 10").unwrap();
+        assert!(verify_domain(&keys[0].public, &domain, &partial).is_none());
+        // nor count for another domain or policy
+        let mut other_domain = MultisigBundle::new(&policy, Domain::new("payout", "elsewhere"), b"pay 10");
+        assert!(other_domain.add(&policy, &keys[0].public, &partial).is_err());
+        let (_, other_policy) = setup(1, 2);
+        assert!(bundle.add(&other_policy, &keys[0].public, &partial).is_err());
+        bundle.add(&policy, &keys[0].public, &partial).unwrap();
+        assert!(multisig_domain().is_reserved());
+    }
+}
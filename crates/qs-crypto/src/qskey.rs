@@ -0,0 +1,180 @@
+// -------- web app keyfile format (lib/qskey.ts, lib/secure.ts) --------
+//
+// QsKeyFile v1 = { v, kemPub, dsaPub, enc } where `enc` is an EncBlob: AES-256-GCM
+// under a PBKDF2-SHA256 key, sealing JSON
+//   { kem: { scheme, privateKey }, dsa: { scheme, privateKey } }
+// All keys and blob fields are standard base64. A file without a KEM keypair
+// has neither `kemPub` nor `kem`; older daemon exports wrote `scheme: "none"`
+// and an empty `kemPub` instead, which reads back the same.
+use aes_gcm::{Aes256Gcm, KeyInit, aead::{Aead, OsRng, generic_array::GenericArray}};
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+use rand::RngCore;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use sha2::Sha256;
+use zeroize::Zeroize;
+
+use crate::Domain;
+
+/// Same work factor the browser uses when it seals a blob.
+pub const PBKDF2_ITERATIONS: u32 = 250_000;
+/// Refuse blobs asking for more work than this; the count comes from the file.
+pub const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+pub const EXPORT_TAG: &str = "key-export";
+
+/// Nothing is signed under it; policies name it to allow or refuse exports.
+pub fn export_domain() -> Domain { Domain::new(EXPORT_TAG, "quantumshield") }
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EncBlob {
+    pub v: u8,
+    pub kdf: String,    // "pbkdf2-sha256"
+    pub iter: u32,
+    pub cipher: String, // "aes-256-gcm"
+    pub salt: String,
+    pub iv: String,
+    pub ct: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct QsKeyFile {
+    pub v: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kem_pub: Option<String>,
+    pub dsa_pub: String,
+    pub enc: EncBlob,
+}
+
+/// One keypair as the web app names it: scheme label plus raw key bytes.
+#[derive(Clone, Debug)]
+pub struct WebKey { pub scheme: String, pub public: Vec<u8>, pub secret: Vec<u8> }
+
+fn pbkdf2_key(pass: &str, salt: &[u8], iter: u32) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(pass.as_bytes(), salt, iter, &mut out);
+    out
+}
+
+pub fn seal_json(value: &Value, pass: &str) -> EncBlob {
+    let mut salt = [0u8; 16]; OsRng.fill_bytes(&mut salt);
+    let mut iv = [0u8; 12]; OsRng.fill_bytes(&mut iv);
+    let mut key = pbkdf2_key(pass, &salt, PBKDF2_ITERATIONS);
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(&key));
+    let pt = serde_json::to_vec(value).expect("json");
+    let ct = cipher.encrypt(GenericArray::from_slice(&iv), pt.as_ref()).expect("encrypt");
+    key.zeroize();
+    EncBlob {
+        v: 1,
+        kdf: "pbkdf2-sha256".to_string(),
+        iter: PBKDF2_ITERATIONS,
+        cipher: "aes-256-gcm".to_string(),
+        salt: B64.encode(salt),
+        iv: B64.encode(iv),
+        ct: B64.encode(ct),
+    }
+}
+
+pub fn open_json(blob: &EncBlob, pass: &str) -> Option<Value> {
+    if blob.v != 1 || blob.kdf != "pbkdf2-sha256" || blob.cipher != "aes-256-gcm" { return None; }
+    if blob.iter == 0 || blob.iter > MAX_PBKDF2_ITERATIONS { return None; }
+    let salt = B64.decode(&blob.salt).ok()?;
+    let iv = B64.decode(&blob.iv).ok()?;
+    let ct = B64.decode(&blob.ct).ok()?;
+    if iv.len() != 12 { return None; }
+    let mut key = pbkdf2_key(pass, &salt, blob.iter);
+    let cipher = Aes256Gcm::new(GenericArray::from_slice(&key));
+    let pt = cipher.decrypt(GenericArray::from_slice(&iv), ct.as_ref()).ok();
+    key.zeroize();
+    serde_json::from_slice(&pt?).ok()
+}
+
+/// KEM scheme older daemon exports wrote for a wallet without a KEM keypair.
+const LEGACY_NO_KEM: &str = "none";
+
+/// Builds a v1 keyfile the web app's `openQsKey` can read.
+pub fn build_qskey(kem: Option<&WebKey>, dsa: &WebKey, pass: &str) -> QsKeyFile {
+    let mut secret = serde_json::json!({
+        "dsa": { "scheme": dsa.scheme, "privateKey": B64.encode(&dsa.secret) },
+    });
+    if let Some(kem) = kem {
+        secret["kem"] = serde_json::json!({ "scheme": kem.scheme, "privateKey": B64.encode(&kem.secret) });
+    }
+    QsKeyFile {
+        v: 1,
+        kem_pub: kem.map(|k| B64.encode(&k.public)),
+        dsa_pub: B64.encode(&dsa.public),
+        enc: seal_json(&secret, pass),
+    }
+}
+
+/// Decrypts a v1 keyfile into its (kem, dsa) keypairs; `None` on a wrong
+/// passphrase or malformed file.
+pub fn open_qskey(file: &QsKeyFile, pass: &str) -> Option<(Option<WebKey>, WebKey)> {
+    if file.v != 1 { return None; }
+    let secret = open_json(&file.enc, pass)?;
+    let key = |part: &str, public_b64: &str| -> Option<WebKey> {
+        let s = secret.get(part)?;
+        Some(WebKey {
+            scheme: s.get("scheme")?.as_str()?.to_string(),
+            public: B64.decode(public_b64).ok()?,
+            secret: B64.decode(s.get("privateKey")?.as_str()?).ok()?,
+        })
+    };
+    let kem = match (&file.kem_pub, secret.get("kem")) {
+        (None, None) => None,
+        (Some(public), Some(_)) => Some(key("kem", public)?).filter(|k| k.scheme != LEGACY_NO_KEM),
+        _ => return None,
+    };
+    Some((kem, key("dsa", &file.dsa_pub)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys() -> (WebKey, WebKey) {
+        let kp = crate::generate_dilithium3();
+        let kem = WebKey { scheme: "ML-KEM-768".into(), public: vec![1; 32], secret: vec![2; 64] };
+        (kem, WebKey { scheme: crate::SCHEME.into(), public: kp.public, secret: kp.secret })
+    }
+
+    #[test]
+    fn round_trips_with_and_without_kem() {
+        let (kem, dsa) = keys();
+        let file = build_qskey(Some(&kem), &dsa, "pass");
+        let (k, d) = open_qskey(&file, "pass").unwrap();
+        let k = k.unwrap();
+        assert_eq!((k.scheme.as_str(), k.public, k.secret), ("ML-KEM-768", kem.public, kem.secret));
+        assert_eq!((d.public, d.secret), (dsa.public.clone(), dsa.secret.clone()));
+        assert!(open_qskey(&file, "wrong").is_none());
+
+        let file = build_qskey(None, &dsa, "pass");
+        let json = serde_json::to_value(&file).unwrap();
+        assert!(json.get("kemPub").is_none());
+        let (k, d) = open_qskey(&file, "pass").unwrap();
+        assert!(k.is_none());
+        assert_eq!(d.public, dsa.public);
+    }
+
+    #[test]
+    fn reads_old_placeholder_kem() {
+        let (_, dsa) = keys();
+        let none = WebKey { scheme: LEGACY_NO_KEM.into(), public: Vec::new(), secret: Vec::new() };
+        let file = build_qskey(Some(&none), &dsa, "pass");
+        assert_eq!(file.kem_pub.as_deref(), Some(""));
+        assert!(open_qskey(&file, "pass").unwrap().0.is_none());
+    }
+
+    #[test]
+    fn refuses_mismatched_kem_halves_and_costly_blobs() {
+        let (kem, dsa) = keys();
+        let mut file = build_qskey(Some(&kem), &dsa, "pass");
+        file.kem_pub = None;
+        assert!(open_qskey(&file, "pass").is_none());
+
+        let mut file = build_qskey(None, &dsa, "pass");
+        file.enc.iter = MAX_PBKDF2_ITERATIONS + 1;
+        assert!(open_qskey(&file, "pass").is_none());
+    }
+}
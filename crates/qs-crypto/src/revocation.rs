@@ -0,0 +1,209 @@
+// -------- key revocation --------
+//
+// A revocation marks a public key as no longer trusted from `revoked_at` on.
+// It is signed either by the revoked key itself or by a recovery key the
+// revoked key designated beforehand (so a lost key can still be revoked).
+//
+//   revocation  = u8 v || lp(scheme) || lp(public) || u64be revoked_at || lp(reason) || lp(signer)
+//   designation = u8 v || lp(scheme) || lp(public) || lp(recovery) || u64be issued_at
+//
+// Each is signed under its own domain so neither can stand in for the other.
+use serde::{Serialize, Deserialize};
+use std::fmt;
+
+use crate::{Domain, SCHEME, put_field, sign_domain, verify_domain};
+
+pub const REVOCATION_VERSION: u8 = 1;
+
+pub fn revocation_domain() -> Domain { Domain::new("key-revocation", "quantumshield") }
+pub fn recovery_domain() -> Domain { Domain::new("recovery-key", "quantumshield") }
+
+/// Statement by `public_hex` that `recovery_public_hex` may revoke it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RecoveryDesignation {
+    pub v: u8,
+    pub scheme: String,
+    pub public_hex: String,
+    pub recovery_public_hex: String,
+    pub issued_at: u64,
+    pub signed_hex: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Revocation {
+    pub v: u8,
+    pub scheme: String,
+    pub public_hex: String,
+    pub revoked_at: u64,
+    pub reason: String,
+    pub signer_public_hex: String, // the revoked key or its recovery key
+    pub signed_hex: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevocationError(pub String);
+
+impl fmt::Display for RevocationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "revocation: {}", self.0) }
+}
+
+impl std::error::Error for RevocationError {}
+
+fn err<T>(msg: impl Into<String>) -> Result<T, RevocationError> { Err(RevocationError(msg.into())) }
+
+fn decode(field: &str, value: &str) -> Result<Vec<u8>, RevocationError> {
+    hex::decode(value).or_else(|_| err(format!("{field} is not hex")))
+}
+
+fn check_header(v: u8, scheme: &str) -> Result<(), RevocationError> {
+    if v != REVOCATION_VERSION {
+        return err(format!("unsupported version {v}"));
+    }
+    if scheme != SCHEME {
+        return err(format!("cannot verify `{scheme}` signatures, this build uses `{SCHEME}`"));
+    }
+    Ok(())
+}
+
+fn opens(public: &[u8], domain: &Domain, signed_hex: &str, expected: &[u8]) -> bool {
+    hex::decode(signed_hex).ok().and_then(|s| verify_domain(public, domain, &s)).as_deref() == Some(expected)
+}
+
+fn designation_statement(scheme: &str, public: &[u8], recovery: &[u8], issued_at: u64) -> Vec<u8> {
+    let mut out = vec![REVOCATION_VERSION];
+    put_field(&mut out, scheme.as_bytes());
+    put_field(&mut out, public);
+    put_field(&mut out, recovery);
+    out.extend_from_slice(&issued_at.to_be_bytes());
+    out
+}
+
+fn revocation_statement(scheme: &str, public: &[u8], revoked_at: u64, reason: &str, signer: &[u8]) -> Vec<u8> {
+    let mut out = vec![REVOCATION_VERSION];
+    put_field(&mut out, scheme.as_bytes());
+    put_field(&mut out, public);
+    out.extend_from_slice(&revoked_at.to_be_bytes());
+    put_field(&mut out, reason.as_bytes());
+    put_field(&mut out, signer);
+    out
+}
+
+/// Key `public` (holding `secret`) names `recovery` as able to revoke it.
+pub fn designate_recovery(public: &[u8], secret: &[u8], recovery: &[u8], issued_at: u64) -> Option<RecoveryDesignation> {
+    let msg = designation_statement(SCHEME, public, recovery, issued_at);
+    Some(RecoveryDesignation {
+        v: REVOCATION_VERSION,
+        scheme: SCHEME.to_string(),
+        public_hex: hex::encode(public),
+        recovery_public_hex: hex::encode(recovery),
+        issued_at,
+        signed_hex: hex::encode(sign_domain(secret, &recovery_domain(), &msg)?),
+    })
+}
+
+/// Revokes `revoked`, signed by `signer` (the key itself or its recovery key).
+pub fn issue_revocation(
+    revoked: &[u8], signer_public: &[u8], signer_secret: &[u8], revoked_at: u64, reason: &str,
+) -> Option<Revocation> {
+    let msg = revocation_statement(SCHEME, revoked, revoked_at, reason, signer_public);
+    Some(Revocation {
+        v: REVOCATION_VERSION,
+        scheme: SCHEME.to_string(),
+        public_hex: hex::encode(revoked),
+        revoked_at,
+        reason: reason.to_string(),
+        signer_public_hex: hex::encode(signer_public),
+        signed_hex: hex::encode(sign_domain(signer_secret, &revocation_domain(), &msg)?),
+    })
+}
+
+impl RecoveryDesignation {
+    pub fn verify(&self) -> Result<(), RevocationError> {
+        check_header(self.v, &self.scheme)?;
+        let public = decode("public_hex", &self.public_hex)?;
+        let recovery = decode("recovery_public_hex", &self.recovery_public_hex)?;
+        if public == recovery {
+            return err("a key cannot be its own recovery key");
+        }
+        let msg = designation_statement(&self.scheme, &public, &recovery, self.issued_at);
+        if !opens(&public, &recovery_domain(), &self.signed_hex, &msg) {
+            return err("designation signature does not verify");
+        }
+        Ok(())
+    }
+}
+
+impl Revocation {
+    pub fn public(&self) -> Result<Vec<u8>, RevocationError> { decode("public_hex", &self.public_hex) }
+
+    /// Checks the signature. A revocation signed by another key is only valid
+    /// together with a verified designation of that key as recovery key.
+    pub fn verify(&self, recovery: Option<&RecoveryDesignation>) -> Result<(), RevocationError> {
+        check_header(self.v, &self.scheme)?;
+        let public = self.public()?;
+        let signer = decode("signer_public_hex", &self.signer_public_hex)?;
+        if signer != public {
+            let Some(d) = recovery else { return err("signed by a key that is not a designated recovery key") };
+            d.verify()?;
+            if decode("public_hex", &d.public_hex)? != public || decode("recovery_public_hex", &d.recovery_public_hex)? != signer {
+                return err("signed by a key that is not a designated recovery key");
+            }
+        }
+        let msg = revocation_statement(&self.scheme, &public, self.revoked_at, &self.reason, &signer);
+        if !opens(&signer, &revocation_domain(), &self.signed_hex, &msg) {
+            return err("revocation signature does not verify");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_dilithium3;
+
+    #[test]
+    fn self_signed_revocation() {
+        let key = generate_dilithium3();
+        let r = issue_revocation(&key.public, &key.public, &key.secret, 50, "compromised").unwrap();
+        r.verify(None).unwrap();
+
+        let mut edited = r.clone();
+        edited.revoked_at = 60;
+        assert!(edited.verify(None).is_err());
+        let mut edited = r.clone();
+        edited.reason = "rotated".into();
+        assert!(edited.verify(None).is_err());
+    }
+
+    #[test]
+    fn recovery_key_needs_a_designation() {
+        let [key, recovery, stranger] = [generate_dilithium3(), generate_dilithium3(), generate_dilithium3()];
+        let d = designate_recovery(&key.public, &key.secret, &recovery.public, 10).unwrap();
+        d.verify().unwrap();
+
+        let r = issue_revocation(&key.public, &recovery.public, &recovery.secret, 20, "lost").unwrap();
+        assert!(r.verify(None).is_err());
+        r.verify(Some(&d)).unwrap();
+
+        // a designation of someone else does not help
+        let other = designate_recovery(&key.public, &key.secret, &stranger.public, 10).unwrap();
+        assert!(r.verify(Some(&other)).is_err());
+        let by_stranger = issue_revocation(&key.public, &stranger.public, &stranger.secret, 20, "lost").unwrap();
+        assert!(by_stranger.verify(Some(&d)).is_err());
+
+        // the recovery key cannot designate itself on the key's behalf
+        let forged = designate_recovery(&key.public, &recovery.secret, &recovery.public, 10).unwrap();
+        assert!(forged.verify().is_err());
+        assert!(designate_recovery(&key.public, &key.secret, &key.public, 10).unwrap().verify().is_err());
+    }
+
+    #[test]
+    fn statements_do_not_cross_domains() {
+        let key = generate_dilithium3();
+        let r = issue_revocation(&key.public, &key.public, &key.secret, 1, "").unwrap();
+        let signed = hex::decode(&r.signed_hex).unwrap();
+        assert!(verify_domain(&key.public, &recovery_domain(), &signed).is_none());
+        assert!(verify_domain(&key.public, &revocation_domain(), &signed).is_some());
+    }
+}
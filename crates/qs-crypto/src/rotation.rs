@@ -0,0 +1,201 @@
+// -------- key rotation certificates --------
+//
+// A rotation certificate says "key `old` is replaced by key `new`" and is
+// signed by both, under the fixed `rotation_domain()`, over
+//   u8 v || lp(old_scheme) || lp(old_public) || lp(new_scheme) || lp(new_public)
+//   || u64be issued_at || lp(reason)
+// where lp(x) = u32be len || x. The old signature proves the holder authorized
+// the change; the new one proves the successor key is actually held.
+// Certificates chain: each one's old key is the previous one's new key.
+use serde::{Serialize, Deserialize};
+use std::{collections::HashSet, fmt};
+
+use crate::{Domain, SCHEME, put_field, sign_domain, verify_domain};
+
+pub const ROTATION_CERT_VERSION: u8 = 1;
+
+pub fn rotation_domain() -> Domain { Domain::new("key-rotation", "quantumshield") }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RotationCert {
+    pub v: u8,
+    pub old_scheme: String,
+    pub old_public_hex: String,
+    pub new_scheme: String,
+    pub new_public_hex: String,
+    pub issued_at: u64,
+    pub reason: Option<String>,
+    pub old_signed_hex: String, // signed statement, as returned by sign_domain
+    pub new_signed_hex: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RotationError(pub String);
+
+impl fmt::Display for RotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "rotation: {}", self.0) }
+}
+
+impl std::error::Error for RotationError {}
+
+fn err<T>(msg: impl Into<String>) -> Result<T, RotationError> { Err(RotationError(msg.into())) }
+
+fn statement(
+    old_scheme: &str, old_public: &[u8], new_scheme: &str, new_public: &[u8], issued_at: u64, reason: Option<&str>,
+) -> Vec<u8> {
+    let mut out = vec![ROTATION_CERT_VERSION];
+    put_field(&mut out, old_scheme.as_bytes());
+    put_field(&mut out, old_public);
+    put_field(&mut out, new_scheme.as_bytes());
+    put_field(&mut out, new_public);
+    out.extend_from_slice(&issued_at.to_be_bytes());
+    put_field(&mut out, reason.unwrap_or("").as_bytes());
+    out
+}
+
+/// Issues a certificate moving from `old` to `new`, both keys of this build's scheme.
+pub fn issue_rotation(
+    old_public: &[u8], old_secret: &[u8], new_public: &[u8], new_secret: &[u8], issued_at: u64, reason: Option<String>,
+) -> Option<RotationCert> {
+    let msg = statement(SCHEME, old_public, SCHEME, new_public, issued_at, reason.as_deref());
+    let domain = rotation_domain();
+    Some(RotationCert {
+        v: ROTATION_CERT_VERSION,
+        old_scheme: SCHEME.to_string(),
+        old_public_hex: hex::encode(old_public),
+        new_scheme: SCHEME.to_string(),
+        new_public_hex: hex::encode(new_public),
+        issued_at,
+        reason,
+        old_signed_hex: hex::encode(sign_domain(old_secret, &domain, &msg)?),
+        new_signed_hex: hex::encode(sign_domain(new_secret, &domain, &msg)?),
+    })
+}
+
+impl RotationCert {
+    pub fn old_public(&self) -> Result<Vec<u8>, RotationError> {
+        hex::decode(&self.old_public_hex).or_else(|_| err("old_public_hex is not hex"))
+    }
+
+    pub fn new_public(&self) -> Result<Vec<u8>, RotationError> {
+        hex::decode(&self.new_public_hex).or_else(|_| err("new_public_hex is not hex"))
+    }
+
+    /// Checks both signatures over this certificate's own fields. Only keys of
+    /// this build's scheme can be checked.
+    pub fn verify(&self) -> Result<(), RotationError> {
+        if self.v != ROTATION_CERT_VERSION {
+            return err(format!("unsupported certificate version {}", self.v));
+        }
+        for scheme in [&self.old_scheme, &self.new_scheme] {
+            if scheme != SCHEME {
+                return err(format!("cannot verify `{scheme}` signatures, this build uses `{SCHEME}`"));
+            }
+        }
+        let (old_public, new_public) = (self.old_public()?, self.new_public()?);
+        if old_public == new_public {
+            return err("old and new keys are the same");
+        }
+        let msg = statement(&self.old_scheme, &old_public, &self.new_scheme, &new_public, self.issued_at, self.reason.as_deref());
+        let domain = rotation_domain();
+        let opens = |public: &[u8], signed_hex: &str| {
+            hex::decode(signed_hex).ok().and_then(|s| verify_domain(public, &domain, &s)).as_deref() == Some(msg.as_slice())
+        };
+        if !opens(&old_public, &self.old_signed_hex) {
+            return err("old key signature does not verify");
+        }
+        if !opens(&new_public, &self.new_signed_hex) {
+            return err("new key signature does not verify");
+        }
+        Ok(())
+    }
+}
+
+/// Follows a chain of certificates, oldest first, and returns the current key.
+/// If `start` is given the chain must begin at that key. Each link must start
+/// where the previous one ended, not go back in time, and never revisit a key.
+pub fn verify_rotation_chain(certs: &[RotationCert], start: Option<&[u8]>) -> Result<Vec<u8>, RotationError> {
+    let Some(first) = certs.first() else { return err("empty chain") };
+    let mut current = first.old_public()?;
+    if start.is_some_and(|s| s != current.as_slice()) {
+        return err("chain does not start at the given key");
+    }
+    let mut seen = HashSet::from([current.clone()]);
+    let mut last_issued = 0;
+    for (i, cert) in certs.iter().enumerate() {
+        cert.verify().map_err(|e| RotationError(format!("link {i}: {}", e.0)))?;
+        if cert.old_public()? != current {
+            return err(format!("link {i} does not continue from the previous key"));
+        }
+        if cert.issued_at < last_issued {
+            return err(format!("link {i} is dated before the previous link"));
+        }
+        let next = cert.new_public()?;
+        if !seen.insert(next.clone()) {
+            return err(format!("link {i} rotates back to an earlier key"));
+        }
+        last_issued = cert.issued_at;
+        current = next;
+    }
+    Ok(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DilithiumKeypair, generate_dilithium3};
+
+    fn rotate(from: &DilithiumKeypair, to: &DilithiumKeypair, at: u64) -> RotationCert {
+        issue_rotation(&from.public, &from.secret, &to.public, &to.secret, at, Some("scheduled".into())).unwrap()
+    }
+
+    #[test]
+    fn follows_a_chain_to_the_current_key() {
+        let keys: Vec<_> = (0..4).map(|_| generate_dilithium3()).collect();
+        let certs: Vec<_> = keys.windows(2).enumerate().map(|(i, w)| rotate(&w[0], &w[1], 100 + i as u64)).collect();
+        assert_eq!(verify_rotation_chain(&certs, None).unwrap(), keys[3].public);
+        assert_eq!(verify_rotation_chain(&certs, Some(&keys[0].public)).unwrap(), keys[3].public);
+        assert!(verify_rotation_chain(&certs, Some(&keys[1].public)).is_err());
+        assert!(verify_rotation_chain(&certs[1..], Some(&keys[1].public)).is_ok());
+        assert!(verify_rotation_chain(&[], None).is_err());
+    }
+
+    #[test]
+    fn rejects_broken_links() {
+        let [a, b, c] = [generate_dilithium3(), generate_dilithium3(), generate_dilithium3()];
+
+        // gap: the second link does not start where the first ended
+        assert!(verify_rotation_chain(&[rotate(&a, &b, 1), rotate(&a, &c, 2)], None).is_err());
+        // out of order in time
+        assert!(verify_rotation_chain(&[rotate(&a, &b, 5), rotate(&b, &c, 4)], None).is_err());
+        // cycle back to an earlier key
+        assert!(verify_rotation_chain(&[rotate(&a, &b, 1), rotate(&b, &a, 2)], None).is_err());
+        // a key cannot succeed itself
+        assert!(rotate(&a, &a, 1).verify().is_err());
+    }
+
+    #[test]
+    fn both_signatures_cover_every_field() {
+        let [a, b, c] = [generate_dilithium3(), generate_dilithium3(), generate_dilithium3()];
+        let cert = rotate(&a, &b, 7);
+        cert.verify().unwrap();
+
+        let edits: [fn(&mut RotationCert); 4] = [
+            |c| c.issued_at += 1,
+            |c| c.reason = None,
+            |c| c.v = 2,
+            |c| c.new_scheme = "other".into(),
+        ];
+        for edit in edits {
+            let mut edited = cert.clone();
+            edit(&mut edited);
+            assert!(edited.verify().is_err());
+        }
+        // swapping in a key that did not sign breaks the statement itself
+        let swapped = RotationCert { new_public_hex: hex::encode(&c.public), ..cert.clone() };
+        assert!(swapped.verify().is_err());
+        // the new key alone cannot forge the old key's consent
+        let forged = issue_rotation(&a.public, &c.secret, &b.public, &b.secret, 7, Some("scheduled".into())).unwrap();
+        assert!(forged.verify().unwrap_err().0.contains("old key"));
+    }
+}
@@ -0,0 +1,450 @@
+// -------- Solana transactions --------
+//
+// Just enough of the Solana wire format to move SPL tokens from an Ed25519
+// wallet: base58 keys, program-derived addresses (for associated token
+// accounts) and legacy transaction messages. A legacy message is
+//
+//   header     num_required_signatures u8, num_readonly_signed u8,
+//              num_readonly_unsigned u8
+//   keys       compact-u16 count || 32-byte keys, ordered signer+writable,
+//              signer+readonly, writable, readonly (the fee payer first,
+//              then by key within each group, as the Solana SDK does)
+//   blockhash  32 bytes
+//   ixs        compact-u16 count || each: program index u8,
+//              compact-u16 count || account indexes, compact-u16 len || data
+//
+// and a transaction is compact-u16 count || 64-byte signatures || message,
+// each signature a plain Ed25519 signature over the message bytes. A message
+// starts with a small signature count, never with DOMAIN_PREFIX, so these
+// signatures cannot be mistaken for domain-separated ones or vice versa.
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use std::{fmt, str::FromStr};
+
+use crate::Amount;
+
+pub const SYSTEM_PROGRAM_ID: &str = "11111111111111111111111111111111";
+pub const TOKEN_PROGRAM_ID: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+pub const TOKEN_2022_PROGRAM_ID: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: &str = "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL";
+
+const PDA_MARKER: &[u8] = b"ProgramDerivedAddress";
+// spl-token instruction TransferChecked; associated-token CreateIdempotent
+const TRANSFER_CHECKED: u8 = 12;
+const CREATE_IDEMPOTENT: u8 = 1;
+
+const B58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+pub fn bs58_encode(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+    // little-endian base-58 digits of the number
+    let mut digits: Vec<u8> = Vec::with_capacity(bytes.len() * 138 / 100 + 1);
+    for &b in &bytes[zeros..] {
+        let mut carry = b as u32;
+        for d in digits.iter_mut() {
+            carry += (*d as u32) << 8;
+            *d = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let mut out = "1".repeat(zeros);
+    out.extend(digits.iter().rev().map(|d| B58_ALPHABET[*d as usize] as char));
+    out
+}
+
+pub fn bs58_decode(s: &str) -> Option<Vec<u8>> {
+    let zeros = s.bytes().take_while(|c| *c == b'1').count();
+    let mut bytes: Vec<u8> = Vec::with_capacity(s.len());
+    for c in s.bytes().skip(zeros) {
+        let mut carry = B58_ALPHABET.iter().position(|a| *a == c)? as u32;
+        for b in bytes.iter_mut() {
+            carry += (*b as u32) * 58;
+            *b = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    let mut out = vec![0u8; zeros];
+    out.extend(bytes.iter().rev());
+    Some(out)
+}
+
+/// A Solana account address: 32 bytes, written in base58.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Pubkey(pub [u8; 32]);
+
+impl Pubkey {
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> { Some(Pubkey(bytes.try_into().ok()?)) }
+
+    /// True if the key is a point on the Ed25519 curve, i.e. it could have a
+    /// private key. Program-derived addresses never are.
+    pub fn is_on_curve(&self) -> bool { CompressedEdwardsY(self.0).decompress().is_some() }
+}
+
+impl fmt::Display for Pubkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(&bs58_encode(&self.0)) }
+}
+
+impl fmt::Debug for Pubkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "Pubkey({self})") }
+}
+
+impl FromStr for Pubkey {
+    type Err = SolanaError;
+    fn from_str(s: &str) -> Result<Self, SolanaError> {
+        bs58_decode(s).and_then(|b| Pubkey::from_bytes(&b)).ok_or_else(|| SolanaError::BadKey(s.to_string()))
+    }
+}
+
+impl Serialize for Pubkey {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> { s.serialize_str(&self.to_string()) }
+}
+
+impl<'de> Deserialize<'de> for Pubkey {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        String::deserialize(d)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+fn known(id: &str) -> Pubkey { id.parse().expect("built-in program id") }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SolanaError {
+    BadKey(String),
+    /// No bump seed gives an off-curve address (practically never happens).
+    NoProgramAddress,
+    UnknownTokenProgram(String),
+    TooLarge(&'static str),
+    /// SPL token amounts are u64 base units.
+    AmountTooLarge(Amount),
+    NotEd25519,
+}
+
+impl fmt::Display for SolanaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SolanaError::BadKey(k)              => write!(f, "solana: `{k}` is not a base58 32-byte key"),
+            SolanaError::NoProgramAddress       => write!(f, "solana: no valid program address for these seeds"),
+            SolanaError::UnknownTokenProgram(p) => write!(f, "solana: `{p}` is not an SPL token program"),
+            SolanaError::TooLarge(what)         => write!(f, "solana: too many {what} for one transaction"),
+            SolanaError::AmountTooLarge(a)      => write!(f, "solana: {a} is more than a token account can hold"),
+            SolanaError::NotEd25519             => write!(f, "solana: transactions need an ed25519 key"),
+        }
+    }
+}
+
+impl std::error::Error for SolanaError {}
+
+/// Address derived from `seeds` for `program`, and its bump seed: the first
+/// of bumps 255, 254, ... whose hash lands off the curve.
+pub fn find_program_address(seeds: &[&[u8]], program: &Pubkey) -> Result<(Pubkey, u8), SolanaError> {
+    for bump in (0..=u8::MAX).rev() {
+        let mut h = Sha256::new();
+        for seed in seeds {
+            h.update(seed);
+        }
+        h.update([bump]);
+        h.update(program.0);
+        h.update(PDA_MARKER);
+        let key = Pubkey(h.finalize().into());
+        if !key.is_on_curve() {
+            return Ok((key, bump));
+        }
+    }
+    Err(SolanaError::NoProgramAddress)
+}
+
+/// The associated token account holding `mint` tokens for `owner`.
+pub fn associated_token_address(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Result<Pubkey, SolanaError> {
+    let seeds: [&[u8]; 3] = [&owner.0, &token_program.0, &mint.0];
+    Ok(find_program_address(&seeds, &known(ASSOCIATED_TOKEN_PROGRAM_ID))?.0)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AccountMeta { pub pubkey: Pubkey, pub is_signer: bool, pub is_writable: bool }
+
+impl AccountMeta {
+    fn writable(pubkey: Pubkey, is_signer: bool) -> Self { AccountMeta { pubkey, is_signer, is_writable: true } }
+    fn readonly(pubkey: Pubkey, is_signer: bool) -> Self { AccountMeta { pubkey, is_signer, is_writable: false } }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Instruction { pub program_id: Pubkey, pub accounts: Vec<AccountMeta>, pub data: Vec<u8> }
+
+/// spl-token `TransferChecked`: moves `amount` base units of `mint` from
+/// token account `source` to token account `destination`.
+pub fn transfer_checked(
+    token_program: &Pubkey, source: &Pubkey, mint: &Pubkey, destination: &Pubkey, owner: &Pubkey, amount: u64, decimals: u8,
+) -> Instruction {
+    let mut data = vec![TRANSFER_CHECKED];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(decimals);
+    Instruction {
+        program_id: *token_program,
+        accounts: vec![
+            AccountMeta::writable(*source, false),
+            AccountMeta::readonly(*mint, false),
+            AccountMeta::writable(*destination, false),
+            AccountMeta::readonly(*owner, true),
+        ],
+        data,
+    }
+}
+
+/// Associated-token `CreateIdempotent`: opens `owner`'s token account for
+/// `mint` at `payer`'s expense, or does nothing if it already exists.
+pub fn create_associated_token_account_idempotent(
+    payer: &Pubkey, owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey,
+) -> Result<Instruction, SolanaError> {
+    Ok(Instruction {
+        program_id: known(ASSOCIATED_TOKEN_PROGRAM_ID),
+        accounts: vec![
+            AccountMeta::writable(*payer, true),
+            AccountMeta::writable(associated_token_address(owner, mint, token_program)?, false),
+            AccountMeta::readonly(*owner, false),
+            AccountMeta::readonly(*mint, false),
+            AccountMeta::readonly(known(SYSTEM_PROGRAM_ID), false),
+            AccountMeta::readonly(*token_program, false),
+        ],
+        data: vec![CREATE_IDEMPOTENT],
+    })
+}
+
+fn put_compact_u16(out: &mut Vec<u8>, n: usize, what: &'static str) -> Result<(), SolanaError> {
+    let mut n = u16::try_from(n).map_err(|_| SolanaError::TooLarge(what))?;
+    loop {
+        let byte = (n & 0x7f) as u8;
+        n >>= 7;
+        if n == 0 {
+            out.push(byte);
+            return Ok(());
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Serializes a legacy message paying fees from `payer`.
+pub fn compile_message(payer: &Pubkey, instructions: &[Instruction], recent_blockhash: &[u8; 32]) -> Result<Vec<u8>, SolanaError> {
+    // merge every reference to a key, keeping the strongest flags
+    let mut metas = vec![AccountMeta::writable(*payer, true)];
+    let all = instructions.iter().flat_map(|ix| {
+        ix.accounts.iter().cloned().chain(std::iter::once(AccountMeta::readonly(ix.program_id, false)))
+    });
+    for meta in all {
+        match metas.iter_mut().find(|m| m.pubkey == meta.pubkey) {
+            Some(m) => {
+                m.is_signer |= meta.is_signer;
+                m.is_writable |= meta.is_writable;
+            }
+            None => metas.push(meta),
+        }
+    }
+    metas.sort_by_key(|m| (m.pubkey != *payer, !m.is_signer, !m.is_writable, m.pubkey));
+    let signers = metas.iter().filter(|m| m.is_signer).count();
+    let readonly_signed = metas.iter().filter(|m| m.is_signer && !m.is_writable).count();
+    let readonly_unsigned = metas.iter().filter(|m| !m.is_signer && !m.is_writable).count();
+    if metas.len() > u8::MAX as usize {
+        return Err(SolanaError::TooLarge("accounts"));
+    }
+    let index = |k: &Pubkey| metas.iter().position(|m| m.pubkey == *k).expect("collected above") as u8;
+
+    let mut out = vec![signers as u8, readonly_signed as u8, readonly_unsigned as u8];
+    put_compact_u16(&mut out, metas.len(), "accounts")?;
+    for m in &metas {
+        out.extend_from_slice(&m.pubkey.0);
+    }
+    out.extend_from_slice(recent_blockhash);
+    put_compact_u16(&mut out, instructions.len(), "instructions")?;
+    for ix in instructions {
+        out.push(index(&ix.program_id));
+        put_compact_u16(&mut out, ix.accounts.len(), "instruction accounts")?;
+        out.extend(ix.accounts.iter().map(|a| index(&a.pubkey)));
+        put_compact_u16(&mut out, ix.data.len(), "instruction data bytes")?;
+        out.extend_from_slice(&ix.data);
+    }
+    Ok(out)
+}
+
+/// An SPL token transfer between wallets (owners), not token accounts.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SplTransfer {
+    pub mint: Pubkey,
+    /// Owner of the receiving token account.
+    pub to: Pubkey,
+    /// At the mint's decimals, which the token program checks.
+    pub amount: Amount,
+    /// Defaults to the original token program.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_program: Option<Pubkey>,
+    /// Open the recipient's token account first if it does not exist yet.
+    #[serde(default)]
+    pub create_recipient_account: bool,
+}
+
+impl SplTransfer {
+    pub fn token_program(&self) -> Result<Pubkey, SolanaError> {
+        let p = self.token_program.unwrap_or_else(|| known(TOKEN_PROGRAM_ID));
+        if p != known(TOKEN_PROGRAM_ID) && p != known(TOKEN_2022_PROGRAM_ID) {
+            return Err(SolanaError::UnknownTokenProgram(p.to_string()));
+        }
+        Ok(p)
+    }
+
+    /// Source and destination token accounts when `owner` sends.
+    pub fn token_accounts(&self, owner: &Pubkey) -> Result<(Pubkey, Pubkey), SolanaError> {
+        let program = self.token_program()?;
+        Ok((
+            associated_token_address(owner, &self.mint, &program)?,
+            associated_token_address(&self.to, &self.mint, &program)?,
+        ))
+    }
+
+    /// The message `owner` signs to make this transfer, paying its own fees.
+    pub fn message(&self, owner: &Pubkey, recent_blockhash: &[u8; 32]) -> Result<Vec<u8>, SolanaError> {
+        let program = self.token_program()?;
+        let (source, destination) = self.token_accounts(owner)?;
+        let mut ixs = Vec::with_capacity(2);
+        if self.create_recipient_account {
+            ixs.push(create_associated_token_account_idempotent(owner, &self.to, &self.mint, &program)?);
+        }
+        let units = u64::try_from(self.amount.units()).map_err(|_| SolanaError::AmountTooLarge(self.amount))?;
+        ixs.push(transfer_checked(&program, &source, &self.mint, &destination, owner, units, self.amount.decimals()));
+        compile_message(owner, &ixs, recent_blockhash)
+    }
+}
+
+/// Ed25519 signature over a compiled message by the key `secret`.
+pub fn sign_solana_message(secret: &[u8], message: &[u8]) -> Result<[u8; 64], SolanaError> {
+    if crate::SCHEME != "ed25519" {
+        return Err(SolanaError::NotEd25519);
+    }
+    let signed = crate::sign_message(secret, message);
+    signed.get(..64).and_then(|s| s.try_into().ok()).ok_or(SolanaError::NotEd25519)
+}
+
+/// A transaction carrying the single signature of its fee payer, ready for
+/// `sendTransaction`.
+pub fn single_signer_transaction(signature: &[u8; 64], message: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(1 + 64 + message.len());
+    out.push(1);
+    out.extend_from_slice(signature);
+    out.extend_from_slice(message);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const OWNER: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+
+    fn key(s: &str) -> Pubkey { s.parse().unwrap() }
+
+    #[test]
+    fn base58_vectors() {
+        let vectors: [(&[u8], &str); 7] = [
+            (b"", ""),
+            (&[0], "1"),
+            (&[0, 0, 0x28, 0x7f, 0xb4, 0xcd], "11233QC4"),
+            (b"a", "2g"),
+            (b"bbb", "a3gV"),
+            (&[0x51, 0x6b, 0x6f, 0xcd, 0x0f], "ABnLTmg"),
+            (&[0xec, 0xac, 0x89, 0xca, 0xd9, 0x39, 0x23, 0xc0, 0x23, 0x21], "EJDM8drfXA6uyA"),
+        ];
+        for (bytes, text) in vectors {
+            assert_eq!(bs58_encode(bytes), text);
+            assert_eq!(bs58_decode(text).as_deref(), Some(bytes));
+        }
+        assert_eq!(key(SYSTEM_PROGRAM_ID).0, [0; 32]);
+        assert_eq!(bs58_decode("0OIl"), None);
+        assert!("tooShort".parse::<Pubkey>().is_err());
+    }
+
+    #[test]
+    fn program_addresses_match_the_sdk() {
+        // computed with solana-pubkey's find_program_address
+        let token = key(TOKEN_PROGRAM_ID);
+        let (pda, bump) = find_program_address(&[b"metadata", &key(MINT).0], &token).unwrap();
+        assert_eq!((pda.to_string().as_str(), bump), ("Ax7Gb5etpEyh6PKTLimKxhYZ6RekcGwiSet176ya3k8F", 255));
+        let (pda, bump) = find_program_address(&[], &key(ASSOCIATED_TOKEN_PROGRAM_ID)).unwrap();
+        assert_eq!((pda.to_string().as_str(), bump), ("DzQr5rR32D2de4ugfqgNmKboRoBK5eid5K7WpRCxeRBY", 254));
+        assert!(!pda.is_on_curve());
+
+        let ata = associated_token_address(&key(OWNER), &key(MINT), &token).unwrap();
+        assert_eq!(ata.to_string(), "FGETo8T8wMcN2wCjav8VK6eh3dLk63evNDPxzLSJra8B");
+        let ata = associated_token_address(&key(OWNER), &key(MINT), &key(TOKEN_2022_PROGRAM_ID)).unwrap();
+        assert_eq!(ata.to_string(), "GdjpegrtGwU3pgtzPivYVViSA8rmGL248qBVKzsrU3DD");
+    }
+
+    #[test]
+    fn messages_match_the_sdk_byte_for_byte() {
+        let payer = Pubkey([7; 32]);
+        let transfer = SplTransfer {
+            mint: key(MINT),
+            to: key(OWNER),
+            amount: Amount::parse("1.500000").unwrap(),
+            token_program: None,
+            create_recipient_account: true,
+        };
+        let message = transfer.message(&payer, &[9; 32]).unwrap();
+        // solana-message's Message::new_with_blockhash for the same two instructions
+        let expected = concat!(
+            "0100050807070707070707070707070707070707070707070707070707070707070707075c8e662bce5ed7fbab9c023346a0a0371afac44d",
+            "60949853ae228940b273de9ed3ea8cf5acaca8cd05207512175c43cef54a5dd99ede20a16b55253738f397dc000000000000000000000000",
+            "000000000000000000000000000000000000000006ddf6e1d765a193d9cbe146ceeb79ac1cb485ed5f5b37913a8cf5857eff00a97e8c0887",
+            "60bfde1dddcf32c17f209b8242ee52aaf131facd88d0ea2c6d0b06f28c97258f4e2489f1bb3d1029148e0d830b5a1399daff1084048e7bd8",
+            "dbe9f859c6fa7af3bedbad3a3d65f36aabc97431b1bbe4c2d2f6e0e47ca60203452f5d610909090909090909090909090909090909090909",
+            "09090909090909090909090902060600020507030401010404010702000a0c60e316000000000006",
+        );
+        assert_eq!(hex::encode(&message), expected);
+        assert_eq!(&message[..3], &[1, 0, 5]);
+        assert_eq!(&message[4..36], &payer.0);
+    }
+
+    #[test]
+    fn keys_merge_and_order_by_role() {
+        let (payer, signer, w, r) = (Pubkey([9; 32]), Pubkey([1; 32]), Pubkey([2; 32]), Pubkey([3; 32]));
+        let program = Pubkey([4; 32]);
+        let ix = Instruction {
+            program_id: program,
+            accounts: vec![
+                AccountMeta::readonly(r, false),
+                AccountMeta::readonly(w, false),
+                AccountMeta::writable(w, false),
+                AccountMeta::readonly(signer, true),
+                AccountMeta::readonly(payer, false),
+            ],
+            data: vec![],
+        };
+        let message = compile_message(&payer, &[ix], &[0; 32]).unwrap();
+        // payer, then readonly signer, writable, and readonly keys sorted
+        assert_eq!(&message[..4], &[2, 1, 2, 5]);
+        let keys: Vec<&[u8]> = message[4..4 + 5 * 32].chunks(32).collect();
+        assert_eq!(keys, [&payer.0[..], &signer.0, &w.0, &r.0, &program.0]);
+        // program index, then the instruction's accounts by index
+        assert_eq!(&message[4 + 5 * 32 + 32..], &[1, 4, 5, 3, 2, 2, 1, 0, 0]);
+    }
+
+    #[test]
+    fn transfers_refuse_what_the_chain_would() {
+        let mut transfer = SplTransfer {
+            mint: key(MINT),
+            to: key(OWNER),
+            amount: Amount::from_units(u128::from(u64::MAX) + 1, 6).unwrap(),
+            token_program: None,
+            create_recipient_account: false,
+        };
+        assert!(matches!(transfer.message(&Pubkey([7; 32]), &[0; 32]), Err(SolanaError::AmountTooLarge(_))));
+        transfer.token_program = Some(key(SYSTEM_PROGRAM_ID));
+        assert!(matches!(transfer.token_program(), Err(SolanaError::UnknownTokenProgram(_))));
+    }
+}
@@ -0,0 +1,129 @@
+// -------- SubjectPublicKeyInfo (RFC 5280) encoding --------
+//
+//   SEQUENCE { SEQUENCE { OID algorithm }, BIT STRING public_key }
+//
+// Parameters are absent for every algorithm here (RFC 8410 for Ed25519,
+// FIPS 204 / draft-ietf-lamps-dilithium-certificates for ML-DSA).
+use base64::{engine::general_purpose::STANDARD as B64, Engine};
+
+/// (normalized scheme label, dotted OID). Round-3 Dilithium, which the `pq`
+/// feature builds, is not ML-DSA; it uses the OQS arc instead.
+const OIDS: &[(&str, &str)] = &[
+    ("ed25519",    "1.3.101.112"),
+    ("mldsa44",    "2.16.840.1.101.3.4.3.17"),
+    ("mldsa65",    "2.16.840.1.101.3.4.3.18"),
+    ("mldsa87",    "2.16.840.1.101.3.4.3.19"),
+    ("dilithium3", "1.3.6.1.4.1.2.267.7.6.5"),
+];
+
+fn normalize(scheme: &str) -> String {
+    scheme.chars().filter(char::is_ascii_alphanumeric).collect::<String>().to_ascii_lowercase()
+}
+
+/// Dotted OID for a scheme label such as "ed25519" or "ML-DSA-65".
+pub fn scheme_oid(scheme: &str) -> Option<&'static str> {
+    let norm = normalize(scheme);
+    OIDS.iter().find(|(s, _)| *s == norm).map(|(_, oid)| *oid)
+}
+
+fn der_len(len: usize, out: &mut Vec<u8>) {
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len.to_be_bytes().into_iter().skip_while(|b| *b == 0).collect();
+        out.push(0x80 | bytes.len() as u8);
+        out.extend_from_slice(&bytes);
+    }
+}
+
+fn der(tag: u8, body: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    der_len(body.len(), &mut out);
+    out.extend_from_slice(body);
+    out
+}
+
+fn der_oid(dotted: &str) -> Vec<u8> {
+    let arcs: Vec<u64> = dotted.split('.').map(|a| a.parse().expect("static oid")).collect();
+    let mut body = Vec::new();
+    let mut push_arc = |mut v: u64| {
+        let mut tmp = vec![(v & 0x7f) as u8];
+        v >>= 7;
+        while v > 0 {
+            tmp.push(0x80 | (v & 0x7f) as u8);
+            v >>= 7;
+        }
+        tmp.reverse();
+        body.extend_from_slice(&tmp);
+    };
+    push_arc(arcs[0] * 40 + arcs[1]);
+    for arc in &arcs[2..] {
+        push_arc(*arc);
+    }
+    der(0x06, &body)
+}
+
+/// DER-encoded SubjectPublicKeyInfo; `None` for schemes without a known OID.
+pub fn spki_der(scheme: &str, public: &[u8]) -> Option<Vec<u8>> {
+    let algorithm = der(0x30, &der_oid(scheme_oid(scheme)?));
+    let mut bits = vec![0u8]; // no unused bits
+    bits.extend_from_slice(public);
+    let mut body = algorithm;
+    body.extend_from_slice(&der(0x03, &bits));
+    Some(der(0x30, &body))
+}
+
+/// `-----BEGIN PUBLIC KEY-----` armor around [`spki_der`].
+pub fn spki_pem(scheme: &str, public: &[u8]) -> Option<String> {
+    let b64 = B64.encode(spki_der(scheme, public)?);
+    let mut pem = String::from("-----BEGIN PUBLIC KEY-----\n");
+    for line in b64.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).expect("base64 is ascii"));
+        pem.push('\n');
+    }
+    pem.push_str("-----END PUBLIC KEY-----\n");
+    Some(pem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ed25519_matches_rfc8410() {
+        // RFC 8410 section 10.1
+        let public = hex::decode("19bf44096984cdfe8541bac167dc3b96c85086aa30b6b6cb0c5c38ad703166e1").unwrap();
+        let der = spki_der("Ed25519", &public).unwrap();
+        assert_eq!(B64.encode(&der), "MCowBQYDK2VwAyEAGb9ECWmEzf6FQbrBZ9w7lshQhqowtrbLDFw4rXAxZuE=");
+        assert_eq!(
+            spki_pem("ed25519", &public).unwrap(),
+            "-----BEGIN PUBLIC KEY-----\nMCowBQYDK2VwAyEAGb9ECWmEzf6FQbrBZ9w7lshQhqowtrbLDFw4rXAxZuE=\n-----END PUBLIC KEY-----\n",
+        );
+    }
+
+    #[test]
+    fn long_keys_use_long_form_lengths() {
+        let public = vec![0xab; 1952];
+        let der = spki_der("dilithium3", &public).unwrap();
+        // outer SEQUENCE and BIT STRING lengths both need two bytes
+        assert_eq!(&der[..4], &[0x30, 0x82, 0x07, 0xb4]);
+        let oid = der_oid("1.3.6.1.4.1.2.267.7.6.5");
+        assert_eq!(oid, [0x06, 0x0b, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x02, 0x82, 0x0b, 0x07, 0x06, 0x05]);
+        assert_eq!(&der[4..6], &[0x30, oid.len() as u8]);
+        let bits = 6 + oid.len();
+        assert_eq!(&der[bits..bits + 5], &[0x03, 0x82, 0x07, 0xa1, 0x00]);
+        assert_eq!(&der[bits + 5..], public.as_slice());
+        assert_eq!(der.len(), 4 + 0x07b4);
+
+        let pem = spki_pem("dilithium3", &public).unwrap();
+        assert!(pem.lines().skip(1).take_while(|l| !l.starts_with("-----")).all(|l| l.len() <= 64));
+    }
+
+    #[test]
+    fn oids_by_label() {
+        assert_eq!(scheme_oid("ML-DSA-65"), Some("2.16.840.1.101.3.4.3.18"));
+        assert_eq!(der_oid("2.16.840.1.101.3.4.3.18"), hex::decode("0609608648016503040312").unwrap());
+        assert_eq!(scheme_oid("rsa"), None);
+        assert!(spki_der("rsa", &[1, 2, 3]).is_none());
+    }
+}
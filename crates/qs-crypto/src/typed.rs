@@ -0,0 +1,303 @@
+// -------- typed structured data (EIP-712 style) --------
+//
+// digest = keccak256(0x19 0x01 || hashStruct(QSDomain, domain) || hashStruct(primaryType, message))
+//
+// The digest is signed like any other message, under the reserved
+// `typed_data_domain()`, so it can never pass for a plain signature.
+//
+// hashStruct(T, v) = keccak256(keccak256(encodeType(T)) || encodeData(v)), with every
+// field encoded to 32 bytes: strings and bytes hashed, uints/bools left-padded,
+// `QS…` addresses as their 20 bytes, arrays and nested structs hashed.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use sha3::{Digest, Keccak256};
+
+use crate::{Domain, sign_domain, verify_domain};
+
+pub const DOMAIN_TYPE: &str = "QSDomain";
+pub const TYPED_DATA_TAG: &str = "typed-data";
+
+/// Domain the digest is signed under; the payload's own domain is inside it.
+pub fn typed_data_domain() -> Domain { Domain::new(TYPED_DATA_TAG, "quantumshield") }
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct TypedField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+}
+
+/// Payload a wallet is asked to sign: a schema, the domain it is valid in, and
+/// the message of type `primary_type`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TypedData {
+    pub types: BTreeMap<String, Vec<TypedField>>,
+    pub primary_type: String,
+    pub domain: Domain,
+    pub message: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypedDataError(pub String);
+
+impl fmt::Display for TypedDataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "typed data: {}", self.0) }
+}
+
+impl std::error::Error for TypedDataError {}
+
+fn err<T>(msg: impl Into<String>) -> Result<T, TypedDataError> { Err(TypedDataError(msg.into())) }
+
+fn keccak(bytes: &[u8]) -> [u8; 32] { Keccak256::digest(bytes).into() }
+
+fn word_u128(v: u128) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out[16..].copy_from_slice(&v.to_be_bytes());
+    out
+}
+
+impl TypedData {
+    fn domain_types() -> Vec<TypedField> {
+        vec![
+            TypedField { name: "tag".into(),    ty: "string".into() },
+            TypedField { name: "app_id".into(), ty: "string".into() },
+        ]
+    }
+
+    fn fields(&self, ty: &str) -> Option<&[TypedField]> {
+        self.types.get(ty).map(Vec::as_slice)
+    }
+
+    /// Struct types reachable from `ty`, including itself.
+    fn collect_deps(&self, ty: &str, seen: &mut BTreeSet<String>) {
+        let base = ty.trim_end_matches("[]");
+        let Some(fields) = self.fields(base) else { return };
+        if !seen.insert(base.to_string()) { return; }
+        for f in fields {
+            self.collect_deps(&f.ty, seen);
+        }
+    }
+
+    /// `Primary(type name,...)` followed by its dependencies in name order.
+    pub fn encode_type(&self, ty: &str) -> Result<String, TypedDataError> {
+        if self.fields(ty).is_none() {
+            return err(format!("unknown struct type `{ty}`"));
+        }
+        let mut deps = BTreeSet::new();
+        self.collect_deps(ty, &mut deps);
+        deps.remove(ty);
+        let mut out = String::new();
+        for name in std::iter::once(ty.to_string()).chain(deps) {
+            let fields = self.fields(&name).unwrap_or_default();
+            let list: Vec<String> = fields.iter().map(|f| format!("{} {}", f.ty, f.name)).collect();
+            out.push_str(&format!("{name}({})", list.join(",")));
+        }
+        Ok(out)
+    }
+
+    pub fn type_hash(&self, ty: &str) -> Result<[u8; 32], TypedDataError> {
+        Ok(keccak(self.encode_type(ty)?.as_bytes()))
+    }
+
+    fn encode_value(&self, ty: &str, v: &Value) -> Result<[u8; 32], TypedDataError> {
+        if let Some(inner) = ty.strip_suffix("[]") {
+            let Value::Array(items) = v else { return err(format!("expected array for `{ty}`")) };
+            let mut buf = Vec::with_capacity(items.len() * 32);
+            for item in items {
+                buf.extend_from_slice(&self.encode_value(inner, item)?);
+            }
+            return Ok(keccak(&buf));
+        }
+        if self.fields(ty).is_some() {
+            return self.hash_struct(ty, v);
+        }
+        match ty {
+            "string" => match v {
+                Value::String(s) => Ok(keccak(s.as_bytes())),
+                _ => err("expected string"),
+            },
+            "bytes" => match v.as_str().map(|s| hex::decode(s.trim_start_matches("0x"))) {
+                Some(Ok(b)) => Ok(keccak(&b)),
+                _ => err("expected hex string for `bytes`"),
+            },
+            "bool" => match v {
+                Value::Bool(b) => Ok(word_u128(*b as u128)),
+                _ => err("expected bool"),
+            },
+            "address" => {
+                let hex_part = v.as_str().and_then(|s| s.strip_prefix("QS"));
+                match hex_part.map(hex::decode) {
+                    Some(Ok(b)) if b.len() == 20 => {
+                        let mut out = [0u8; 32];
+                        out[12..].copy_from_slice(&b);
+                        Ok(out)
+                    }
+                    _ => err("expected `QS…` address"),
+                }
+            }
+            t if t.starts_with("uint") => {
+                let bits: u32 = t[4..].parse().map_err(|_| TypedDataError(format!("unknown type `{t}`")))?;
+                if bits == 0 || bits > 256 || !bits.is_multiple_of(8) {
+                    return err(format!("unknown type `{t}`"));
+                }
+                // numbers up to u64 may be JSON numbers; larger ones must be decimal strings (max u128)
+                let n: u128 = match v {
+                    Value::Number(n) => n.as_u64().map(u128::from).ok_or_else(|| TypedDataError("expected unsigned integer".into()))?,
+                    Value::String(s) => s.parse().map_err(|_| TypedDataError(format!("bad integer `{s}`")))?,
+                    _ => return err("expected unsigned integer"),
+                };
+                if bits < 128 && n >> bits != 0 {
+                    return err(format!("value out of range for `{t}`"));
+                }
+                Ok(word_u128(n))
+            }
+            t => err(format!("unknown type `{t}`")),
+        }
+    }
+
+    /// Every declared field must be present; undeclared fields are rejected so
+    /// nothing reaches the signer that the schema does not describe.
+    pub fn hash_struct(&self, ty: &str, v: &Value) -> Result<[u8; 32], TypedDataError> {
+        let fields = self.fields(ty).ok_or_else(|| TypedDataError(format!("unknown struct type `{ty}`")))?;
+        let Value::Object(obj) = v else { return err(format!("expected object for `{ty}`")) };
+        if let Some(extra) = obj.keys().find(|k| !fields.iter().any(|f| &f.name == *k)) {
+            return err(format!("field `{extra}` is not part of `{ty}`"));
+        }
+        let mut buf = self.type_hash(ty)?.to_vec();
+        for f in fields {
+            let fv = obj.get(&f.name).ok_or_else(|| TypedDataError(format!("missing field `{}.{}`", ty, f.name)))?;
+            buf.extend_from_slice(&self.encode_value(&f.ty, fv)?);
+        }
+        Ok(keccak(&buf))
+    }
+
+    pub fn domain_separator(&self) -> Result<[u8; 32], TypedDataError> {
+        if !self.domain.is_valid() {
+            return err("invalid domain");
+        }
+        let mut types = BTreeMap::new();
+        types.insert(DOMAIN_TYPE.to_string(), Self::domain_types());
+        let schema = TypedData { types, primary_type: DOMAIN_TYPE.into(), domain: self.domain.clone(), message: Value::Null };
+        let domain = serde_json::to_value(&self.domain).map_err(|e| TypedDataError(e.to_string()))?;
+        schema.hash_struct(DOMAIN_TYPE, &domain)
+    }
+
+    /// The 32-byte digest that actually gets signed.
+    pub fn signing_hash(&self) -> Result<[u8; 32], TypedDataError> {
+        if self.types.contains_key(DOMAIN_TYPE) {
+            return err(format!("`{DOMAIN_TYPE}` is reserved"));
+        }
+        let mut buf = vec![0x19, 0x01];
+        buf.extend_from_slice(&self.domain_separator()?);
+        buf.extend_from_slice(&self.hash_struct(&self.primary_type, &self.message)?);
+        Ok(keccak(&buf))
+    }
+}
+
+/// Returns the signing hash and the hash signed under [`typed_data_domain`].
+pub fn sign_typed(secret: &[u8], data: &TypedData) -> Result<([u8; 32], Vec<u8>), TypedDataError> {
+    let hash = data.signing_hash()?;
+    let signed = sign_domain(secret, &typed_data_domain(), &hash).ok_or_else(|| TypedDataError("signing failed".into()))?;
+    Ok((hash, signed))
+}
+
+/// True only if `signed` opens under `public` to this payload's signing hash.
+pub fn verify_typed(public: &[u8], data: &TypedData, signed: &[u8]) -> Result<bool, TypedDataError> {
+    let hash = data.signing_hash()?;
+    Ok(verify_domain(public, &typed_data_domain(), signed).as_deref() == Some(hash.as_slice()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate_dilithium3;
+    use serde_json::json;
+
+    fn mail() -> TypedData {
+        serde_json::from_value(json!({
+            "types": {
+                "Person": [{ "name": "name", "type": "string" }, { "name": "wallet", "type": "address" }],
+                "Mail": [
+                    { "name": "from", "type": "Person" },
+                    { "name": "to", "type": "Person" },
+                    { "name": "contents", "type": "string" },
+                    { "name": "amount", "type": "uint64" },
+                ],
+            },
+            "primaryType": "Mail",
+            "domain": { "tag": "mail", "app_id": "test" },
+            "message": {
+                "from": { "name": "Cow", "wallet": "QScd2a3d9f938e13cd947ec05abc7fe734df8dd826" },
+                "to": { "name": "Bob", "wallet": "QSbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb" },
+                "contents": "Hello, Bob!",
+                "amount": 42,
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn encode_type_lists_dependencies_after_the_primary_type() {
+        assert_eq!(
+            mail().encode_type("Mail").unwrap(),
+            "Mail(Person from,Person to,string contents,uint64 amount)Person(string name,address wallet)",
+        );
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let kp = generate_dilithium3();
+        let data = mail();
+        let (hash, signed) = sign_typed(&kp.secret, &data).unwrap();
+        assert_eq!(hash, data.signing_hash().unwrap());
+        assert!(verify_typed(&kp.public, &data, &signed).unwrap());
+
+        let mut changed = data.clone();
+        changed.message["amount"] = json!(43);
+        assert!(!verify_typed(&kp.public, &changed, &signed).unwrap());
+        let mut moved = data.clone();
+        moved.domain = Domain::new("mail", "elsewhere");
+        assert!(!verify_typed(&kp.public, &moved, &signed).unwrap());
+    }
+
+    #[test]
+    fn digests_are_not_interchangeable_with_plain_signatures() {
+        let kp = generate_dilithium3();
+        let data = mail();
+        let hash = data.signing_hash().unwrap();
+        // a raw or domain-separated signature of the same 32 bytes does not pass as typed
+        assert!(!verify_typed(&kp.public, &data, &crate::sign_message(&kp.secret, &hash)).unwrap());
+        let plain = sign_domain(&kp.secret, &data.domain, &hash).unwrap();
+        assert!(!verify_typed(&kp.public, &data, &plain).unwrap());
+        // and a typed signature does not open under the payload's own domain
+        let (_, signed) = sign_typed(&kp.secret, &data).unwrap();
+        assert_eq!(verify_domain(&kp.public, &data.domain, &signed), None);
+    }
+
+    #[test]
+    fn rejects_malformed_payloads() {
+        let mut extra = mail();
+        extra.message["bcc"] = json!("x");
+        assert!(extra.signing_hash().is_err());
+
+        let mut missing = mail();
+        missing.message.as_object_mut().unwrap().remove("contents");
+        assert!(missing.signing_hash().is_err());
+
+        let mut too_big = mail();
+        too_big.message["amount"] = json!("18446744073709551616");
+        assert!(too_big.signing_hash().is_err());
+
+        let mut bad_address = mail();
+        bad_address.message["to"]["wallet"] = json!("0xbbbb");
+        assert!(bad_address.signing_hash().is_err());
+
+        let mut reserved = mail();
+        reserved.types.insert(DOMAIN_TYPE.into(), vec![]);
+        assert!(reserved.signing_hash().is_err());
+    }
+}
@@ -0,0 +1,39 @@
+//! Wallet directory and JSON file helpers shared by the QuantumShield crates.
+use anyhow::Context;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Overrides where wallets and the daemon's state files live.
+pub const WALLET_DIR_ENV: &str = "QS_WALLET_DIR";
+pub const DEFAULT_WALLET_DIR: &str = "wallet_data";
+
+pub fn wallet_dir() -> PathBuf {
+    std::env::var_os(WALLET_DIR_ENV).map_or_else(|| PathBuf::from(DEFAULT_WALLET_DIR), PathBuf::from)
+}
+
+/// Creates the wallet directory if needed and returns it.
+pub fn ensure_wallet_dir() -> anyhow::Result<PathBuf> {
+    let dir = wallet_dir();
+    fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+    Ok(dir)
+}
+
+pub fn read_json<T: DeserializeOwned>(path: impl AsRef<Path>) -> anyhow::Result<T> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_slice(&bytes).with_context(|| format!("parsing {}", path.display()))
+}
+
+/// Writes to a temporary file next to `path` and renames it over, so readers
+/// never see a half-written file.
+pub fn write_json<T: Serialize + ?Sized>(path: impl AsRef<Path>, value: &T) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, serde_json::to_vec_pretty(value)?).with_context(|| format!("writing {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("replacing {}", path.display()))
+}
@@ -0,0 +1,111 @@
+//! Approval queue routes for protected wallets, and the event stream the
+//! frontend subscribes to for queue changes.
+use axum::{
+    extract::{Path, Query},
+    response::sse::{Event, KeepAlive, Sse},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, sync::OnceLock, time::Duration};
+use tokio::sync::broadcast;
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+use qs_core::approval::{self, ApprovalState, SignRequest};
+
+use crate::{ApiError, bad_request, core_err};
+
+/// How often overdue requests are marked expired (and announced).
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+const EVENT_BUFFER: usize = 256;
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/approvals",             get(list))
+        .route("/approvals/events",      get(events))
+        .route("/approvals/:id",         get(lookup))
+        .route("/approvals/:id/approve", post(approve))
+        .route("/approvals/:id/reject",  post(reject))
+        .route("/wallets/:name/approval", get(protection).post(set_protection))
+}
+
+fn channel() -> &'static broadcast::Sender<SignRequest> {
+    static EVENTS: OnceLock<broadcast::Sender<SignRequest>> = OnceLock::new();
+    EVENTS.get_or_init(|| broadcast::channel(EVENT_BUFFER).0)
+}
+
+/// Announces a request that was created or changed state. No subscribers is fine.
+pub fn publish(request: &SignRequest) {
+    let _ = channel().send(request.clone());
+}
+
+/// Periodically expires overdue requests so subscribers hear about them.
+pub fn spawn_expiry_sweeper() {
+    tokio::spawn(async {
+        let mut tick = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            tick.tick().await;
+            match tokio::task::spawn_blocking(approval::expire_due).await {
+                Ok(Ok(expired)) => expired.iter().for_each(publish),
+                Ok(Err(e)) => eprintln!("approvals: expiry sweep failed: {e}"),
+                Err(e) => eprintln!("approvals: expiry sweep panicked: {e}"),
+            }
+        }
+    });
+}
+
+#[derive(Deserialize)] struct ListQuery     { state: Option<ApprovalState> }
+#[derive(Deserialize)] struct ApproveReq    { approver_password: String }
+#[derive(Deserialize)] struct RejectReq     { reason: Option<String> }
+/// Enabling needs the wallet `password` and sets `approver_password`;
+/// disabling needs only `approver_password`.
+#[derive(Deserialize)]
+struct ProtectionReq { password: Option<String>, approver_password: String, enabled: bool, ttl_secs: Option<u64> }
+#[derive(Serialize)]   struct ProtectionRes { protected: bool, ttl_secs: Option<u64> }
+
+async fn list(Query(q): Query<ListQuery>) -> Result<Json<Vec<SignRequest>>, ApiError> {
+    Ok(Json(approval::list(q.state).map_err(core_err)?))
+}
+
+/// Server-sent events: one `sign_request` event, carrying the full request,
+/// whenever a request is queued, approved, rejected or expires.
+async fn events() -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = BroadcastStream::new(channel().subscribe()).filter_map(|r| {
+        // a lagging subscriber skips what it missed and can re-list /approvals
+        let request = r.ok()?;
+        Event::default().event("sign_request").json_data(&request).ok().map(Ok)
+    });
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+async fn lookup(Path(id): Path<String>) -> Result<Json<SignRequest>, ApiError> {
+    Ok(Json(approval::get(&id).map_err(core_err)?))
+}
+
+async fn approve(Path(id): Path<String>, Json(req): Json<ApproveReq>) -> Result<Json<SignRequest>, ApiError> {
+    let request = approval::approve(&id, &req.approver_password).map_err(core_err)?;
+    publish(&request);
+    Ok(Json(request))
+}
+
+async fn reject(Path(id): Path<String>, Json(req): Json<RejectReq>) -> Result<Json<SignRequest>, ApiError> {
+    let request = approval::reject(&id, req.reason).map_err(core_err)?;
+    publish(&request);
+    Ok(Json(request))
+}
+
+async fn protection(Path(name): Path<String>) -> Result<Json<ProtectionRes>, ApiError> {
+    qs_core::read_wallet(&name).map_err(core_err)?;
+    let p = approval::protection(&name).map_err(core_err)?;
+    Ok(Json(ProtectionRes { protected: p.is_some(), ttl_secs: p.map(|p| p.ttl_secs) }))
+}
+
+async fn set_protection(Path(name): Path<String>, Json(req): Json<ProtectionReq>) -> Result<Json<ProtectionRes>, ApiError> {
+    if !req.enabled {
+        approval::disable_protection(&name, &req.approver_password).map_err(core_err)?;
+        return Ok(Json(ProtectionRes { protected: false, ttl_secs: None }));
+    }
+    let password = req.password.ok_or_else(|| bad_request("the wallet password is needed to turn protection on"))?;
+    let p = approval::enable_protection(&name, &password, &req.approver_password, req.ttl_secs).map_err(core_err)?;
+    Ok(Json(ProtectionRes { protected: true, ttl_secs: Some(p.ttl_secs) }))
+}
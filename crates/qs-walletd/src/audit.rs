@@ -0,0 +1,161 @@
+//! Audit log routes, the middleware that records every request, and the
+//! offline `qs-walletd audit-verify` command.
+use axum::{
+    extract::{ConnectInfo, Query, Request},
+    middleware::Next,
+    response::Response,
+    routing::get,
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
+
+use qs_core::audit::{self, AuditEntry, AuditEvent, AuditQuery, AuditReport};
+use qs_crypto::{SCHEME, address_from_pubkey};
+
+use crate::{ApiError, bad_request, core_err};
+
+/// How often an unsigned tail of the log gets a checkpoint.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(60);
+/// Passphrase the audit key is sealed under.
+const PASSPHRASE_VAR: &str = "QS_AUDIT_PASSPHRASE";
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/audit",        get(list))
+        .route("/audit/verify", get(verify))
+        .route("/audit/key",    get(key))
+}
+
+#[derive(Serialize)] struct KeyRes { scheme: &'static str, public_key_hex: String, address: String }
+#[derive(Serialize)] struct VerifyRes { ok: bool, report: Option<AuditReport>, error: Option<String> }
+#[derive(Deserialize)] struct VerifyQuery { public_key: Option<String> }
+
+async fn list(Query(q): Query<AuditQuery>) -> Result<Json<Vec<AuditEntry>>, ApiError> {
+    Ok(Json(audit::query(&q).map_err(core_err)?))
+}
+
+/// Checks this daemon's log against `public_key` if given, else against the
+/// audit key it unlocked at startup (never the one currently on disk).
+async fn verify(Query(q): Query<VerifyQuery>) -> Result<Json<VerifyRes>, ApiError> {
+    let public = match q.public_key {
+        Some(h) => hex::decode(h).map_err(bad_request)?,
+        None => audit::public_key().map_err(core_err)?,
+    };
+    let entries = audit::read_all().map_err(core_err)?;
+    Ok(Json(match audit::verify(&entries, Some(&public)) {
+        Ok(report) => VerifyRes { ok: true, report: Some(report), error: None },
+        Err(e) => VerifyRes { ok: false, report: None, error: Some(e.to_string()) },
+    }))
+}
+
+async fn key() -> Result<Json<KeyRes>, ApiError> {
+    let public = audit::public_key().map_err(core_err)?;
+    Ok(Json(KeyRes { scheme: SCHEME, address: address_from_pubkey(&public), public_key_hex: hex::encode(public) }))
+}
+
+/// `/wallets/:name/...` names a wallet; `/wallets/watch` and `/wallets/import/...` do not.
+pub(crate) fn wallet_in(path: &str) -> Option<&str> {
+    let name = path.strip_prefix("/wallets/")?.split('/').next()?;
+    (!name.is_empty() && name != "watch" && name != "import").then_some(name)
+}
+
+/// Records method, path, status and client of every request. Bodies are never
+/// logged, so passwords stay out of the log.
+pub async fn record_request(req: Request, next: Next) -> Response {
+    let method = req.method().to_string();
+    let path = req.uri().path().to_string();
+    let client = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|c| c.0.to_string());
+    let res = next.run(req).await;
+    let status = res.status();
+    let outcome = if status.is_success() { "ok" } else if status.is_client_error() { "rejected" } else { "error" };
+    let event = AuditEvent::new(wallet_in(&path), "http", outcome, json!({
+        "method": method,
+        "path": path,
+        "status": status.as_u16(),
+        "client": client,
+    }));
+    match tokio::task::spawn_blocking(move || audit::record(&event)).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => eprintln!("audit: could not record {method} {path}: {e}"),
+        Err(e) => eprintln!("audit: recording {method} {path} panicked: {e}"),
+    }
+    res
+}
+
+/// Unlocks the audit key with `QS_AUDIT_PASSPHRASE`. Without it the daemon
+/// still chains every entry but signs no checkpoints; returns whether the
+/// key was unlocked.
+pub fn unlock_from_env() -> anyhow::Result<bool> {
+    let Ok(passphrase) = std::env::var(PASSPHRASE_VAR) else {
+        eprintln!("audit: {PASSPHRASE_VAR} is not set; the log will not be checkpointed");
+        if audit::key_is_unsealed()? {
+            eprintln!("audit: warning: the audit key is stored unencrypted; set {PASSPHRASE_VAR} to seal it");
+        }
+        return Ok(false);
+    };
+    let public = audit::unlock_key(&passphrase).map_err(|e| anyhow::anyhow!("audit key: {e}"))?;
+    println!("audit: checkpoints signed by {}", address_from_pubkey(&public));
+    Ok(true)
+}
+
+/// Periodically signs whatever was logged since the last checkpoint.
+pub fn spawn_checkpointer() {
+    tokio::spawn(async {
+        let mut tick = tokio::time::interval(CHECKPOINT_INTERVAL);
+        loop {
+            tick.tick().await;
+            match tokio::task::spawn_blocking(audit::checkpoint).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => eprintln!("audit: checkpoint failed: {e}"),
+                Err(e) => eprintln!("audit: checkpoint panicked: {e}"),
+            }
+        }
+    });
+}
+
+/// `qs-walletd audit-verify [LOG] [--public-key HEX]`: checks a log file
+/// without a running daemon. Without `--public-key` the checkpoints only have
+/// to agree with each other, so pass the key from `GET /audit/key` when you can.
+pub fn verify_cli(args: &[String]) -> anyhow::Result<()> {
+    let mut log = None;
+    let mut public = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--public-key" => {
+                let hex_key = args.next().ok_or_else(|| anyhow::anyhow!("--public-key needs a value"))?;
+                public = Some(hex::decode(hex_key)?);
+            }
+            _ if log.is_none() && !arg.starts_with("--") => log = Some(PathBuf::from(arg)),
+            _ => anyhow::bail!("usage: qs-walletd audit-verify [LOG] [--public-key HEX]"),
+        }
+    }
+    let log = log.unwrap_or_else(audit::log_path);
+    let entries = audit::read_file(&log)?;
+    let report = audit::verify(&entries, public.as_deref())?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    if public.is_none() {
+        eprintln!("warning: no --public-key given; checkpoints were only checked against each other");
+    }
+    if report.unsigned_tail > 0 {
+        eprintln!("warning: the last {} entries are not covered by a checkpoint yet", report.unsigned_tail);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_name_their_wallet() {
+        assert_eq!(wallet_in("/wallets/alice/sign"), Some("alice"));
+        assert_eq!(wallet_in("/wallets/alice"), Some("alice"));
+        assert_eq!(wallet_in("/wallets/watch"), None);
+        assert_eq!(wallet_in("/wallets/import/qskey"), None);
+        assert_eq!(wallet_in("/wallets/"), None);
+        assert_eq!(wallet_in("/audit/verify"), None);
+    }
+}
@@ -0,0 +1,40 @@
+//! Wallet-directory backup routes: `/backup` and `/restore`.
+use axum::{routing::post, Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use qs_core::backup::{self, BackupArchive, BackupManifest};
+
+use crate::{ApiError, core_err};
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/backup",  post(create))
+        .route("/restore", post(restore))
+}
+
+#[derive(Deserialize)]
+struct BackupReq { passphrase: String }
+
+#[derive(Deserialize)]
+struct RestoreReq {
+    archive: BackupArchive,
+    passphrase: String,
+    #[serde(default)] overwrite: bool,
+    /// Optional wallet passwords; those keyfiles are also checked to unlock.
+    #[serde(default)] passwords: BTreeMap<String, String>,
+    /// Wallets to restore under a new password (their current one must be in `passwords`).
+    #[serde(default)] new_passwords: BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct RestoreRes { restored: Vec<String>, manifest: BackupManifest }
+
+async fn create(Json(req): Json<BackupReq>) -> Result<Json<BackupArchive>, ApiError> {
+    Ok(Json(backup::backup(&req.passphrase).map_err(core_err)?))
+}
+
+async fn restore(Json(req): Json<RestoreReq>) -> Result<Json<RestoreRes>, ApiError> {
+    let restored = backup::restore(&req.archive, &req.passphrase, req.overwrite, &req.passwords, &req.new_passwords).map_err(core_err)?;
+    Ok(Json(RestoreRes { restored, manifest: req.archive.manifest }))
+}
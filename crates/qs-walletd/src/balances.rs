@@ -0,0 +1,46 @@
+//! Linked on-chain addresses and their cached balances:
+//! `/wallets/:name/addresses` and `/wallets/:name/balances`.
+//! Balances are only read here; the qs_wallet API and `qs_wallet balances`
+//! fetch them from the chains.
+use axum::{extract::{Path, Query}, http::StatusCode, routing::{delete, get}, Json, Router};
+use serde::Deserialize;
+
+use qs_core::balances::{self, AddressBalance, Chain, LinkedAddress};
+
+use crate::{ApiError, core_err};
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/wallets/:name/addresses",                 get(list).post(link))
+        .route("/wallets/:name/addresses/:chain/:address", delete(unlink))
+        .route("/wallets/:name/balances",                  get(cached))
+}
+
+#[derive(Deserialize)]
+struct LinkReq { password: String, chain: Chain, address: String, token: Option<String>, label: Option<String> }
+#[derive(Deserialize)]
+struct UnlinkReq { password: String }
+#[derive(Deserialize)]
+struct TokenQuery { token: Option<String> }
+
+async fn list(Path(name): Path<String>) -> Result<Json<Vec<LinkedAddress>>, ApiError> {
+    Ok(Json(balances::linked(&name).map_err(core_err)?))
+}
+
+async fn link(Path(name): Path<String>, Json(req): Json<LinkReq>) -> Result<Json<LinkedAddress>, ApiError> {
+    let linked = balances::link(&name, &req.password, req.chain, &req.address, req.token.as_deref(), req.label).map_err(core_err)?;
+    Ok(Json(linked))
+}
+
+async fn unlink(
+    Path((name, chain, address)): Path<(String, Chain, String)>,
+    Query(q): Query<TokenQuery>,
+    Json(req): Json<UnlinkReq>,
+) -> Result<StatusCode, ApiError> {
+    balances::unlink(&name, &req.password, chain, &address, q.token.as_deref()).map_err(core_err)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn cached(Path(name): Path<String>) -> Result<Json<Vec<AddressBalance>>, ApiError> {
+    Ok(Json(balances::balances(&name).map_err(core_err)?))
+}
@@ -0,0 +1,37 @@
+//! Bridge intent routes: wallets sign intents, the bridge verifies them.
+use axum::{extract::Path, routing::post, Json, Router};
+use serde::Deserialize;
+
+use qs_core::intent::{self, IntentRequest, VerifiedIntent};
+use qs_crypto::SignedIntent;
+
+use crate::{ApiError, core_err};
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/wallets/:name/bridge-intent", post(sign))
+        .route("/bridge-intents/check",        post(check))
+        .route("/bridge-intents/verify",       post(verify))
+}
+
+#[derive(Deserialize)]
+struct SignReq {
+    password: String,
+    #[serde(flatten)]
+    intent: IntentRequest,
+}
+
+async fn sign(Path(name): Path<String>, Json(req): Json<SignReq>) -> Result<Json<SignedIntent>, ApiError> {
+    Ok(Json(intent::sign(&name, &req.password, &req.intent).map_err(core_err)?))
+}
+
+/// Everything `verify` checks, without using up the nonce: call first, so a
+/// bad intent is refused before anything is prepared for it.
+async fn check(Json(signed): Json<SignedIntent>) -> Result<Json<VerifiedIntent>, ApiError> {
+    Ok(Json(intent::check(&signed).map_err(core_err)?))
+}
+
+/// Uses up the intent's nonce: call once, right before acting on it.
+async fn verify(Json(signed): Json<SignedIntent>) -> Result<Json<VerifiedIntent>, ApiError> {
+    Ok(Json(intent::verify(&signed).map_err(core_err)?))
+}
@@ -0,0 +1,27 @@
+//! The old warp API's routes, served from qs-walletd so existing callers
+//! (e.g. `bridge/index.ts` hitting `/verify/:msg/:sig`) keep working.
+//! `/sign/:msg` is not served: it was a password-less GET, triggerable
+//! cross-site, signing with the stored legacy password.
+use axum::{extract::Path, routing::get, Json, Router};
+use qs_core::legacy::{self, BalanceRes, GenerateRes, VerifyRes};
+
+use crate::{ApiError, core_err};
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/balance",          get(balance))
+        .route("/verify/:msg/:sig", get(verify))
+        .route("/generate",         get(generate))
+}
+
+async fn balance() -> Result<Json<BalanceRes>, ApiError> {
+    Ok(Json(legacy::balance().map_err(core_err)?))
+}
+
+async fn verify(Path((msg, sig)): Path<(String, String)>) -> Result<Json<VerifyRes>, ApiError> {
+    Ok(Json(legacy::verify(&msg, &sig).map_err(core_err)?))
+}
+
+async fn generate() -> Json<GenerateRes> {
+    Json(legacy::generate())
+}
@@ -0,0 +1,33 @@
+//! Sign-in with QuantumShield routes: challenges and verification for
+//! services logging users in, and answering challenges with a local wallet.
+use axum::{extract::Path, routing::post, Json, Router};
+use serde::Deserialize;
+
+use qs_core::login::{self, LoginOutcome};
+use qs_crypto::{LoginChallenge, SignedLogin};
+
+use crate::{ApiError, core_err};
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/login/challenge",       post(challenge))
+        .route("/login/verify",          post(verify))
+        .route("/wallets/:name/sign-in", post(sign_in))
+}
+
+#[derive(Deserialize)]
+struct ChallengeReq { domain: String, uri: String, statement: Option<String>, ttl_secs: Option<u64> }
+#[derive(Deserialize)]
+struct SignInReq { password: String, challenge: LoginChallenge }
+
+async fn challenge(Json(req): Json<ChallengeReq>) -> Result<Json<LoginChallenge>, ApiError> {
+    Ok(Json(login::challenge(&req.domain, &req.uri, req.statement, req.ttl_secs).map_err(core_err)?))
+}
+
+async fn verify(Json(login): Json<SignedLogin>) -> Result<Json<LoginOutcome>, ApiError> {
+    Ok(Json(login::verify(&login).map_err(core_err)?))
+}
+
+async fn sign_in(Path(name): Path<String>, Json(req): Json<SignInReq>) -> Result<Json<SignedLogin>, ApiError> {
+    Ok(Json(login::sign_in(&name, &req.password, &req.challenge).map_err(core_err)?))
+}
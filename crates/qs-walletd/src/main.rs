@@ -1,16 +1,42 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query},
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
-use std::{net::SocketAddr, path::PathBuf, sync::Arc, fs, time::Duration};
+use std::{fs, net::SocketAddr};
 use tower_http::cors::{Any, CorsLayer};
-use http::HeaderValue;
+
+use qs_core::{CoreError, WalletInfo};
+use qs_crypto::{Domain, QsKeyFile, TypedData};
+use qs_utils::ensure_wallet_dir;
+
+use revocation::{revoked, RevokedInfo};
+
+mod approvals;
+mod audit;
+mod backup;
+mod balances;
+mod intent;
+mod legacy;
+mod login;
+mod multisig;
+mod password;
+mod policy;
+mod ratelimit;
+mod registry;
+mod revocation;
+mod rotation;
+mod solana;
+mod throttle;
+mod verify;
+
 // simple readiness: wallet dir exists + r/w works + quick crypto self-check
-async fn readyz() -> Result<&'static str, (axum::http::StatusCode, String)> {
+async fn readyz() -> Result<&'static str, ApiError> {
     // 1) wallet dir
-    let root = qs_utils::ensure_wallet_dir().map_err(internal)?;
+    let root = ensure_wallet_dir().map_err(internal)?;
 
     // 2) r/w check
     let probe = root.join(".readyz.tmp");
@@ -28,77 +54,180 @@ async fn readyz() -> Result<&'static str, (axum::http::StatusCode, String)> {
     Ok("ready")
 }
 
-use qs_crypto::{
-    DilithiumKeypair, EncryptedKeyfile, address_from_pubkey, decrypt_secret, encrypt_secret,
-    generate_dilithium3, sign_message, verify_message,
-};
-use qs_utils::{ensure_wallet_dir, read_json, wallet_dir, write_json};
-
-#[derive(Clone)]
-struct AppState {
-    _root: PathBuf,
-}
-
 #[derive(Deserialize)] struct NewWalletReq { name: String, password: String }
 #[derive(Serialize)]   struct NewWalletRes { name: String, address: String }
 #[derive(Serialize)]   struct AddressRes   { address: String }
-#[derive(Deserialize)] struct SignReq      { password: String, message: String }
+#[derive(Deserialize)] struct PublicKeyQuery { format: Option<String> }
+#[derive(Serialize)]
+struct PublicKeyRes {
+    scheme: String,
+    address: String,
+    hex: String,
+    base64: String,
+    oid: Option<&'static str>,
+    spki_der_base64: Option<String>,
+    pem: Option<String>,
+}
+#[derive(Deserialize)]
+struct SignReq {
+    password: String,
+    message: String,
+    domain: Domain,
+    /// Shown to the approver when the wallet is protected.
+    description: Option<String>,
+}
 #[derive(Serialize)]   struct SignRes      { signed_hex: String }
-#[derive(Deserialize)] struct VerifyReq    { signed_hex: String }
-#[derive(Serialize)]   struct VerifyRes    { ok: bool, message: Option<String> }
-
-fn wallet_path(name: &str) -> PathBuf {
-    let mut p = wallet_dir();
-    p.push(format!("{name}.json"));
-    p
+#[derive(Deserialize)] struct VerifyReq    { signed_hex: String, domain: Domain }
+#[derive(Serialize)]   struct VerifyRes    { ok: bool, message: Option<String>, revoked: Option<RevokedInfo> }
+#[derive(Deserialize)]
+struct WatchReq {
+    name: String,
+    public_key_hex: Option<String>,
+    address: Option<String>,
+    scheme: Option<String>,
+    kem_public_key_hex: Option<String>,
+}
+#[derive(Deserialize)]
+struct ImportQsKeyReq {
+    name: String,
+    qskey: QsKeyFile,
+    qskey_password: String,
+    /// Daemon password for the new keyfile; defaults to `qskey_password`.
+    password: Option<String>,
 }
+#[derive(Deserialize)] struct ExportQsKeyReq { password: String, export_password: Option<String> }
+#[derive(Deserialize)] struct SignTypedReq   { password: String, typed_data: TypedData }
+#[derive(Serialize)]   struct SignTypedRes   { hash_hex: String, signed_hex: String }
+#[derive(Deserialize)] struct VerifyTypedReq { typed_data: TypedData, signed_hex: String }
+#[derive(Serialize)]   struct VerifyTypedRes { ok: bool, hash_hex: String, revoked: Option<RevokedInfo> }
 
 async fn healthz() -> &'static str { "ok" }
 
-async fn new_wallet(
-    State(_st): State<Arc<AppState>>,
-    Json(req): Json<NewWalletReq>,
-) -> Result<Json<NewWalletRes>, (axum::http::StatusCode, String)> {
-    ensure_wallet_dir().map_err(internal)?;
-    let kp: DilithiumKeypair = generate_dilithium3();
-    let ek: EncryptedKeyfile = encrypt_secret(&kp.public, &kp.secret, &req.password);
-    write_json(wallet_path(&req.name), &ek).map_err(internal)?;
-    let addr = address_from_pubkey(&kp.public);
-    Ok(Json(NewWalletRes { name: req.name, address: addr }))
+async fn new_wallet(Json(req): Json<NewWalletReq>) -> Result<Json<NewWalletRes>, ApiError> {
+    let info = qs_core::create_wallet(&req.name, &req.password).map_err(core_err)?;
+    Ok(Json(NewWalletRes { name: info.name, address: info.address }))
 }
 
-async fn get_address(Path(name): Path<String>) -> Result<Json<AddressRes>, (axum::http::StatusCode, String)> {
-    let ek: EncryptedKeyfile = read_json(wallet_path(&name)).map_err(not_found)?;
-    let pub_bytes = hex::decode(&ek.public_hex).map_err(bad_request)?;
-    Ok(Json(AddressRes { address: address_from_pubkey(&pub_bytes) }))
+async fn list_wallets() -> Result<Json<Vec<WalletInfo>>, ApiError> {
+    Ok(Json(qs_core::list_wallets().map_err(core_err)?))
 }
 
+/// Imports a public key (or the key behind a known address) as a watch-only wallet.
+async fn watch_wallet(Json(req): Json<WatchReq>) -> Result<Json<WalletInfo>, ApiError> {
+    let index = qs_core::address_index().map_err(core_err)?;
+    let public = qs_core::resolve_signer(req.public_key_hex.as_deref(), req.address.as_deref(), &index).map_err(core_err)?;
+    let kem = req.kem_public_key_hex.as_deref().map(hex::decode).transpose().map_err(bad_request)?;
+    let scheme = req.scheme.as_deref().unwrap_or(qs_crypto::SCHEME);
+    Ok(Json(qs_core::create_watch_only(&req.name, &public, scheme, kem.as_deref()).map_err(core_err)?))
+}
+
+async fn import_qskey(Json(req): Json<ImportQsKeyReq>) -> Result<Json<WalletInfo>, ApiError> {
+    let password = req.password.as_deref().unwrap_or(&req.qskey_password);
+    Ok(Json(qs_core::qskey::import(&req.name, &req.qskey, &req.qskey_password, password).map_err(core_err)?))
+}
+
+async fn export_qskey(
+    Path(name): Path<String>,
+    Json(req): Json<ExportQsKeyReq>,
+) -> Result<Json<QsKeyFile>, ApiError> {
+    let export_password = req.export_password.as_deref().unwrap_or(&req.password);
+    Ok(Json(qs_core::qskey::export(&name, &req.password, export_password).map_err(core_err)?))
+}
+
+async fn get_address(Path(name): Path<String>) -> Result<Json<AddressRes>, ApiError> {
+    Ok(Json(AddressRes { address: qs_core::address(&name).map_err(core_err)? }))
+}
+
+/// Public key in several encodings; `?format=raw|pem` returns just that body.
+async fn get_public_key(
+    Path(name): Path<String>,
+    Query(q): Query<PublicKeyQuery>,
+) -> Result<Response, ApiError> {
+    use base64::{engine::general_purpose::STANDARD as B64, Engine};
+    let (scheme, public) = qs_core::public_key_info(&name).map_err(core_err)?;
+    let pem = qs_crypto::spki_pem(&scheme, &public);
+    match q.format.as_deref() {
+        None | Some("json") => Ok(Json(PublicKeyRes {
+            address: qs_crypto::address_from_pubkey(&public),
+            hex: hex::encode(&public),
+            base64: B64.encode(&public),
+            oid: qs_crypto::scheme_oid(&scheme),
+            spki_der_base64: qs_crypto::spki_der(&scheme, &public).map(|d| B64.encode(d)),
+            pem,
+            scheme,
+        }).into_response()),
+        Some("raw") => Ok(([(header::CONTENT_TYPE, "application/octet-stream")], public).into_response()),
+        Some("pem") => {
+            let pem = pem.ok_or_else(|| bad_request(format!("no SPKI encoding for scheme `{scheme}`")))?;
+            Ok(([(header::CONTENT_TYPE, "application/x-pem-file")], pem).into_response())
+        }
+        Some(other) => Err(bad_request(format!("unknown format `{other}` (json, raw, pem)"))),
+    }
+}
+
+/// Signs at once, or for protected wallets queues the request and answers
+/// 202 with it; the signature then arrives through `/approvals`.
 async fn sign(
     Path(name): Path<String>,
     Json(req): Json<SignReq>,
-) -> Result<Json<SignRes>, (axum::http::StatusCode, String)> {
-    let ek: EncryptedKeyfile = read_json(wallet_path(&name)).map_err(not_found)?;
-    let secret = decrypt_secret(&ek, &req.password).ok_or_else(|| unauthorized("bad password"))?;
-    Ok(Json(SignRes { signed_hex: hex::encode(sign_message(&secret, req.message.as_bytes())) }))
+) -> Result<Response, ApiError> {
+    if qs_core::approval::requires_approval(&name).map_err(core_err)? {
+        let description = req.description.as_deref().unwrap_or("");
+        let pending = qs_core::approval::submit(&name, &req.password, &req.domain, req.message.as_bytes(), description)
+            .map_err(core_err)?;
+        approvals::publish(&pending);
+        return Ok((StatusCode::ACCEPTED, Json(pending)).into_response());
+    }
+    let signed = qs_core::sign(&name, &req.password, &req.domain, req.message.as_bytes()).map_err(core_err)?;
+    Ok(Json(SignRes { signed_hex: hex::encode(signed) }).into_response())
 }
 
 async fn verify(
     Path(name): Path<String>,
     Json(req): Json<VerifyReq>,
-) -> Result<Json<VerifyRes>, (axum::http::StatusCode, String)> {
-    let ek: EncryptedKeyfile = read_json(wallet_path(&name)).map_err(not_found)?;
-    let public = hex::decode(&ek.public_hex).map_err(bad_request)?;
+) -> Result<Json<VerifyRes>, ApiError> {
     let signed = hex::decode(&req.signed_hex).map_err(bad_request)?;
-    match verify_message(&public, &signed) {
-        Some(m) => Ok(Json(VerifyRes { ok: true, message: Some(String::from_utf8_lossy(&m).to_string()) })),
-        None => Ok(Json(VerifyRes { ok: false, message: None })),
+    let opened = qs_core::verify(&name, &req.domain, &signed).map_err(core_err)?;
+    let revoked = revoked(&qs_core::public_key(&name).map_err(core_err)?)?;
+    match opened.filter(|_| revoked.is_none()) {
+        Some(m) => Ok(Json(VerifyRes { ok: true, message: Some(String::from_utf8_lossy(&m).to_string()), revoked })),
+        None => Ok(Json(VerifyRes { ok: false, message: None, revoked })),
     }
 }
 
+async fn sign_typed(
+    Path(name): Path<String>,
+    Json(req): Json<SignTypedReq>,
+) -> Result<Json<SignTypedRes>, ApiError> {
+    let (hash, signed) = qs_core::sign_typed_data(&name, &req.password, &req.typed_data).map_err(core_err)?;
+    Ok(Json(SignTypedRes { hash_hex: hex::encode(hash), signed_hex: hex::encode(signed) }))
+}
+
+async fn verify_typed(
+    Path(name): Path<String>,
+    Json(req): Json<VerifyTypedReq>,
+) -> Result<Json<VerifyTypedRes>, ApiError> {
+    let hash = req.typed_data.signing_hash().map_err(bad_request)?;
+    let signed = hex::decode(&req.signed_hex).map_err(bad_request)?;
+    let ok = qs_core::verify_typed_data(&name, &req.typed_data, &signed).map_err(core_err)?;
+    let revoked = revoked(&qs_core::public_key(&name).map_err(core_err)?)?;
+    Ok(Json(VerifyTypedRes { ok: ok && revoked.is_none(), hash_hex: hex::encode(hash), revoked }))
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let root = ensure_wallet_dir()?;
-    let state = Arc::new(AppState { _root: root });
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("audit-verify") => return audit::verify_cli(&args[1..]),
+        Some("reset-lockout") => return throttle::reset_cli(&args[1..]),
+        _ => {}
+    }
+
+    ensure_wallet_dir()?;
+    approvals::spawn_expiry_sweeper();
+    if audit::unlock_from_env()? {
+        audit::spawn_checkpointer();
+    }
 
     let cors = CorsLayer::new()
         .allow_origin(HeaderValue::from_static("http://localhost:3000"))
@@ -108,145 +237,134 @@ async fn main() -> anyhow::Result<()> {
     let app = Router::new()
         .route("/healthz", get(healthz))
         .route("/readyz",  get(readyz))
-        .route("/wallets", post(new_wallet))
+        .route("/wallets", get(list_wallets).post(new_wallet))
+        .route("/wallets/watch", post(watch_wallet))
+        .route("/wallets/import/qskey", post(import_qskey))
+        .route("/wallets/:name/export/qskey", post(export_qskey))
         .route("/wallets/:name/address", get(get_address))
+        .route("/wallets/:name/public-key", get(get_public_key))
         .route("/wallets/:name/sign",    post(sign))
         .route("/wallets/:name/verify",  post(verify))
-        .with_state(state)
+        .route("/wallets/:name/sign-typed",   post(sign_typed))
+        .route("/wallets/:name/verify-typed", post(verify_typed))
+        .merge(verify::routes())
+        .merge(backup::routes())
+        .merge(registry::routes())
+        .merge(rotation::routes())
+        .merge(revocation::routes())
+        .merge(multisig::routes())
+        .merge(approvals::routes())
+        .merge(policy::routes())
+        .merge(audit::routes())
+        .merge(throttle::routes())
+        .merge(password::routes())
+        .merge(login::routes())
+        .merge(intent::routes())
+        .merge(solana::routes())
+        .merge(balances::routes())
+        .merge(legacy::routes())
+        // requests the limiter turns away never reach (or flood) the audit log
+        .layer(axum::middleware::from_fn(audit::record_request))
+        .layer(axum::middleware::from_fn(ratelimit::limit))
         .layer(cors);
 
     let addr: SocketAddr = ([127, 0, 0, 1], 8787).into();
     println!("qs-walletd listening on http://{addr}");
-    axum::serve(tokio::net::TcpListener::bind(addr).await?, app).await?;
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
     Ok(())
 }
 
-// error mappers
-fn internal<E: std::fmt::Display>(e: E) -> (axum::http::StatusCode, String) {
-    (axum::http::StatusCode::INTERNAL_SERVER_ERROR, format!("{e}"))
-}
-fn not_found<E: std::fmt::Display>(e: E) -> (axum::http::StatusCode, String) {
-    (axum::http::StatusCode::NOT_FOUND, format!("{e}"))
-}
-fn bad_request<E: std::fmt::Display>(e: E) -> (axum::http::StatusCode, String) {
-    (axum::http::StatusCode::BAD_REQUEST, format!("{e}"))
-}
-fn unauthorized(msg: &str) -> (axum::http::StatusCode, String) {
-    (axum::http::StatusCode::UNAUTHORIZED, msg.to_string())
+/// Error replies: the message as plain text, or a JSON object (sent as
+/// `application/json`) for errors clients act on.
+pub(crate) enum ErrorBody { Text(String), Json(serde_json::Value) }
+pub(crate) type ApiError = (StatusCode, ErrorBody);
+
+impl IntoResponse for ErrorBody {
+    fn into_response(self) -> Response {
+        match self {
+            ErrorBody::Text(message) => message.into_response(),
+            ErrorBody::Json(body) => Json(body).into_response(),
+        }
+    }
 }
-use axum::{
-    extract::{Path, State},
-    routing::{get, post},
-    Json, Router,
-};
-use serde::{Deserialize, Serialize};
-use std::{net::SocketAddr, sync::Arc, path::PathBuf};
-use tower_http::cors::{Any, CorsLayer};
-use qs_crypto::{
-    DilithiumKeypair, EncryptedKeyfile, generate_dilithium3, address_from_pubkey,
-    encrypt_secret, decrypt_secret, sign_message, verify_message,
-};
-use qs_utils::{ensure_wallet_dir, wallet_dir, write_json, read_json,};
-use anyhow::{Result, anyhow};
 
-#[derive(Clone)]
-struct AppState {
-    _root: PathBuf,
+impl From<String> for ErrorBody {
+    fn from(message: String) -> Self { ErrorBody::Text(message) }
 }
 
-#[derive(Deserialize)]
-struct NewWalletReq { name: String, password: String }
-#[derive(Serialize)]
-struct NewWalletRes { name: String, address: String }
-
-#[derive(Serialize)]
-struct AddressRes { address: String }
-
-#[derive(Deserialize)]
-struct SignReq { password: String, message: String }
-#[derive(Serialize)]
-struct SignRes { signed_hex: String }
-
-#[derive(Deserialize)]
-struct VerifyReq { signed_hex: String }
-#[derive(Serialize)]
-struct VerifyRes { ok: bool, message: Option<String> }
-
-fn wallet_path(name: &str) -> PathBuf {
-    let mut p = wallet_dir(); p.push(format!("{name}.json")); p
+// error mappers
+fn internal<E: std::fmt::Display>(e: E) -> ApiError {
+    (StatusCode::INTERNAL_SERVER_ERROR, format!("{e}").into())
 }
-
-async fn new_wallet(State(st): State<Arc<AppState>>, Json(req): Json<NewWalletReq>) -> Result<Json<NewWalletRes>, (axum::http::StatusCode, String)> {
-    ensure_wallet_dir().map_err(internal)?;
-    let kp: DilithiumKeypair = generate_dilithium3();
-    let ek: EncryptedKeyfile = encrypt_secret(&kp.public, &kp.secret, &req.password);
-    let path = wallet_path(&req.name); 
-    write_json(path, &ek).map_err(internal)?;
-    let addr = address_from_pubkey(&kp.public);
-    Ok(Json(NewWalletRes { name: req.name, address: addr }))
+fn not_found<E: std::fmt::Display>(e: E) -> ApiError {
+    (StatusCode::NOT_FOUND, format!("{e}").into())
 }
-
-async fn get_address(Path(name): Path<String>) -> Result<Json<AddressRes>, (axum::http::StatusCode, String)> {
-    let ek: EncryptedKeyfile = read_json(wallet_path(&name)).map_err(not_found)?;
-    // stored as hex of pubkey
-    let pub_bytes = hex::decode(&ek.public_hex).map_err(bad_request)?;
-    let addr = address_from_pubkey(&pub_bytes);
-    Ok(Json(AddressRes { address: addr }))
+fn bad_request<E: std::fmt::Display>(e: E) -> ApiError {
+    (StatusCode::BAD_REQUEST, format!("{e}").into())
 }
-
-async fn sign(Path(name): Path<String>, Json(req): Json<SignReq>) -> Result<Json<SignRes>, (axum::http::StatusCode, String)> {
-    let ek: EncryptedKeyfile = read_json(wallet_path(&name)).map_err(not_found)?;
-    let secret = decrypt_secret(&ek, &req.password).ok_or_else(|| unauthorized("bad password"))?;
-    let sig = sign_message(&secret, req.message.as_bytes());
-    Ok(Json(SignRes { signed_hex: hex::encode(sig) }))
+fn unauthorized(msg: &str) -> ApiError {
+    (StatusCode::UNAUTHORIZED, msg.to_string().into())
 }
-
-async fn verify(Path(name): Path<String>, Json(req): Json<VerifyReq>) -> Result<Json<VerifyRes>, (axum::http::StatusCode, String)> {
-    let ek: EncryptedKeyfile = read_json(wallet_path(&name)).map_err(not_found)?;
-    let public = hex::decode(&ek.public_hex).map_err(bad_request)?;
-    let signed = hex::decode(&req.signed_hex).map_err(bad_request)?;
-    match verify_message(&public, &signed) {
-        Some(m) => Ok(Json(VerifyRes { ok: true, message: Some(String::from_utf8_lossy(&m).to_string()) })),
-        None    => Ok(Json(VerifyRes { ok: false, message: None })),
+fn conflict<E: std::fmt::Display>(e: E) -> ApiError {
+    (StatusCode::CONFLICT, format!("{e}").into())
+}
+fn forbidden<E: std::fmt::Display>(e: E) -> ApiError {
+    (StatusCode::FORBIDDEN, format!("{e}").into())
+}
+fn core_err(e: CoreError) -> ApiError {
+    match e {
+        CoreError::NotFound(_) | CoreError::UnknownAddress(_) | CoreError::UnknownRequest(_) => not_found(e),
+        CoreError::Exists(_)   => conflict(e),
+        CoreError::WatchOnly(_) | CoreError::ApprovalRequired(_) => forbidden(e),
+        CoreError::PolicyDenied(d) => {
+            let body = serde_json::json!({ "error": "policy_denied", "wallet": d.wallet, "rule": d.rule, "reason": d.reason });
+            (StatusCode::FORBIDDEN, ErrorBody::Json(body))
+        }
+        CoreError::WeakPassword(w) => {
+            let body = serde_json::json!({ "error": "weak_password", "message": w.to_string(), "problems": w.problems });
+            (StatusCode::BAD_REQUEST, ErrorBody::Json(body))
+        }
+        CoreError::BadPassword => unauthorized("bad password"),
+        // ratelimit turns `retry_after_secs` into a Retry-After header
+        CoreError::Throttled { wallet, retry_after_secs } => {
+            let body = serde_json::json!({ "error": "throttled", "wallet": wallet, "retry_after_secs": retry_after_secs });
+            (StatusCode::TOO_MANY_REQUESTS, ErrorBody::Json(body))
+        }
+        CoreError::LockedOut(_) => (StatusCode::LOCKED, e.to_string().into()),
+        CoreError::LoginRejected(_) | CoreError::IntentRejected(_) => (StatusCode::UNAUTHORIZED, e.to_string().into()),
+        CoreError::Tampered(_) => internal(e),
+        CoreError::Invalid(_)  => bad_request(e),
+        CoreError::Io(_)       => internal(e),
     }
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let root = ensure_wallet_dir()?;
-    let state = Arc::new(AppState { _root: root });
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+    use qs_core::password::WeakPassword;
+
+    async fn sent(e: CoreError) -> (StatusCode, String, serde_json::Value) {
+        let res = core_err(e).into_response();
+        let content_type = res.headers()[header::CONTENT_TYPE].to_str().unwrap().to_string();
+        let status = res.status();
+        let body = to_bytes(res.into_body(), 4096).await.unwrap();
+        (status, content_type, serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null))
+    }
 
-    let cors = CorsLayer::new()
-        .allow_methods(Any)
-        .allow_headers(Any)
-        .allow_origin(Any); // local apps; if you want stricter, set a specific origin list
+    #[tokio::test]
+    async fn structured_errors_go_out_as_json() {
+        let (status, content_type, body) = sent(CoreError::Throttled { wallet: "w".into(), retry_after_secs: 30 }).await;
+        assert_eq!((status, content_type.as_str()), (StatusCode::TOO_MANY_REQUESTS, "application/json"));
+        assert_eq!(body["retry_after_secs"], 30);
 
-    let app = Router::new()
-        .route("/wallets", post(new_wallet))
-        .route("/wallets/:name/address", get(get_address))
-        .route("/wallets/:name/sign", post(sign))
-        .route("/wallets/:name/verify", post(verify))
-        .route("/healthz", get(|| async { "ok" }))
-        .with_state(state)
-        .layer(cors);
-
-    // Bind to localhost only
-    let addr = SocketAddr::from(([127, 0, 0, 1], 8787));
-    println!("qs-walletd listening on http://{addr}");
-    axum::serve(tokio::net::TcpListener::bind(addr).await?, app).await?;
-    Ok(())
-}
+        let (status, content_type, body) = sent(CoreError::WeakPassword(WeakPassword { problems: vec!["too short".into()] })).await;
+        assert_eq!((status, content_type.as_str()), (StatusCode::BAD_REQUEST, "application/json"));
+        assert_eq!(body["problems"], serde_json::json!(["too short"]));
 
-// --- small helpers to map errors to http ---
-fn internal<E: std::fmt::Display>(e: E) -> (axum::http::StatusCode, String) {
-    (axum::http::StatusCode::INTERNAL_SERVER_ERROR, format!("{e}"))
-}
-fn not_found<E: std::fmt::Display>(e: E) -> (axum::http::StatusCode, String) {
-    (axum::http::StatusCode::NOT_FOUND, format!("{e}"))
-}
-fn bad_request<E: std::fmt::Display>(e: E) -> (axum::http::StatusCode, String) {
-    (axum::http::StatusCode::BAD_REQUEST, format!("{e}"))
-}
-fn unauthorized(msg: &str) -> (axum::http::StatusCode, String) {
-    (axum::http::StatusCode::UNAUTHORIZED, msg.to_string())
+        let (status, content_type, _) = sent(CoreError::BadPassword).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(content_type.starts_with("text/plain"), "{content_type}");
+    }
 }
@@ -0,0 +1,79 @@
+//! M-of-N multisig routes under `/multisig`.
+use axum::{extract::Path, routing::{get, post}, Json, Router};
+use serde::Deserialize;
+
+use qs_core::multisig::{self, MultisigStatus, StoredPolicy};
+use qs_crypto::{Domain, MultisigBundle, MultisigPolicy};
+
+use crate::{ApiError, bad_request, core_err};
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/multisig/policies",                     get(list).post(create))
+        .route("/multisig/policies/:address",            get(lookup))
+        .route("/multisig/policies/:address/signatures", post(add_signature))
+        .route("/multisig/policies/:address/sign",       post(sign))
+        .route("/multisig/policies/:address/status",     post(status))
+        .route("/multisig/check",                        post(check))
+}
+
+#[derive(Deserialize)]
+struct CreateReq {
+    threshold: u16,
+    /// Hex public keys, or `QS…` addresses of keys the daemon already knows.
+    members: Vec<String>,
+    label: Option<String>,
+}
+#[derive(Deserialize)]
+struct AddSignatureReq {
+    public_key_hex: Option<String>,
+    address: Option<String>,
+    domain: Domain,
+    message: String,
+    signed_hex: String,
+}
+#[derive(Deserialize)] struct SignReq   { wallet: String, password: String, domain: Domain, message: String }
+#[derive(Deserialize)] struct StatusReq { domain: Domain, message: String }
+#[derive(Deserialize)] struct CheckReq  { policy: MultisigPolicy, bundle: MultisigBundle }
+
+async fn list() -> Result<Json<Vec<StoredPolicy>>, ApiError> {
+    Ok(Json(multisig::list_policies().map_err(core_err)?))
+}
+
+async fn create(Json(req): Json<CreateReq>) -> Result<Json<StoredPolicy>, ApiError> {
+    let index = qs_core::address_index().map_err(core_err)?;
+    let members = req.members.iter()
+        .map(|m| if m.starts_with("QS") {
+            qs_core::resolve_signer(None, Some(m), &index)
+        } else {
+            qs_core::resolve_signer(Some(m), None, &index)
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(core_err)?;
+    Ok(Json(multisig::create_policy(req.threshold, &members, req.label).map_err(core_err)?))
+}
+
+async fn lookup(Path(address): Path<String>) -> Result<Json<StoredPolicy>, ApiError> {
+    Ok(Json(multisig::get_policy(&address).map_err(core_err)?))
+}
+
+async fn add_signature(Path(address): Path<String>, Json(req): Json<AddSignatureReq>) -> Result<Json<MultisigStatus>, ApiError> {
+    let index = qs_core::address_index().map_err(core_err)?;
+    let public = qs_core::resolve_signer(req.public_key_hex.as_deref(), req.address.as_deref(), &index).map_err(core_err)?;
+    let signed = hex::decode(&req.signed_hex).map_err(bad_request)?;
+    Ok(Json(multisig::add_signature(&address, &req.domain, req.message.as_bytes(), &public, &signed).map_err(core_err)?))
+}
+
+async fn sign(Path(address): Path<String>, Json(req): Json<SignReq>) -> Result<Json<MultisigStatus>, ApiError> {
+    let status = multisig::sign_with_wallet(&address, &req.wallet, &req.password, &req.domain, req.message.as_bytes());
+    Ok(Json(status.map_err(core_err)?))
+}
+
+async fn status(Path(address): Path<String>, Json(req): Json<StatusReq>) -> Result<Json<MultisigStatus>, ApiError> {
+    Ok(Json(multisig::status(&address, &req.domain, req.message.as_bytes()).map_err(core_err)?))
+}
+
+/// Stateless: checks a bundle against the policy sent with it.
+async fn check(Json(req): Json<CheckReq>) -> Result<Json<MultisigStatus>, ApiError> {
+    Ok(Json(multisig::check_bundle(&req.policy, req.bundle).map_err(core_err)?))
+}
@@ -0,0 +1,34 @@
+//! Password rules and password changes.
+use axum::{extract::Path, routing::{get, post}, Json, Router};
+use serde::{Deserialize, Serialize};
+
+use qs_core::password::{self, PasswordPolicy};
+
+use crate::{ApiError, core_err};
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/password-policy",       get(policy))
+        .route("/password-policy/check", post(check))
+        .route("/wallets/:name/password", post(change))
+}
+
+#[derive(Deserialize)] struct CheckReq  { #[serde(default)] name: String, password: String }
+#[derive(Serialize)]   struct CheckRes  { ok: bool, entropy_bits: f64, problems: Vec<String> }
+#[derive(Deserialize)] struct ChangeReq { old_password: String, new_password: String }
+#[derive(Serialize)]   struct ChangeRes { changed: bool }
+
+async fn policy() -> Json<PasswordPolicy> {
+    Json(PasswordPolicy::from_env())
+}
+
+/// Lets a UI show what is wrong with a password before using it.
+async fn check(Json(req): Json<CheckReq>) -> Json<CheckRes> {
+    let problems = PasswordPolicy::from_env().check(&req.name, &req.password).err().map(|w| w.problems).unwrap_or_default();
+    Json(CheckRes { ok: problems.is_empty(), entropy_bits: password::estimate_entropy(&req.password), problems })
+}
+
+async fn change(Path(name): Path<String>, Json(req): Json<ChangeReq>) -> Result<Json<ChangeRes>, ApiError> {
+    qs_core::change_password(&name, &req.old_password, &req.new_password).map_err(core_err)?;
+    Ok(Json(ChangeRes { changed: true }))
+}
@@ -0,0 +1,24 @@
+//! Per-wallet signing policy routes.
+use axum::{extract::Path, routing::get, Json, Router};
+use serde::Deserialize;
+
+use qs_core::policy::{self, WalletPolicy};
+
+use crate::{ApiError, core_err};
+
+pub fn routes() -> Router {
+    Router::new().route("/wallets/:name/policy", get(lookup).put(replace))
+}
+
+#[derive(Deserialize)]
+struct SetPolicyReq { password: String, policy: Option<WalletPolicy> }
+
+/// `null` when the wallet has no policy.
+async fn lookup(Path(name): Path<String>) -> Result<Json<Option<WalletPolicy>>, ApiError> {
+    Ok(Json(policy::get(&name).map_err(core_err)?))
+}
+
+async fn replace(Path(name): Path<String>, Json(req): Json<SetPolicyReq>) -> Result<Json<Option<WalletPolicy>>, ApiError> {
+    policy::set(&name, &req.password, req.policy.clone()).map_err(core_err)?;
+    Ok(Json(req.policy))
+}
@@ -0,0 +1,212 @@
+//! Request rate limiting per wallet and per peer, and Retry-After for 429s
+//! from either this limiter or wallet password throttling.
+//!
+//! A request that names a wallet (`/wallets/:name/...`) takes a token from
+//! that wallet's bucket, whoever sends it; every request also takes one from
+//! its peer address's bucket. Nothing the caller sends (headers, Origin)
+//! picks a bucket, so new ones cannot be had for the asking. The daemon only
+//! listens on 127.0.0.1, so local callers share that one peer bucket.
+//! At most `MAX_TRACKED` buckets are kept, dropping the least recently used.
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Request},
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
+
+use crate::audit::wallet_in;
+
+/// Sustained requests per second per bucket, and how many may come at once.
+const RATE_PER_SEC: f64 = 20.0;
+const BURST: f64 = 60.0;
+const MAX_TRACKED: usize = 10_000;
+const MAX_ERROR_BODY: usize = 4096;
+const MAX_KEY_LEN: usize = 128;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+enum Key { Wallet(String), Peer(Option<IpAddr>) }
+
+struct Bucket { tokens: f64, updated: Instant }
+
+fn refill(b: &mut Bucket, now: Instant) {
+    b.tokens = (b.tokens + now.duration_since(b.updated).as_secs_f64() * RATE_PER_SEC).min(BURST);
+    b.updated = now;
+}
+
+struct Buckets { map: HashMap<Key, Bucket>, cap: usize }
+
+fn buckets() -> &'static Mutex<Buckets> {
+    static BUCKETS: OnceLock<Mutex<Buckets>> = OnceLock::new();
+    BUCKETS.get_or_init(|| Mutex::new(Buckets::new(MAX_TRACKED)))
+}
+
+impl Buckets {
+    fn new(cap: usize) -> Self { Buckets { map: HashMap::new(), cap } }
+
+    /// Makes room for `incoming` new buckets, dropping the least recently
+    /// used (a bucket's `updated` is its last use), full or not. Evicts a
+    /// tenth of the cap at a time so the sort is rare.
+    fn make_room(&mut self, incoming: usize) {
+        if self.map.len() + incoming <= self.cap {
+            return;
+        }
+        let keep = self.cap.saturating_sub(incoming + self.cap / 10);
+        let mut by_use: Vec<(Instant, Key)> = self.map.iter().map(|(k, b)| (b.updated, k.clone())).collect();
+        by_use.sort_unstable_by_key(|(updated, _)| *updated);
+        for (_, key) in by_use.into_iter().take(self.map.len().saturating_sub(keep)) {
+            self.map.remove(&key);
+        }
+    }
+
+    /// Takes a token from every bucket in `keys`, or from none of them; on
+    /// refusal, the seconds until all have one.
+    fn take(&mut self, keys: &[Key], now: Instant) -> Result<(), u64> {
+        self.make_room(keys.iter().filter(|k| !self.map.contains_key(k)).count());
+        let mut wait = 0.0f64;
+        for key in keys {
+            let b = self.map.entry(key.clone()).or_insert(Bucket { tokens: BURST, updated: now });
+            refill(b, now);
+            if b.tokens < 1.0 {
+                wait = wait.max((1.0 - b.tokens) / RATE_PER_SEC);
+            }
+        }
+        if wait > 0.0 {
+            return Err(wait.ceil() as u64);
+        }
+        for key in keys {
+            if let Some(b) = self.map.get_mut(key) {
+                b.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The buckets a request draws from.
+fn keys(req: &Request) -> Vec<Key> {
+    let peer = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|c| c.0.ip());
+    let mut keys = vec![Key::Peer(peer)];
+    if let Some(wallet) = wallet_in(req.uri().path()) {
+        keys.push(Key::Wallet(wallet.chars().take(MAX_KEY_LEN).collect()));
+    }
+    keys
+}
+
+fn retry_after(secs: u64) -> HeaderValue {
+    HeaderValue::from(secs.max(1))
+}
+
+pub async fn limit(req: Request, next: Next) -> Response {
+    let taken = buckets().lock().unwrap_or_else(|e| e.into_inner()).take(&keys(&req), Instant::now());
+    if let Err(wait) = taken {
+        let mut res = (StatusCode::TOO_MANY_REQUESTS, "rate limit exceeded").into_response();
+        res.headers_mut().insert(header::RETRY_AFTER, retry_after(wait));
+        return res;
+    }
+    let res = next.run(req).await;
+    if res.status() != StatusCode::TOO_MANY_REQUESTS || res.headers().contains_key(header::RETRY_AFTER) {
+        return res;
+    }
+    // a throttled unlock: the wait is in the JSON body (see `core_err`)
+    let (mut parts, body) = res.into_parts();
+    let bytes = to_bytes(body, MAX_ERROR_BODY).await.unwrap_or_default();
+    let wait = serde_json::from_slice::<serde_json::Value>(&bytes).ok()
+        .and_then(|v| v.get("retry_after_secs")?.as_u64());
+    if let Some(wait) = wait {
+        parts.headers.insert(header::RETRY_AFTER, retry_after(wait));
+    }
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn request(path: &str, client: Option<&str>) -> Request {
+        let mut builder = Request::builder().uri(path).header(header::ORIGIN, "http://localhost:3000");
+        if let Some(c) = client {
+            builder = builder.header("x-qs-client", c);
+        }
+        let mut req = builder.body(Body::empty()).unwrap();
+        req.extensions_mut().insert(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 40000))));
+        req
+    }
+
+    fn peer(ip: [u8; 4]) -> Key { Key::Peer(Some(IpAddr::from(ip))) }
+
+    #[test]
+    fn requests_draw_from_their_wallet_and_peer() {
+        assert_eq!(keys(&request("/audit", None)), vec![peer([127, 0, 0, 1])]);
+        assert_eq!(keys(&request("/wallets/alice/sign", Some("app"))), vec![peer([127, 0, 0, 1]), Key::Wallet("alice".into())]);
+        let unknown = Request::builder().uri("/healthz").body(Body::empty()).unwrap();
+        assert_eq!(keys(&unknown), vec![Key::Peer(None)]);
+    }
+
+    #[test]
+    fn rotating_client_headers_gets_no_fresh_bucket() {
+        let mut buckets = Buckets::new(MAX_TRACKED);
+        let now = Instant::now();
+        for i in 0..BURST as usize {
+            buckets.take(&keys(&request("/audit", Some(&format!("client-{i}")))), now).unwrap();
+        }
+        assert!(buckets.take(&keys(&request("/audit", Some("client-new"))), now).is_err());
+        assert_eq!(buckets.map.len(), 1);
+    }
+
+    #[test]
+    fn tracked_buckets_are_capped_least_recently_used_first() {
+        let mut buckets = Buckets::new(100);
+        let start = Instant::now();
+        let busy = Key::Wallet("busy".into());
+        for i in 0..1000u64 {
+            let now = start + Duration::from_millis(i);
+            // drained (so never full), and used more recently than most
+            let _ = buckets.take(std::slice::from_ref(&busy), now);
+            buckets.take(&[Key::Wallet(format!("w{i}"))], now).unwrap();
+            assert!(buckets.map.len() <= 100);
+        }
+        assert!(buckets.map.contains_key(&busy));
+        assert!(buckets.map.contains_key(&Key::Wallet("w999".into())));
+        assert!(!buckets.map.contains_key(&Key::Wallet("w0".into())));
+        // the busy wallet kept its drained bucket through the evictions
+        assert!(buckets.map[&busy].tokens < 2.0);
+    }
+
+    #[test]
+    fn one_wallet_cannot_starve_another() {
+        let mut buckets = Buckets::new(MAX_TRACKED);
+        let now = Instant::now();
+        let (a, b) = (Key::Wallet("rl-a".into()), Key::Wallet("rl-b".into()));
+        for i in 0..BURST as usize {
+            buckets.take(&[peer([10, 0, 0, i as u8]), a.clone()], now).unwrap();
+        }
+        // a fresh peer still cannot reach the exhausted wallet
+        let wait = buckets.take(&[peer([10, 0, 1, 0]), a.clone()], now).unwrap_err();
+        assert!(wait >= 1);
+        buckets.take(&[peer([10, 0, 1, 0]), b.clone()], now).unwrap();
+        buckets.take(std::slice::from_ref(&a), now + Duration::from_secs(1)).unwrap();
+    }
+
+    #[test]
+    fn refusals_take_nothing() {
+        let mut buckets = Buckets::new(MAX_TRACKED);
+        let now = Instant::now();
+        let (client, wallet) = (peer([10, 0, 0, 1]), Key::Wallet("rl-busy".into()));
+        for _ in 0..BURST as usize {
+            buckets.take(std::slice::from_ref(&wallet), now).unwrap();
+        }
+        assert!(buckets.take(&[client.clone(), wallet], now).is_err());
+        for _ in 0..BURST as usize {
+            buckets.take(std::slice::from_ref(&client), now).unwrap();
+        }
+        assert!(buckets.take(&[client], now).is_err());
+    }
+}
@@ -0,0 +1,43 @@
+//! Address book routes: `/registry` and `/registry/:address`.
+use axum::{extract::Path, http::StatusCode, routing::get, Json, Router};
+use serde::Deserialize;
+
+use qs_core::registry::{self, RegistryEntry};
+use qs_crypto::SCHEME;
+
+use crate::{ApiError, bad_request, core_err};
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/registry",          get(list).post(import))
+        .route("/registry/:address", get(lookup).delete(remove))
+}
+
+#[derive(Deserialize)]
+struct ImportReq { public_key_hex: String, scheme: Option<String>, label: Option<String> }
+
+async fn list() -> Result<Json<Vec<RegistryEntry>>, ApiError> {
+    Ok(Json(registry::list().map_err(core_err)?))
+}
+
+async fn import(Json(req): Json<ImportReq>) -> Result<Json<RegistryEntry>, ApiError> {
+    let public = hex::decode(&req.public_key_hex).map_err(bad_request)?;
+    let scheme = req.scheme.as_deref().unwrap_or(SCHEME);
+    Ok(Json(registry::import(&public, scheme, req.label).map_err(core_err)?))
+}
+
+async fn lookup(Path(address): Path<String>) -> Result<Json<RegistryEntry>, ApiError> {
+    Ok(Json(registry::get(&address).map_err(core_err)?))
+}
+
+async fn remove(Path(address): Path<String>) -> Result<StatusCode, ApiError> {
+    registry::remove(&address).map_err(core_err)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remembers signers that just verified; a failure here never fails the verify call.
+pub fn record_seen<'a>(keys: impl IntoIterator<Item = &'a [u8]>) {
+    if let Err(e) = registry::observe(keys) {
+        eprintln!("registry: could not record first-seen keys: {e}");
+    }
+}
@@ -0,0 +1,84 @@
+//! Revocation routes, plus the check every verify endpoint runs against the store.
+use axum::{extract::Path, http::StatusCode, routing::{get, post}, Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use qs_core::revocation;
+use qs_crypto::{RecoveryDesignation, Revocation};
+
+use crate::{ApiError, bad_request, core_err, not_found};
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/revocations",                get(list).post(submit))
+        .route("/revocations/:address",       get(lookup))
+        .route("/recovery-keys",              post(submit_designation))
+        .route("/recovery-keys/:address",     get(lookup_designation))
+        .route("/wallets/:name/revoke",       post(revoke_wallet))
+        .route("/wallets/:name/recovery-key", post(designate_wallet))
+}
+
+/// Why a verify endpoint reported `ok: false` for an otherwise valid signature.
+#[derive(Serialize, Clone)]
+pub struct RevokedInfo { revoked_at: u64, reason: String }
+
+impl From<&Revocation> for RevokedInfo {
+    fn from(r: &Revocation) -> Self { RevokedInfo { revoked_at: r.revoked_at, reason: r.reason.clone() } }
+}
+
+impl std::fmt::Display for RevokedInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "key revoked at {}: {}", self.revoked_at, self.reason)
+    }
+}
+
+/// Signatures carry no timestamp, so once a key is revoked none of its
+/// signatures verify any more.
+pub fn revoked(public: &[u8]) -> Result<Option<RevokedInfo>, ApiError> {
+    Ok(revocation::status(public).map_err(core_err)?.as_ref().map(RevokedInfo::from))
+}
+
+/// Snapshot for checking a whole batch against one read of the store.
+pub fn revoked_all() -> Result<BTreeMap<String, Revocation>, ApiError> {
+    revocation::revoked().map_err(core_err)
+}
+
+#[derive(Deserialize)]
+struct RevokeReq { password: String, reason: String, target_public_key_hex: Option<String> }
+#[derive(Deserialize)]
+struct DesignateReq { password: String, recovery_public_key_hex: String }
+
+async fn list() -> Result<Json<Vec<Revocation>>, ApiError> {
+    Ok(Json(revocation::list().map_err(core_err)?))
+}
+
+async fn submit(Json(r): Json<Revocation>) -> Result<Json<Revocation>, ApiError> {
+    Ok(Json(revocation::add_revocation(r).map_err(core_err)?))
+}
+
+async fn lookup(Path(address): Path<String>) -> Result<Json<Revocation>, ApiError> {
+    revocation::revoked().map_err(core_err)?.remove(&address)
+        .map(Json)
+        .ok_or_else(|| not_found(format!("`{address}` is not revoked")))
+}
+
+async fn submit_designation(Json(d): Json<RecoveryDesignation>) -> Result<StatusCode, ApiError> {
+    revocation::add_designation(d).map_err(core_err)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn lookup_designation(Path(address): Path<String>) -> Result<Json<RecoveryDesignation>, ApiError> {
+    revocation::recovery_key(&address).map_err(core_err)?
+        .map(Json)
+        .ok_or_else(|| not_found(format!("no recovery key designated for `{address}`")))
+}
+
+async fn revoke_wallet(Path(name): Path<String>, Json(req): Json<RevokeReq>) -> Result<Json<Revocation>, ApiError> {
+    let target = req.target_public_key_hex.as_deref().map(hex::decode).transpose().map_err(bad_request)?;
+    Ok(Json(revocation::revoke_with_wallet(&name, &req.password, target.as_deref(), &req.reason).map_err(core_err)?))
+}
+
+async fn designate_wallet(Path(name): Path<String>, Json(req): Json<DesignateReq>) -> Result<Json<RecoveryDesignation>, ApiError> {
+    let recovery = hex::decode(&req.recovery_public_key_hex).map_err(bad_request)?;
+    Ok(Json(revocation::designate_wallet_recovery(&name, &req.password, &recovery).map_err(core_err)?))
+}
@@ -0,0 +1,47 @@
+//! Key rotation routes: rotate a wallet, read its certificate chain, and
+//! verify a chain someone else presents.
+use axum::{extract::Path, routing::{get, post}, Json, Router};
+use serde::{Deserialize, Serialize};
+
+use qs_core::rotation;
+use qs_crypto::{address_from_pubkey, RotationCert};
+
+use crate::{ApiError, bad_request, core_err};
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/wallets/:name/rotate",    post(rotate))
+        .route("/wallets/:name/rotations", get(chain))
+        .route("/rotations/verify",        post(verify_chain))
+}
+
+#[derive(Deserialize)]
+struct RotateReq { password: String, reason: Option<String> }
+#[derive(Serialize)]
+struct RotateRes { name: String, address: String, certificate: RotationCert }
+
+#[derive(Deserialize)]
+struct VerifyChainReq { certificates: Vec<RotationCert>, from_public_key_hex: Option<String> }
+#[derive(Serialize)]
+struct VerifyChainRes { ok: bool, current_public_key_hex: String, current_address: String }
+
+async fn rotate(Path(name): Path<String>, Json(req): Json<RotateReq>) -> Result<Json<RotateRes>, ApiError> {
+    let (info, certificate) = rotation::rotate(&name, &req.password, req.reason).map_err(core_err)?;
+    Ok(Json(RotateRes { name: info.name, address: info.address, certificate }))
+}
+
+async fn chain(Path(name): Path<String>) -> Result<Json<Vec<RotationCert>>, ApiError> {
+    // 404 for unknown wallets rather than an empty chain
+    qs_core::read_wallet(&name).map_err(core_err)?;
+    Ok(Json(rotation::chain(&name).map_err(core_err)?))
+}
+
+async fn verify_chain(Json(req): Json<VerifyChainReq>) -> Result<Json<VerifyChainRes>, ApiError> {
+    let start = req.from_public_key_hex.as_deref().map(hex::decode).transpose().map_err(bad_request)?;
+    let current = rotation::follow(&req.certificates, start.as_deref()).map_err(core_err)?;
+    Ok(Json(VerifyChainRes {
+        ok: true,
+        current_address: address_from_pubkey(&current),
+        current_public_key_hex: hex::encode(current),
+    }))
+}
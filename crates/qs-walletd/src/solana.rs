@@ -0,0 +1,34 @@
+//! Solana addresses of Ed25519 wallets and signed SPL token transfers.
+//! The daemon never talks to a Solana node; callers bring a recent
+//! blockhash and submit the returned transaction themselves.
+use axum::{extract::Path, routing::{get, post}, Json, Router};
+use serde::{Deserialize, Serialize};
+
+use qs_core::solana::{self, SignedTransfer};
+use qs_crypto::solana::{Pubkey, SplTransfer};
+
+use crate::{ApiError, core_err};
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/wallets/:name/solana/address",      get(address))
+        .route("/wallets/:name/solana/spl-transfer", post(spl_transfer))
+}
+
+#[derive(Serialize)]
+struct AddressRes { address: Pubkey }
+#[derive(Deserialize)]
+struct TransferReq {
+    password: String,
+    recent_blockhash: String,
+    #[serde(flatten)]
+    transfer: SplTransfer,
+}
+
+async fn address(Path(name): Path<String>) -> Result<Json<AddressRes>, ApiError> {
+    Ok(Json(AddressRes { address: solana::address(&name).map_err(core_err)? }))
+}
+
+async fn spl_transfer(Path(name): Path<String>, Json(req): Json<TransferReq>) -> Result<Json<SignedTransfer>, ApiError> {
+    Ok(Json(solana::sign_spl_transfer(&name, &req.password, &req.transfer, &req.recent_blockhash).map_err(core_err)?))
+}
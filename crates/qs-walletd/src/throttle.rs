@@ -0,0 +1,30 @@
+//! Failed-unlock status and lockout settings, plus the operator's
+//! `qs-walletd reset-lockout NAME` command.
+use axum::{extract::Path, routing::get, Json, Router};
+use serde::Deserialize;
+
+use qs_core::throttle::{self, ThrottleStatus};
+
+use crate::{ApiError, core_err};
+
+pub fn routes() -> Router {
+    Router::new().route("/wallets/:name/lockout", get(status).put(set_lockout))
+}
+
+#[derive(Deserialize)]
+struct SetLockoutReq { password: String, lockout_after: Option<u32> }
+
+async fn status(Path(name): Path<String>) -> Result<Json<ThrottleStatus>, ApiError> {
+    Ok(Json(throttle::status(&name).map_err(core_err)?))
+}
+
+async fn set_lockout(Path(name): Path<String>, Json(req): Json<SetLockoutReq>) -> Result<Json<ThrottleStatus>, ApiError> {
+    Ok(Json(throttle::set_lockout(&name, &req.password, req.lockout_after).map_err(core_err)?))
+}
+
+pub fn reset_cli(args: &[String]) -> anyhow::Result<()> {
+    let [name] = args else { anyhow::bail!("usage: qs-walletd reset-lockout NAME") };
+    throttle::reset(name)?;
+    println!("cleared failed unlocks and lockout for wallet `{name}`");
+    Ok(())
+}
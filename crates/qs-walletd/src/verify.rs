@@ -0,0 +1,123 @@
+//! Verification that does not need a wallet's password or, per item, its keyfile.
+use axum::{routing::post, Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use qs_crypto::{BatchItem, Domain, SCHEME, address_from_pubkey, verify_batch, verify_domain};
+
+use crate::{ApiError, bad_request, core_err, internal, registry::record_seen, revocation::{revoked, revoked_all, RevokedInfo}};
+
+pub const MAX_BATCH: usize = 10_000;
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/verify",       post(verify))
+        .route("/verify/batch", post(batch))
+}
+
+#[derive(Deserialize)]
+struct VerifyReq {
+    public_key_hex: Option<String>,
+    address: Option<String>,
+    scheme: String,
+    domain: Domain,
+    signed_hex: String,
+    /// If given, the signed message must equal it.
+    message: Option<String>,
+}
+#[derive(Serialize)]
+struct VerifyRes { ok: bool, address: String, message: Option<String>, revoked: Option<RevokedInfo> }
+
+fn check_scheme(scheme: &str) -> Result<(), ApiError> {
+    if scheme == SCHEME { Ok(()) } else {
+        Err(bad_request(format!("unsupported scheme `{scheme}` (this daemon verifies `{SCHEME}`)")))
+    }
+}
+
+/// Verifies a signature from any key, local wallet or not.
+async fn verify(Json(req): Json<VerifyReq>) -> Result<Json<VerifyRes>, ApiError> {
+    check_scheme(&req.scheme)?;
+    // the index reads every wallet and the registry; only an address needs it
+    let index = if req.address.is_some() { qs_core::address_index().map_err(core_err)? } else { HashMap::new() };
+    let public = qs_core::resolve_signer(req.public_key_hex.as_deref(), req.address.as_deref(), &index).map_err(core_err)?;
+    let signed = hex::decode(&req.signed_hex).map_err(bad_request)?;
+    let opened = verify_domain(&public, &req.domain, &signed);
+    let revoked = revoked(&public)?;
+    let ok = revoked.is_none() && match (&opened, &req.message) {
+        (Some(m), Some(expected)) => m == expected.as_bytes(),
+        (Some(_), None) => true,
+        (None, _) => false,
+    };
+    if ok && req.public_key_hex.is_some() {
+        record_seen([public.as_slice()]);
+    }
+    let message = opened.filter(|_| ok).map(|m| String::from_utf8_lossy(&m).to_string());
+    Ok(Json(VerifyRes { ok, address: address_from_pubkey(&public), message, revoked }))
+}
+
+#[derive(Deserialize)]
+struct BatchReqItem {
+    public_key_hex: Option<String>,
+    address: Option<String>,
+    domain: Domain,
+    message: String,
+    signed_hex: String,
+}
+#[derive(Deserialize)] struct BatchReq     { items: Vec<BatchReqItem> }
+#[derive(Serialize)]   struct BatchResItem { ok: bool, error: Option<String> }
+#[derive(Serialize)]   struct BatchRes     { results: Vec<BatchResItem>, valid: usize }
+
+/// Items that cannot be decoded or resolved, or whose key is revoked, are
+/// reported individually and do not fail the batch.
+async fn batch(Json(req): Json<BatchReq>) -> Result<Json<BatchRes>, ApiError> {
+    if req.items.len() > MAX_BATCH {
+        return Err(bad_request(format!("batch too large ({} > {MAX_BATCH})", req.items.len())));
+    }
+    let index = if req.items.iter().any(|it| it.address.is_some()) {
+        qs_core::address_index().map_err(core_err)?
+    } else {
+        HashMap::new()
+    };
+    let revocations = revoked_all()?;
+
+    let mut errors: Vec<Option<String>> = Vec::with_capacity(req.items.len());
+    let mut items = Vec::new();
+    let mut slots = Vec::new();
+    let mut by_key = Vec::new();
+    for (i, it) in req.items.into_iter().enumerate() {
+        let given_key = it.public_key_hex.is_some();
+        let decoded = qs_core::resolve_signer(it.public_key_hex.as_deref(), it.address.as_deref(), &index)
+            .map_err(|e| e.to_string())
+            .and_then(|public| match revocations.get(&address_from_pubkey(&public)) {
+                Some(r) => Err(RevokedInfo::from(r).to_string()),
+                None => Ok(public),
+            })
+            .and_then(|public| Ok((public, hex::decode(&it.signed_hex).map_err(|e| e.to_string())?)));
+        match decoded {
+            Ok((public, signed)) => {
+                items.push(BatchItem { public, domain: it.domain, message: it.message.into_bytes(), signed });
+                slots.push(i);
+                by_key.push(given_key);
+                errors.push(None);
+            }
+            Err(e) => errors.push(Some(e)),
+        }
+    }
+
+    let (items, oks) = tokio::task::spawn_blocking(move || {
+        let oks = verify_batch(&items);
+        (items, oks)
+    }).await.map_err(internal)?;
+
+    let seen = items.iter().zip(&oks).zip(&by_key)
+        .filter(|((_, ok), given_key)| **ok && **given_key)
+        .map(|((item, _), _)| item.public.as_slice());
+    record_seen(seen);
+
+    let mut results: Vec<BatchResItem> = errors.into_iter().map(|error| BatchResItem { ok: false, error }).collect();
+    for (slot, ok) in slots.into_iter().zip(oks) {
+        results[slot].ok = ok;
+    }
+    let valid = results.iter().filter(|r| r.ok).count();
+    Ok(Json(BatchRes { results, valid }))
+}
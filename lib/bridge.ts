@@ -1,4 +1,10 @@
-export async function bridgeToNova(publicKey: string, amount: number) {
+import type { Amount } from "./qs-walletd-client";
+
+/** `amount` is a decimal string such as "1.5"; it is sent as given, never through a JS number. */
+export async function bridgeToNova(publicKey: string, amount: Amount) {
+  if (!/^(\d+(\.\d+)?|\.\d+)$/.test(amount)) {
+    throw new Error(`amount: \`${amount}\` is not a decimal number`);
+  }
   const res = await fetch("https://api.novatok.tech/bridge", {
     method: "POST",
     headers: { "Content-Type": "application/json" },
@@ -1,12 +1,245 @@
 
 
+import type { QsKeyFile } from "./qskey";
+
 export type NewWalletReq = { name: string; password: string };
 export type NewWalletRes = { name: string; address: string };
 export type AddressRes   = { address: string };
-export type SignReq      = { password: string; message: string };
+export type PublicKeyRes = {
+  scheme: string;
+  address: string;
+  hex: string;
+  base64: string;
+  oid?: string;
+  spki_der_base64?: string;
+  pem?: string;
+};
+export type WalletInfo   = { name: string; address: string; watch_only: boolean };
+export type WatchReq     = {
+  name: string;
+  public_key_hex?: string;
+  address?: string;
+  scheme?: string;
+  kem_public_key_hex?: string;
+};
+export type Domain       = { tag: string; app_id: string };
+export type ImportQsKeyReq = { name: string; qskey: QsKeyFile; qskey_password: string; password?: string };
+export type ExportQsKeyReq = { password: string; export_password?: string };
+export type SignReq      = { password: string; message: string; domain: Domain; description?: string };
 export type SignRes      = { signed_hex: string };
-export type VerifyReq    = { signed_hex: string };
-export type VerifyRes    = { ok: boolean; message?: string };
+export type VerifyReq    = { signed_hex: string; domain: Domain };
+export type RevokedInfo  = { revoked_at: number; reason: string };
+export type VerifyRes    = { ok: boolean; message?: string; revoked?: RevokedInfo | null };
+export type TypedField   = { name: string; type: string };
+export type TypedData    = {
+  types: Record<string, TypedField[]>;
+  primaryType: string;
+  domain: Domain;
+  message: Record<string, unknown>;
+};
+export type SignTypedReq   = { password: string; typed_data: TypedData };
+export type SignTypedRes   = { hash_hex: string; signed_hex: string };
+export type VerifyTypedReq = { typed_data: TypedData; signed_hex: string };
+export type VerifyTypedRes = { ok: boolean; hash_hex: string; revoked?: RevokedInfo | null };
+export type VerifyAnyReq   = {
+  public_key_hex?: string;
+  address?: string;
+  scheme: string;
+  domain: Domain;
+  signed_hex: string;
+  message?: string;
+};
+export type VerifyAnyRes   = {
+  ok: boolean;
+  address: string;
+  message?: string;
+  revoked?: RevokedInfo | null;
+};
+export type RegistryEntry  = {
+  address: string;
+  scheme: string;
+  public_key_hex: string;
+  source: "import" | "first_seen";
+  added_at: number;
+  label?: string;
+};
+export type BatchItem      = {
+  public_key_hex?: string;
+  address?: string;
+  domain: Domain;
+  message: string;
+  signed_hex: string;
+};
+export type BatchRes       = { results: { ok: boolean; error?: string }[]; valid: number };
+export type RotationCert   = {
+  v: number;
+  old_scheme: string;
+  old_public_hex: string;
+  new_scheme: string;
+  new_public_hex: string;
+  issued_at: number;
+  reason?: string;
+  old_signed_hex: string;
+  new_signed_hex: string;
+};
+export type RotateRes      = { name: string; address: string; certificate: RotationCert };
+export type VerifyChainRes = { ok: boolean; current_public_key_hex: string; current_address: string };
+export type Revocation     = {
+  v: number;
+  scheme: string;
+  public_hex: string;
+  revoked_at: number;
+  reason: string;
+  signer_public_hex: string;
+  signed_hex: string;
+};
+export type RecoveryDesignation = {
+  v: number;
+  scheme: string;
+  public_hex: string;
+  recovery_public_hex: string;
+  issued_at: number;
+  signed_hex: string;
+};
+export type MultisigPolicy = { v: number; scheme: string; threshold: number; members_hex: string[] };
+export type MultisigBundle = {
+  policy_address: string;
+  domain: Domain;
+  message_hex: string;
+  signatures: { signer_public_hex: string; signed_hex: string }[];
+};
+export type StoredPolicy   = { address: string; policy: MultisigPolicy; label?: string; created_at: number };
+export type MultisigStatus = {
+  policy_address: string;
+  bundle_id: string;
+  threshold: number;
+  members: number;
+  signers: string[];
+  met: boolean;
+  bundle: MultisigBundle;
+};
+export type ApprovalState = "pending" | "approved" | "rejected" | "expired";
+export type SignRequest    = {
+  id: string;
+  wallet: string;
+  description: string;
+  domain: Domain;
+  message_hex: string;
+  created_at: number;
+  expires_at: number;
+  state: ApprovalState;
+  decided_at?: number | null;
+  signed_hex?: string | null;
+  reject_reason?: string | null;
+};
+export type ProtectionRes  = { protected: boolean; ttl_secs?: number | null };
+export type WalletPolicy = {
+  allowed_domains?: { tag: string; app_id?: string | null }[] | null;
+  max_signs_per_hour?: number | null;
+  /** By token (intent `token` or Solana mint); decimal strings at the token's decimals, e.g. "0.500000". */
+  bridge?: Record<string, { min_amount?: string | null; max_amount?: string | null }> | null;
+  allowed_destinations?: string[] | null;
+  /** UTC "HH:MM"; `days` are "mon".."sun", all days if empty. */
+  time_windows?: { days?: string[]; start: string; end: string }[] | null;
+};
+/** Body of the 403 returned when a policy refuses to sign. */
+export type PolicyDenial = { error: "policy_denied"; wallet: string; rule: string; reason: string };
+export type LoginChallenge = {
+  v: number;
+  domain: string;
+  uri: string;
+  statement?: string | null;
+  nonce: string;
+  issued_at: number;
+  expires_at: number;
+};
+export type SignedLogin = { challenge: LoginChallenge; address: string; public_key_hex: string; signed_hex: string };
+export type LoginOutcome = { address: string; public_key_hex: string; domain: string; uri: string };
+/**
+ * Exact token amount as a decimal string with every fractional digit, e.g.
+ * "1.500000" for 1.5 of a 6-decimal token. Never go through a JS number.
+ */
+export type Amount = string;
+export type BridgeIntent = {
+  v: number;
+  source: string;
+  dest_chain: string;
+  dest_address: string;
+  token: string;
+  amount: Amount;
+  nonce: string;
+  issued_at: number;
+  expires_at: number;
+};
+export type SignedIntent = { intent: BridgeIntent; public_key_hex: string; signed_hex: string };
+export type IntentRequest = Pick<BridgeIntent, "dest_chain" | "dest_address" | "token" | "amount"> & {
+  ttl_secs?: number;
+};
+/** Addresses are base58; `amount` must be at the mint's decimals. */
+export type SplTransfer = {
+  mint: string;
+  to: string;
+  amount: Amount;
+  token_program?: string;
+  create_recipient_account?: boolean;
+};
+export type SignedTransfer = {
+  from: string;
+  source_account: string;
+  destination_account: string;
+  signature: string;
+  transaction_base64: string;
+};
+export type Chain = "solana" | "evm";
+/** `token` is an SPL mint or ERC-20 contract; absent or null for SOL / ETH. */
+export type LinkRequest = { chain: Chain; address: string; token?: string | null; label?: string | null };
+export type LinkedAddress = Required<LinkRequest> & { added_at: number };
+/** Cached by qs-walletd; `error` set means the last refresh failed and `amount` is older. */
+export type AddressBalance = LinkedAddress & { amount: Amount | null; fetched_at: number | null; error: string | null };
+export type PasswordPolicy = { min_length: number; min_entropy_bits: number; reject_common: boolean };
+/** Body of the 400 returned when a new password breaks the rules. */
+export type WeakPassword = { error: "weak_password"; message: string; problems: string[] };
+export type ThrottleStatus = {
+  failures: number;
+  retry_after_secs: number;
+  locked: boolean;
+  lockout_after?: number | null;
+};
+export type AuditEntry = {
+  seq: number;
+  prev_hash: string;
+  event: { at: number; wallet?: string | null; action: string; outcome: string; detail: unknown };
+  hash: string;
+};
+export type AuditQuery = { wallet?: string; action?: string; after?: number; limit?: number };
+export type AuditReport = {
+  entries: number;
+  head_hash: string;
+  checkpoints: number;
+  signer_public_hex?: string | null;
+  last_checkpoint_seq?: number | null;
+  unsigned_tail: number;
+};
+export type BackupManifest = {
+  v: number;
+  created_at: number;
+  wallets: { name: string; address: string; scheme: string; watch_only: boolean }[];
+  registry_entries: number;
+  payload_sha3: string;
+};
+export type BackupArchive  = {
+  manifest: BackupManifest;
+  sealed: { kdf: string; salt_b64: string; nonce_b64: string; ct_b64: string };
+};
+export type RestoreReq     = {
+  archive: BackupArchive;
+  passphrase: string;
+  overwrite?: boolean;
+  passwords?: Record<string, string>;
+  /** Restore these wallets under a new password; their current one must be in `passwords`. */
+  new_passwords?: Record<string, string>;
+};
+export type RestoreRes     = { restored: string[]; manifest: BackupManifest };
 
 const BASE = process.env.NEXT_PUBLIC_QS_WALLETD_URL || "http://127.0.0.1:8787";
 
@@ -27,11 +260,45 @@ export const qsWalletd = {
       })
     );
   },
+  async listWallets() {
+    return check<WalletInfo[]>(await fetch(`${BASE}/wallets`));
+  },
+  async watchWallet(req: WatchReq) {
+    return check<WalletInfo>(
+      await fetch(`${BASE}/wallets/watch`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(req),
+      })
+    );
+  },
+  async importQsKey(req: ImportQsKeyReq) {
+    return check<WalletInfo>(
+      await fetch(`${BASE}/wallets/import/qskey`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(req),
+      })
+    );
+  },
+  async exportQsKey(name: string, req: ExportQsKeyReq) {
+    return check<QsKeyFile>(
+      await fetch(`${BASE}/wallets/${name}/export/qskey`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(req),
+      })
+    );
+  },
   async address(name: string) {
     return check<AddressRes>(await fetch(`${BASE}/wallets/${name}/address`));
   },
+  async publicKey(name: string) {
+    return check<PublicKeyRes>(await fetch(`${BASE}/wallets/${name}/public-key`));
+  },
+  /** Protected wallets answer 202 with a pending `SignRequest` instead of a signature. */
   async sign(name: string, req: SignReq) {
-    return check<SignRes>(
+    return check<SignRes | SignRequest>(
       await fetch(`${BASE}/wallets/${name}/sign`, {
         method: "POST",
         headers: { "content-type": "application/json" },
@@ -48,4 +315,398 @@ export const qsWalletd = {
       })
     );
   },
+  async verifyAny(req: VerifyAnyReq) {
+    return check<VerifyAnyRes>(
+      await fetch(`${BASE}/verify`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(req),
+      })
+    );
+  },
+  async verifyBatch(items: BatchItem[]) {
+    return check<BatchRes>(
+      await fetch(`${BASE}/verify/batch`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ items }),
+      })
+    );
+  },
+  async registry() {
+    return check<RegistryEntry[]>(await fetch(`${BASE}/registry`));
+  },
+  async registryLookup(address: string) {
+    return check<RegistryEntry>(await fetch(`${BASE}/registry/${address}`));
+  },
+  async registryImport(req: { public_key_hex: string; scheme?: string; label?: string }) {
+    return check<RegistryEntry>(
+      await fetch(`${BASE}/registry`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(req),
+      })
+    );
+  },
+  async rotate(name: string, password: string, reason?: string) {
+    return check<RotateRes>(
+      await fetch(`${BASE}/wallets/${name}/rotate`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ password, reason }),
+      })
+    );
+  },
+  async rotations(name: string) {
+    return check<RotationCert[]>(await fetch(`${BASE}/wallets/${name}/rotations`));
+  },
+  async verifyRotations(certificates: RotationCert[], from_public_key_hex?: string) {
+    return check<VerifyChainRes>(
+      await fetch(`${BASE}/rotations/verify`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ certificates, from_public_key_hex }),
+      })
+    );
+  },
+  async revocations() {
+    return check<Revocation[]>(await fetch(`${BASE}/revocations`));
+  },
+  async revocation(address: string) {
+    return check<Revocation>(await fetch(`${BASE}/revocations/${address}`));
+  },
+  async submitRevocation(revocation: Revocation) {
+    return check<Revocation>(
+      await fetch(`${BASE}/revocations`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(revocation),
+      })
+    );
+  },
+  async submitRecoveryKey(designation: RecoveryDesignation) {
+    const r = await fetch(`${BASE}/recovery-keys`, {
+      method: "POST",
+      headers: { "content-type": "application/json" },
+      body: JSON.stringify(designation),
+    });
+    if (!r.ok) throw new Error(await r.text().catch(() => r.statusText));
+  },
+  async revoke(name: string, req: { password: string; reason: string; target_public_key_hex?: string }) {
+    return check<Revocation>(
+      await fetch(`${BASE}/wallets/${name}/revoke`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(req),
+      })
+    );
+  },
+  async designateRecoveryKey(name: string, password: string, recovery_public_key_hex: string) {
+    return check<RecoveryDesignation>(
+      await fetch(`${BASE}/wallets/${name}/recovery-key`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ password, recovery_public_key_hex }),
+      })
+    );
+  },
+  async multisigPolicies() {
+    return check<StoredPolicy[]>(await fetch(`${BASE}/multisig/policies`));
+  },
+  async multisigPolicy(address: string) {
+    return check<StoredPolicy>(await fetch(`${BASE}/multisig/policies/${address}`));
+  },
+  async createMultisigPolicy(req: { threshold: number; members: string[]; label?: string }) {
+    return check<StoredPolicy>(
+      await fetch(`${BASE}/multisig/policies`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(req),
+      })
+    );
+  },
+  async multisigAddSignature(
+    address: string,
+    req: { public_key_hex?: string; address?: string; domain: Domain; message: string; signed_hex: string }
+  ) {
+    return check<MultisigStatus>(
+      await fetch(`${BASE}/multisig/policies/${address}/signatures`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(req),
+      })
+    );
+  },
+  async multisigSign(address: string, req: { wallet: string; password: string; domain: Domain; message: string }) {
+    return check<MultisigStatus>(
+      await fetch(`${BASE}/multisig/policies/${address}/sign`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(req),
+      })
+    );
+  },
+  async multisigStatus(address: string, domain: Domain, message: string) {
+    return check<MultisigStatus>(
+      await fetch(`${BASE}/multisig/policies/${address}/status`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ domain, message }),
+      })
+    );
+  },
+  async multisigCheck(policy: MultisigPolicy, bundle: MultisigBundle) {
+    return check<MultisigStatus>(
+      await fetch(`${BASE}/multisig/check`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ policy, bundle }),
+      })
+    );
+  },
+  async approvals(state?: ApprovalState) {
+    const q = state ? `?state=${state}` : "";
+    return check<SignRequest[]>(await fetch(`${BASE}/approvals${q}`));
+  },
+  async approval(id: string) {
+    return check<SignRequest>(await fetch(`${BASE}/approvals/${id}`));
+  },
+  /** `approverPassword` is the one set when protection was turned on, not the wallet password. */
+  async approve(id: string, approverPassword: string) {
+    return check<SignRequest>(
+      await fetch(`${BASE}/approvals/${id}/approve`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ approver_password: approverPassword }),
+      })
+    );
+  },
+  async reject(id: string, reason?: string) {
+    return check<SignRequest>(
+      await fetch(`${BASE}/approvals/${id}/reject`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ reason }),
+      })
+    );
+  },
+  /** Calls `onEvent` for every queued or decided request; returns a function that unsubscribes. */
+  subscribeApprovals(onEvent: (req: SignRequest) => void) {
+    const es = new EventSource(`${BASE}/approvals/events`);
+    es.addEventListener("sign_request", (e) => onEvent(JSON.parse((e as MessageEvent).data)));
+    return () => es.close();
+  },
+  async protection(name: string) {
+    return check<ProtectionRes>(await fetch(`${BASE}/wallets/${name}/approval`));
+  },
+  /** Turning protection on needs `password` (the wallet's); turning it off needs only `approver_password`. */
+  async setProtection(
+    name: string,
+    req: { password?: string; approver_password: string; enabled: boolean; ttl_secs?: number }
+  ) {
+    return check<ProtectionRes>(
+      await fetch(`${BASE}/wallets/${name}/approval`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(req),
+      })
+    );
+  },
+  async policy(name: string) {
+    return check<WalletPolicy | null>(await fetch(`${BASE}/wallets/${name}/policy`));
+  },
+  /** Pass `policy: null` to remove the wallet's policy. */
+  async setPolicy(name: string, password: string, policy: WalletPolicy | null) {
+    return check<WalletPolicy | null>(
+      await fetch(`${BASE}/wallets/${name}/policy`, {
+        method: "PUT",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ password, policy }),
+      })
+    );
+  },
+  /** Service side: issue a single-use challenge for `uri` on `domain`. */
+  async loginChallenge(req: { domain: string; uri: string; statement?: string; ttl_secs?: number }) {
+    return check<LoginChallenge>(
+      await fetch(`${BASE}/login/challenge`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(req),
+      })
+    );
+  },
+  /** Service side: check a signed answer; each challenge verifies once. */
+  async loginVerify(login: SignedLogin) {
+    return check<LoginOutcome>(
+      await fetch(`${BASE}/login/verify`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(login),
+      })
+    );
+  },
+  /** Wallet side: answer a service's challenge. */
+  async signIn(name: string, password: string, challenge: LoginChallenge) {
+    return check<SignedLogin>(
+      await fetch(`${BASE}/wallets/${name}/sign-in`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ password, challenge }),
+      })
+    );
+  },
+  async signBridgeIntent(name: string, password: string, req: IntentRequest) {
+    return check<SignedIntent>(
+      await fetch(`${BASE}/wallets/${name}/bridge-intent`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ password, ...req }),
+      })
+    );
+  },
+  /** Like verifyBridgeIntent, but leaves the nonce unused: call before preparing anything. */
+  async checkBridgeIntent(signed: SignedIntent) {
+    return check<{ intent: BridgeIntent; public_key_hex: string }>(
+      await fetch(`${BASE}/bridge-intents/check`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(signed),
+      })
+    );
+  },
+  /** Uses up the intent's nonce: call once, right before acting on it. */
+  async verifyBridgeIntent(signed: SignedIntent) {
+    return check<{ intent: BridgeIntent; public_key_hex: string }>(
+      await fetch(`${BASE}/bridge-intents/verify`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(signed),
+      })
+    );
+  },
+  async solanaAddress(name: string) {
+    return check<{ address: string }>(await fetch(`${BASE}/wallets/${name}/solana/address`));
+  },
+  /** Signed, not submitted: send `transaction_base64` with the RPC's sendTransaction. */
+  async signSplTransfer(name: string, password: string, recent_blockhash: string, transfer: SplTransfer) {
+    return check<SignedTransfer>(
+      await fetch(`${BASE}/wallets/${name}/solana/spl-transfer`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ password, recent_blockhash, ...transfer }),
+      })
+    );
+  },
+  async linkedAddresses(name: string) {
+    return check<LinkedAddress[]>(await fetch(`${BASE}/wallets/${name}/addresses`));
+  },
+  /** Takes the wallet's password. Linking an address again only updates its label. */
+  async linkAddress(name: string, password: string, req: LinkRequest) {
+    return check<LinkedAddress>(
+      await fetch(`${BASE}/wallets/${name}/addresses`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ password, ...req }),
+      })
+    );
+  },
+  async unlinkAddress(name: string, password: string, chain: Chain, address: string, token?: string) {
+    const query = token ? `?token=${encodeURIComponent(token)}` : "";
+    const res = await fetch(`${BASE}/wallets/${name}/addresses/${chain}/${address}${query}`, {
+      method: "DELETE",
+      headers: { "content-type": "application/json" },
+      body: JSON.stringify({ password }),
+    });
+    if (!res.ok) throw new Error(await res.text().catch(() => res.statusText));
+  },
+  /** Last balances fetched by the qs_wallet API (`/wallets/:name/balances` on :8080 refreshes them). */
+  async balances(name: string) {
+    return check<AddressBalance[]>(await fetch(`${BASE}/wallets/${name}/balances`));
+  },
+  async passwordPolicy() {
+    return check<PasswordPolicy>(await fetch(`${BASE}/password-policy`));
+  },
+  async checkPassword(password: string, name?: string) {
+    return check<{ ok: boolean; entropy_bits: number; problems: string[] }>(
+      await fetch(`${BASE}/password-policy/check`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ name, password }),
+      })
+    );
+  },
+  async changePassword(name: string, old_password: string, new_password: string) {
+    return check<{ changed: boolean }>(
+      await fetch(`${BASE}/wallets/${name}/password`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ old_password, new_password }),
+      })
+    );
+  },
+  async lockout(name: string) {
+    return check<ThrottleStatus>(await fetch(`${BASE}/wallets/${name}/lockout`));
+  },
+  /** Pass `lockout_after: null` to turn lockout off; only `qs-walletd reset-lockout` clears a locked wallet. */
+  async setLockout(name: string, password: string, lockout_after: number | null) {
+    return check<ThrottleStatus>(
+      await fetch(`${BASE}/wallets/${name}/lockout`, {
+        method: "PUT",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ password, lockout_after }),
+      })
+    );
+  },
+  async audit(query: AuditQuery = {}) {
+    const params = new URLSearchParams(
+      Object.entries(query).filter(([, v]) => v !== undefined).map(([k, v]) => [k, String(v)])
+    );
+    return check<AuditEntry[]>(await fetch(`${BASE}/audit?${params}`));
+  },
+  /** Pass the audit key you pinned earlier (hex) to check against it rather than the daemon's. */
+  async verifyAudit(publicKeyHex?: string) {
+    const params = publicKeyHex ? `?${new URLSearchParams({ public_key: publicKeyHex })}` : "";
+    return check<{ ok: boolean; report?: AuditReport | null; error?: string | null }>(
+      await fetch(`${BASE}/audit/verify${params}`)
+    );
+  },
+  async auditKey() {
+    return check<{ scheme: string; public_key_hex: string; address: string }>(await fetch(`${BASE}/audit/key`));
+  },
+  async backup(passphrase: string) {
+    return check<BackupArchive>(
+      await fetch(`${BASE}/backup`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify({ passphrase }),
+      })
+    );
+  },
+  async restore(req: RestoreReq) {
+    return check<RestoreRes>(
+      await fetch(`${BASE}/restore`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(req),
+      })
+    );
+  },
+  async signTyped(name: string, req: SignTypedReq) {
+    return check<SignTypedRes>(
+      await fetch(`${BASE}/wallets/${name}/sign-typed`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(req),
+      })
+    );
+  },
+  async verifyTyped(name: string, req: VerifyTypedReq) {
+    return check<VerifyTypedRes>(
+      await fetch(`${BASE}/wallets/${name}/verify-typed`, {
+        method: "POST",
+        headers: { "content-type": "application/json" },
+        body: JSON.stringify(req),
+      })
+    );
+  },
 };
@@ -4,7 +4,7 @@ import { decryptJSON, encryptJSON } from "./secure";
 
 export type QsKeyFile = {
   v: 1;
-  kemPub: string;
+  kemPub?: string; // absent in daemon exports of wallets without a KEM keypair
   dsaPub: string;
   enc: EncBlob; // contains both private keys
 };
@@ -21,8 +21,17 @@ export async function buildQsKey(
   return { v: 1, kemPub: kem.publicKey, dsaPub: dsa.publicKey, enc };
 }
 
+// The browser signs with ML-DSA-65 (FIPS 204). Keyfiles exported by qs-walletd
+// hold its round-3 Dilithium key instead; those belong back in the daemon.
+const BROWSER_DSA = "ML-DSA-65";
+
 export async function openQsKey(qs: QsKeyFile, pass: string) {
   const secret = await decryptJSON(qs.enc, pass);
+  const dsaScheme = String(secret.dsa?.scheme ?? "");
+  if (!dsaScheme.startsWith(BROWSER_DSA)) {
+    throw new Error(`this keyfile holds a ${dsaScheme || "unknown"} key, not ${BROWSER_DSA}; import it into qs-walletd instead`);
+  }
+  if (!secret.kem || qs.kemPub === undefined) throw new Error("this keyfile has no KEM keypair");
   return {
     kem: { scheme: secret.kem.scheme as string, publicKey: qs.kemPub, privateKey: secret.kem.privateKey as string },
     dsa: { scheme: secret.dsa.scheme as string, publicKey: qs.dsaPub, privateKey: secret.dsa.privateKey as string },
@@ -0,0 +1,287 @@
+// Balances of the addresses linked to each wallet, fetched from the chains.
+//
+// Links and the cache live in qs-core (`qs_core::balances`); this module is
+// the only place that asks a node. Solana balances come from `getBalance`
+// and `getTokenAccountsByOwner`, EVM ones from `eth_getBalance` and the
+// ERC-20 `balanceOf`/`decimals` calls, as `scripts/wallet/sol-balance.ts`
+// and `evm-balance.ts` do. A balance fetched less than QS_BALANCE_MAX_AGE_SECS
+// ago is fresh; a failed fetch keeps the last amount and is reported next to
+// it. The HTTP routes answer from the cache at once and refresh stale
+// balances in the background, one refresh per wallet at a time, so callers
+// cannot make the API wait on (or hammer) the nodes.
+use reqwest::Client;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{collections::HashSet, sync::{Arc, Mutex}, time::Duration};
+use tracing::warn;
+
+use qs_core::{
+    balances::{self, AddressBalance, Chain, LinkedAddress},
+    Amount, CoreError,
+};
+
+use crate::bridge::{DEFAULT_SOLANA_RPC_URL, SOLANA_RPC_URL_ENV};
+
+pub const EVM_RPC_URL_ENV: &str = "QS_EVM_RPC_URL";
+pub const DEFAULT_EVM_RPC_URL: &str = "http://127.0.0.1:8545";
+pub const MAX_AGE_ENV: &str = "QS_BALANCE_MAX_AGE_SECS";
+pub const DEFAULT_MAX_AGE_SECS: u64 = 30;
+
+const SOL_DECIMALS: u8 = 9;
+const ETH_DECIMALS: u8 = 18;
+// ERC-20 selectors: decimals() and balanceOf(address)
+const DECIMALS_SELECTOR: &str = "0x313ce567";
+const BALANCE_OF_SELECTOR: &str = "0x70a08231";
+
+pub struct BalanceService {
+    client: Client,
+    solana_rpc: String,
+    evm_rpc: String,
+    max_age_secs: u64,
+    /// Wallets with a background refresh under way.
+    refreshing: Mutex<HashSet<String>>,
+}
+
+/// `0x`-prefixed big-endian quantity, as EVM nodes return them, if it fits u128.
+fn evm_quantity(hex: &str) -> Result<u128, String> {
+    let digits = hex.strip_prefix("0x").ok_or_else(|| format!("`{hex}` is not a hex quantity"))?.trim_start_matches('0');
+    if digits.len() > 32 {
+        return Err(format!("{hex} does not fit 128 bits"));
+    }
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u128::from_str_radix(digits, 16).map_err(|_| format!("`{hex}` is not a hex quantity"))
+}
+
+impl BalanceService {
+    pub fn new(solana_rpc: impl Into<String>, evm_rpc: impl Into<String>, max_age_secs: u64) -> qs_core::Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(10))
+            .connect_timeout(Duration::from_secs(3))
+            .build()
+            .map_err(|e| CoreError::Io(e.to_string()))?;
+        Ok(BalanceService {
+            client,
+            solana_rpc: solana_rpc.into(),
+            evm_rpc: evm_rpc.into(),
+            max_age_secs,
+            refreshing: Mutex::default(),
+        })
+    }
+
+    /// Nodes at QS_SOLANA_RPC_URL and QS_EVM_RPC_URL, defaulting to local
+    /// ones, and the cache age from QS_BALANCE_MAX_AGE_SECS.
+    pub fn from_env() -> qs_core::Result<Self> {
+        let var = |k: &str, d: &str| std::env::var(k).unwrap_or_else(|_| d.to_string());
+        let max_age = std::env::var(MAX_AGE_ENV).ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_MAX_AGE_SECS);
+        BalanceService::new(var(SOLANA_RPC_URL_ENV, DEFAULT_SOLANA_RPC_URL), var(EVM_RPC_URL_ENV, DEFAULT_EVM_RPC_URL), max_age)
+    }
+
+    /// Wallet `name`'s balances, fetching those not fetched within the max age.
+    pub async fn refresh(&self, name: &str) -> qs_core::Result<Vec<AddressBalance>> {
+        for cached in balances::balances(name)? {
+            if cached.is_fresh(self.max_age_secs) { continue; }
+            let fetched = self.fetch(&cached.link).await;
+            balances::record(&cached.link, fetched)?;
+        }
+        balances::balances(name)
+    }
+
+    /// Wallet `name`'s cached balances, as they are now. If any is stale,
+    /// a background task refreshes them unless one already is.
+    pub fn cached(self: &Arc<Self>, name: &str) -> qs_core::Result<Vec<AddressBalance>> {
+        let cached = balances::balances(name)?;
+        if cached.iter().all(|b| b.is_fresh(self.max_age_secs)) {
+            return Ok(cached);
+        }
+        let mut refreshing = self.refreshing.lock().map_err(|e| CoreError::Io(e.to_string()))?;
+        if refreshing.insert(name.to_string()) {
+            let (service, name) = (self.clone(), name.to_string());
+            tokio::spawn(async move {
+                if let Err(e) = service.refresh(&name).await {
+                    warn!(wallet = %name, "balance refresh failed: {e}");
+                }
+                service.refreshing.lock().unwrap_or_else(|e| e.into_inner()).remove(&name);
+            });
+        }
+        Ok(cached)
+    }
+
+    pub async fn fetch(&self, link: &LinkedAddress) -> Result<Amount, String> {
+        match (link.chain, link.token.as_deref()) {
+            (Chain::Solana, None) => self.sol_balance(&link.address).await,
+            (Chain::Solana, Some(mint)) => self.spl_balance(&link.address, mint).await,
+            (Chain::Evm, None) => self.eth_balance(&link.address).await,
+            (Chain::Evm, Some(token)) => self.erc20_balance(&link.address, token).await,
+        }
+    }
+
+    async fn rpc<T: DeserializeOwned>(&self, chain: Chain, method: &str, params: Value) -> Result<T, String> {
+        let url = match chain { Chain::Solana => &self.solana_rpc, Chain::Evm => &self.evm_rpc };
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let res = self.client.post(url).json(&body).send().await.map_err(|e| match std::error::Error::source(&e) {
+            Some(cause) => format!("{chain} rpc: {e}: {cause}"),
+            None => format!("{chain} rpc: {e}"),
+        })?;
+        if !res.status().is_success() {
+            return Err(format!("{chain} rpc answered {}", res.status()));
+        }
+        let res: Value = res.json().await.map_err(|e| format!("{chain} rpc: {e}"))?;
+        if let Some(err) = res.get("error") {
+            return Err(format!("{chain} rpc error {}: {}", err["code"], err["message"].as_str().unwrap_or_default()));
+        }
+        serde_json::from_value(res["result"].clone()).map_err(|e| format!("{chain} rpc `{method}` result: {e}"))
+    }
+
+    async fn sol_balance(&self, address: &str) -> Result<Amount, String> {
+        #[derive(Deserialize)] struct Res { value: u64 }
+        let res: Res = self.rpc(Chain::Solana, "getBalance", json!([address, { "commitment": "confirmed" }])).await?;
+        Amount::from_units(res.value.into(), SOL_DECIMALS).map_err(|e| e.to_string())
+    }
+
+    /// Sum over every account of `mint` the owner has, not only the associated one.
+    async fn spl_balance(&self, owner: &str, mint: &str) -> Result<Amount, String> {
+        #[derive(Deserialize)] struct TokenAmount { amount: String, decimals: u8 }
+        #[derive(Deserialize)] struct Info { #[serde(rename = "tokenAmount")] token_amount: TokenAmount }
+        #[derive(Deserialize)] struct Parsed { info: Info }
+        #[derive(Deserialize)] struct Data { parsed: Parsed }
+        #[derive(Deserialize)] struct Account { data: Data }
+        #[derive(Deserialize)] struct Keyed { account: Account }
+        #[derive(Deserialize)] struct Res { value: Vec<Keyed> }
+        #[derive(Deserialize)] struct Supply { decimals: u8 }
+        #[derive(Deserialize)] struct SupplyRes { value: Supply }
+
+        let params = json!([owner, { "mint": mint }, { "encoding": "jsonParsed", "commitment": "confirmed" }]);
+        let res: Res = self.rpc(Chain::Solana, "getTokenAccountsByOwner", params).await?;
+        let decimals = match res.value.first() {
+            Some(keyed) => keyed.account.data.parsed.info.token_amount.decimals,
+            // no token account yet: zero, at the mint's decimals
+            None => self.rpc::<SupplyRes>(Chain::Solana, "getTokenSupply", json!([mint])).await?.value.decimals,
+        };
+        let mut total = Amount::zero(decimals).map_err(|e| e.to_string())?;
+        for keyed in res.value {
+            let token_amount = keyed.account.data.parsed.info.token_amount;
+            let units = token_amount.amount.parse().map_err(|_| format!("token amount `{}` is not an integer", token_amount.amount))?;
+            total = total.checked_add(Amount::from_units(units, token_amount.decimals).map_err(|e| e.to_string())?)
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(total)
+    }
+
+    async fn eth_balance(&self, address: &str) -> Result<Amount, String> {
+        let wei: String = self.rpc(Chain::Evm, "eth_getBalance", json!([address, "latest"])).await?;
+        Amount::from_units(evm_quantity(&wei)?, ETH_DECIMALS).map_err(|e| e.to_string())
+    }
+
+    async fn erc20_balance(&self, owner: &str, token: &str) -> Result<Amount, String> {
+        let call = |data: String| json!([{ "to": token, "data": data }, "latest"]);
+        let decimals: String = self.rpc(Chain::Evm, "eth_call", call(DECIMALS_SELECTOR.to_string())).await?;
+        let decimals = u8::try_from(evm_quantity(&decimals)?).map_err(|_| format!("{token}: decimals() out of range"))?;
+        // the owner as a 32-byte word; addresses are stored as 0x + 40 lower-case hex
+        let data = format!("{BALANCE_OF_SELECTOR}{:0>64}", owner.trim_start_matches("0x"));
+        let units: String = self.rpc(Chain::Evm, "eth_call", call(data)).await?;
+        Amount::from_units(evm_quantity(&units)?, decimals).map_err(|e| e.to_string())
+    }
+}
+
+/// `qs_wallet balances WALLET`: refreshes a wallet's balances and prints them as JSON.
+pub async fn cli(args: &[String]) -> qs_core::Result<()> {
+    let [wallet] = args else {
+        return Err(CoreError::Invalid("usage: qs_wallet balances WALLET".into()));
+    };
+    let balances = BalanceService::from_env()?.refresh(wallet).await?;
+    println!("{}", serde_json::to_string_pretty(&balances).map_err(|e| CoreError::Io(e.to_string()))?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use qs_core::testing::{self, PASSWORD};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::Notify;
+    use warp::Filter;
+
+    /// A wallet linked to `address`; each test uses its own, as the cache is shared by address.
+    fn wallet(prefix: &str, address: &str) -> String {
+        let name = testing::wallet(prefix);
+        balances::link(&name, PASSWORD, Chain::Solana, address, None, None).unwrap();
+        name
+    }
+
+    /// A Solana node answering `getBalance` with 1 SOL once `gate` is
+    /// notified, counting calls.
+    async fn node() -> (String, Arc<AtomicUsize>, Arc<Notify>) {
+        let (calls, gate) = (Arc::new(AtomicUsize::new(0)), Arc::new(Notify::new()));
+        let (counted, held) = (calls.clone(), gate.clone());
+        let routes = warp::post().and(warp::body::json()).then(move |body: Value| {
+            let (calls, gate) = (counted.clone(), held.clone());
+            async move {
+                assert_eq!(body["method"], "getBalance");
+                calls.fetch_add(1, Ordering::SeqCst);
+                gate.notified().await;
+                warp::reply::json(&json!({ "jsonrpc": "2.0", "id": 1, "result": { "value": 1_000_000_000u64 } }))
+            }
+        });
+        let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (format!("http://{addr}"), calls, gate)
+    }
+
+    /// Waits for the background refresh of `name` to finish.
+    async fn settle(service: &BalanceService, name: &str) {
+        let done = async {
+            while service.refreshing.lock().unwrap().contains(name) {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(10), done).await.expect("background refresh did not finish");
+    }
+
+    #[tokio::test]
+    async fn cached_answers_at_once_and_refreshes_in_the_background() {
+        let name = wallet("background", "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM");
+        let (url, calls, gate) = node().await;
+        let service = Arc::new(BalanceService::new(url, "http://127.0.0.1:1", 60).unwrap());
+
+        // the node holds every answer until the gate opens, so these return
+        // while the refresh is still waiting on it
+        let first = service.cached(&name).unwrap();
+        assert_eq!((first.len(), first[0].amount), (1, None));
+        assert!(service.refreshing.lock().unwrap().contains(&name));
+        // a second caller while the refresh runs does not start another
+        assert_eq!(service.cached(&name).unwrap()[0].amount, None);
+
+        gate.notify_one();
+        settle(&service, &name).await;
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let now = service.cached(&name).unwrap();
+        assert_eq!(now[0].amount, Some(Amount::from_units(1_000_000_000, SOL_DECIMALS).unwrap()));
+        // fresh: nothing to refresh
+        assert!(!service.refreshing.lock().unwrap().contains(&name));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn failed_refreshes_are_reported_with_the_cache() {
+        let name = wallet("unreachable", "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+        let service = Arc::new(BalanceService::new("http://127.0.0.1:1", "http://127.0.0.1:1", 60).unwrap());
+        assert_eq!(service.cached(&name).unwrap()[0].error, None);
+        settle(&service, &name).await;
+        let after = &service.cached(&name).unwrap()[0];
+        assert!(after.error.as_deref().is_some_and(|e| e.starts_with("solana rpc")), "{after:?}");
+        assert_eq!(after.amount, None);
+    }
+
+    #[test]
+    fn evm_quantities_fit_or_fail() {
+        assert_eq!(evm_quantity("0x0"), Ok(0));
+        assert_eq!(evm_quantity("0x"), Ok(0));
+        assert_eq!(evm_quantity("0xde0b6b3a7640000"), Ok(1_000_000_000_000_000_000));
+        assert_eq!(evm_quantity(&format!("0x{}", "0".repeat(40))), Ok(0));
+        assert!(evm_quantity(&format!("0x1{}", "0".repeat(32))).is_err());
+        assert!(evm_quantity("1234").is_err());
+        assert!(evm_quantity("0xzz").is_err());
+    }
+}
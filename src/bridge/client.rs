@@ -0,0 +1,304 @@
+// HTTP shared by the bridge's clients, and the client for the bridge service.
+//
+// Requests time out, and those safe to repeat are retried on connection
+// failures, timeouts, 429 and 5xx answers, waiting `initial_backoff`,
+// doubling up to `max_backoff`. A Retry-After is waited out in full; one
+// longer than `max_backoff` ends the retries instead of being cut short.
+// Non-2xx answers become `BridgeError::Status` with the service's message.
+use qs_core::Amount;
+use reqwest::{header::RETRY_AFTER, Client, Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+
+use super::BridgeError;
+
+pub const BRIDGE_URL_ENV: &str = "QS_BRIDGE_URL";
+pub const DEFAULT_BRIDGE_URL: &str = "http://127.0.0.1:3001";
+pub const TIMEOUT_ENV: &str = "QS_BRIDGE_TIMEOUT_MS";
+pub const MAX_RETRIES_ENV: &str = "QS_BRIDGE_MAX_RETRIES";
+
+#[derive(Clone, Debug)]
+pub struct HttpConfig {
+    /// Whole request, connecting included.
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    /// Retries after the first attempt, for requests that may be repeated.
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            timeout: Duration::from_secs(10),
+            connect_timeout: Duration::from_secs(3),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+        }
+    }
+}
+
+impl HttpConfig {
+    /// Defaults, with QS_BRIDGE_TIMEOUT_MS and QS_BRIDGE_MAX_RETRIES applied if set.
+    pub fn from_env() -> Self {
+        let d = HttpConfig::default();
+        let var = |k: &str| std::env::var(k).ok().and_then(|v| v.parse::<u64>().ok());
+        HttpConfig {
+            timeout: var(TIMEOUT_ENV).map_or(d.timeout, Duration::from_millis),
+            max_retries: var(MAX_RETRIES_ENV).and_then(|n| u32::try_from(n).ok()).unwrap_or(d.max_retries),
+            ..d
+        }
+    }
+
+    /// How long to wait before retrying, or None if the service asks for longer than we wait.
+    fn backoff(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        match retry_after {
+            Some(asked) => (asked <= self.max_backoff).then_some(asked),
+            None => Some(self.initial_backoff.saturating_mul(1 << attempt.min(16)).min(self.max_backoff)),
+        }
+    }
+}
+
+/// Whether a request may be sent again if the answer is lost or refused for now.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(super) enum Repeat { Safe, Never }
+
+pub(super) struct Http {
+    client: Client,
+    config: HttpConfig,
+}
+
+fn retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn retry_after(res: &Response) -> Option<Duration> {
+    res.headers().get(RETRY_AFTER)?.to_str().ok()?.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// The most useful line out of an error body: JSON `reason` or `error`, else the text.
+fn error_message(body: &str) -> String {
+    let json: Option<Value> = serde_json::from_str(body).ok();
+    json.as_ref()
+        .and_then(|v| v.get("reason").or_else(|| v.get("error")))
+        .and_then(Value::as_str)
+        .map_or_else(|| body.trim().to_string(), str::to_string)
+}
+
+impl Http {
+    pub(super) fn new(config: HttpConfig) -> Result<Self, BridgeError> {
+        let client = Client::builder().timeout(config.timeout).connect_timeout(config.connect_timeout).build()?;
+        Ok(Http { client, config })
+    }
+
+    /// POSTs `body` as JSON and decodes a 2xx answer as `T`. An idempotency
+    /// key, if given, is sent unchanged on every attempt.
+    pub(super) async fn post<T: DeserializeOwned>(
+        &self, service: &'static str, url: &str, body: &impl Serialize, idempotency_key: Option<&str>, repeat: Repeat,
+    ) -> Result<T, BridgeError> {
+        let retries = if repeat == Repeat::Safe { self.config.max_retries } else { 0 };
+        let mut attempt = 0;
+        loop {
+            let mut req = self.client.post(url).json(body);
+            if let Some(key) = idempotency_key {
+                req = req.header("idempotency-key", key);
+            }
+            let wait = match req.send().await {
+                Ok(res) if res.status().is_success() => {
+                    return res.json().await.map_err(|e| BridgeError::Unexpected { service, message: e.to_string() });
+                }
+                Ok(res) => {
+                    let wait = (attempt < retries && retryable(res.status()))
+                        .then(|| self.config.backoff(attempt, retry_after(&res)))
+                        .flatten();
+                    let Some(wait) = wait else {
+                        let status = res.status().as_u16();
+                        let message = error_message(&res.text().await.unwrap_or_default());
+                        return Err(BridgeError::Status { service, status, message });
+                    };
+                    wait
+                }
+                Err(e) if attempt < retries && (e.is_connect() || e.is_timeout()) => {
+                    self.config.backoff(attempt, None).unwrap_or(self.config.max_backoff)
+                }
+                Err(e) => return Err(e.into()),
+            };
+            tokio::time::sleep(wait).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// The bridge service's answer to an accepted intent.
+#[derive(Deserialize, Debug, Clone)]
+pub struct BridgeReceipt {
+    pub nonce: String,
+    pub dest_chain: String,
+    pub dest_address: String,
+    pub token: String,
+    pub amount: Amount,
+    /// The Solana transaction that carried it out.
+    pub signature: String,
+}
+
+/// Client for the bridge service (`bridge/index.ts`).
+pub struct BridgeClient {
+    http: Http,
+    base_url: String,
+}
+
+impl BridgeClient {
+    pub fn new(base_url: impl Into<String>, config: HttpConfig) -> Result<Self, BridgeError> {
+        Ok(BridgeClient { http: Http::new(config)?, base_url: base_url.into().trim_end_matches('/').to_string() })
+    }
+
+    /// Service at QS_BRIDGE_URL (default a local bridge) with [`HttpConfig::from_env`].
+    pub fn from_env() -> Result<Self, BridgeError> {
+        let url = std::env::var(BRIDGE_URL_ENV).unwrap_or_else(|_| DEFAULT_BRIDGE_URL.to_string());
+        BridgeClient::new(url, HttpConfig::from_env())
+    }
+
+    /// Submits a signed intent. Retries carry the intent's source and nonce
+    /// as idempotency key, so the service acts on it once however many
+    /// attempts reach it.
+    pub async fn submit(&self, signed_intent: &Value) -> Result<BridgeReceipt, BridgeError> {
+        let intent = &signed_intent["intent"];
+        let (Some(source), Some(nonce)) = (intent["source"].as_str(), intent["nonce"].as_str()) else {
+            return Err(BridgeError::Config("not a signed bridge intent (no intent.source / intent.nonce)".into()));
+        };
+        let key = format!("{source}:{nonce}");
+        let url = format!("{}/bridge", self.base_url);
+        self.http.post("bridge", &url, signed_intent, Some(&key), Repeat::Safe).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+    use warp::{http::Response as WarpResponse, Filter};
+
+    /// One scripted answer: status, Retry-After seconds, and a delay before answering.
+    type Answer = (u16, Option<u64>, u64);
+
+    /// Answers each POST with the next scripted answer (repeating the last)
+    /// and records the idempotency key each attempt carried.
+    #[derive(Clone)]
+    struct StandIn {
+        script: Arc<Mutex<Vec<Answer>>>,
+        keys: Arc<Mutex<Vec<Option<String>>>>,
+    }
+
+    impl StandIn {
+        fn new(script: &[Answer]) -> Self {
+            StandIn { script: Arc::new(Mutex::new(script.iter().rev().copied().collect())), keys: Arc::default() }
+        }
+
+        fn next(&self) -> Answer {
+            let mut script = self.script.lock().unwrap();
+            if script.len() > 1 { script.pop().unwrap() } else { script[0] }
+        }
+
+        fn attempts(&self) -> usize { self.keys.lock().unwrap().len() }
+
+        async fn url(&self) -> String {
+            let state = self.clone();
+            let routes = warp::post().and(warp::header::optional::<String>("idempotency-key")).then(move |key: Option<String>| {
+                let state = state.clone();
+                async move {
+                    state.keys.lock().unwrap().push(key);
+                    let (status, retry_after, delay) = state.next();
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    let mut res = WarpResponse::builder().status(status).header("content-type", "application/json");
+                    if let Some(secs) = retry_after {
+                        res = res.header("retry-after", secs.to_string());
+                    }
+                    let body = if status == 200 { json!({ "ok": true }) } else { json!({ "ok": false, "error": format!("answered {status}") }) };
+                    res.body(body.to_string()).unwrap()
+                }
+            });
+            let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+            tokio::spawn(server);
+            format!("http://{addr}/bridge")
+        }
+    }
+
+    fn http() -> Http {
+        Http::new(HttpConfig {
+            timeout: Duration::from_millis(200),
+            connect_timeout: Duration::from_millis(200),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_secs(1),
+        })
+        .unwrap()
+    }
+
+    async fn post(standin: &StandIn, repeat: Repeat) -> Result<Value, BridgeError> {
+        http().post("bridge", &standin.url().await, &json!({}), Some("QSsource:00ff"), repeat).await
+    }
+
+    #[test]
+    fn retry_after_is_honoured_or_ends_the_retries() {
+        let config = HttpConfig { initial_backoff: Duration::from_millis(100), max_backoff: Duration::from_secs(2), ..Default::default() };
+        assert_eq!(config.backoff(0, None), Some(Duration::from_millis(100)));
+        assert_eq!(config.backoff(2, None), Some(Duration::from_millis(400)));
+        assert_eq!(config.backoff(10, None), Some(Duration::from_secs(2)));
+        assert_eq!(config.backoff(0, Some(Duration::from_secs(2))), Some(Duration::from_secs(2)));
+        assert_eq!(config.backoff(0, Some(Duration::from_secs(3))), None);
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_and_throttling_with_the_same_key() {
+        let standin = StandIn::new(&[(500, None, 0), (429, Some(0), 0), (503, None, 0), (200, None, 0)]);
+        assert_eq!(post(&standin, Repeat::Safe).await.unwrap(), json!({ "ok": true }));
+        let keys = standin.keys.lock().unwrap().clone();
+        assert_eq!(keys, vec![Some("QSsource:00ff".to_string()); 4]);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_retries() {
+        let standin = StandIn::new(&[(502, None, 0)]);
+        let err = post(&standin, Repeat::Safe).await.unwrap_err();
+        assert!(matches!(&err, BridgeError::Status { status: 502, message, .. } if message == "answered 502"), "{err}");
+        assert_eq!(standin.attempts(), 4);
+    }
+
+    #[tokio::test]
+    async fn client_errors_and_unrepeatable_requests_are_not_retried() {
+        let standin = StandIn::new(&[(401, None, 0), (200, None, 0)]);
+        let err = post(&standin, Repeat::Safe).await.unwrap_err();
+        assert!(matches!(err, BridgeError::Status { status: 401, .. }), "{err}");
+        assert_eq!(standin.attempts(), 1);
+
+        let standin = StandIn::new(&[(500, None, 0), (200, None, 0)]);
+        assert!(matches!(post(&standin, Repeat::Never).await, Err(BridgeError::Status { status: 500, .. })));
+        assert_eq!(standin.attempts(), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_retry_after_exceeds_the_backoff_cap() {
+        let standin = StandIn::new(&[(429, Some(30), 0), (200, None, 0)]);
+        let started = std::time::Instant::now();
+        let err = post(&standin, Repeat::Safe).await.unwrap_err();
+        assert!(matches!(err, BridgeError::Status { status: 429, .. }), "{err}");
+        assert_eq!(standin.attempts(), 1);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn slow_answers_time_out_and_are_retried() {
+        let standin = StandIn::new(&[(200, None, 1000), (200, None, 0)]);
+        assert_eq!(post(&standin, Repeat::Safe).await.unwrap(), json!({ "ok": true }));
+        assert_eq!(standin.attempts(), 2);
+
+        let standin = StandIn::new(&[(200, None, 1000)]);
+        let err = post(&standin, Repeat::Safe).await.unwrap_err();
+        assert!(matches!(&err, BridgeError::Http(e) if e.is_timeout()), "{err}");
+        assert_eq!(standin.attempts(), 4);
+    }
+}
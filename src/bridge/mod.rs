@@ -0,0 +1,90 @@
+// The NovaTok bridge, Rust side.
+//
+// Users sign bridge intents with qs-walletd and hand them to the bridge
+// service with a [`BridgeClient`]; the operator carries out NOVA intents on
+// Solana with [`SolanaBridge`]. Every outgoing request goes through the same
+// HTTP layer (`client::Http`): timeouts, and retries with backoff where a
+// request is safe to repeat.
+use serde_json::Value;
+use std::fmt;
+
+mod client;
+mod solana;
+
+pub use client::BridgeClient;
+pub use solana::send_to_novatok;
+pub(crate) use solana::{DEFAULT_RPC_URL as DEFAULT_SOLANA_RPC_URL, RPC_URL_ENV as SOLANA_RPC_URL_ENV};
+
+/// Password of the sending wallet for `qs_wallet bridge-send`.
+pub const PASSWORD_ENV: &str = "QS_BRIDGE_PASSWORD";
+
+#[derive(Debug)]
+pub enum BridgeError {
+    Config(String),
+    /// No answer: connection failure or timeout, after any retries.
+    Http(reqwest::Error),
+    /// A non-2xx answer from `service` ("qs-walletd", "bridge", "solana-rpc").
+    Status { service: &'static str, status: u16, message: String },
+    /// The Solana node answered with a JSON-RPC error.
+    Rpc { code: i64, message: String },
+    /// A 2xx answer that is not what the API promises.
+    Unexpected { service: &'static str, message: String },
+    /// The intent was used up but its signed transaction could not be sent;
+    /// it can be sent again as is.
+    Unsubmitted { signature: String, transaction_base64: String, cause: Box<BridgeError> },
+}
+
+impl fmt::Display for BridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BridgeError::Config(msg) => write!(f, "bridge: {msg}"),
+            BridgeError::Http(e) if e.is_timeout() => write!(f, "bridge: {e}: timed out"),
+            BridgeError::Http(e) => match std::error::Error::source(e) {
+                Some(cause) => write!(f, "bridge: {e}: {cause}"),
+                None => write!(f, "bridge: {e}"),
+            },
+            BridgeError::Status { service, status, message } => write!(f, "bridge: {service} answered {status}: {message}"),
+            BridgeError::Rpc { code, message } => write!(f, "bridge: solana rpc error {code}: {message}"),
+            BridgeError::Unexpected { service, message } => write!(f, "bridge: unexpected answer from {service}: {message}"),
+            BridgeError::Unsubmitted { signature, transaction_base64, cause } => write!(
+                f,
+                "{cause}\nbridge: the intent is used up but transaction {signature} was not sent; \
+                 send it again before its blockhash expires:\n{transaction_base64}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BridgeError {}
+
+impl From<reqwest::Error> for BridgeError {
+    fn from(e: reqwest::Error) -> Self { BridgeError::Http(e) }
+}
+
+fn read_intent(path: &str) -> Result<Value, BridgeError> {
+    let read = std::fs::read(path).map_err(|e| BridgeError::Config(format!("{path}: {e}")))?;
+    serde_json::from_slice(&read).map_err(|e| BridgeError::Config(format!("{path}: {e}")))
+}
+
+/// `qs_wallet bridge-send WALLET INTENT.json`: carries out one signed intent from the command line.
+pub async fn send_cli(args: &[String]) -> Result<(), BridgeError> {
+    let [wallet, intent_path] = args else {
+        return Err(BridgeError::Config("usage: qs_wallet bridge-send WALLET INTENT.json".into()));
+    };
+    let signed_intent = read_intent(intent_path)?;
+    let password = std::env::var(PASSWORD_ENV).map_err(|_| BridgeError::Config(format!("{PASSWORD_ENV} is not set")))?;
+    let sent = send_to_novatok(wallet, &password, &signed_intent).await?;
+    println!("submitted {} (from {} to token account {})", sent.signature, sent.from, sent.destination_account);
+    Ok(())
+}
+
+/// `qs_wallet bridge-submit INTENT.json`: hands a signed intent to the bridge service.
+pub async fn submit_cli(args: &[String]) -> Result<(), BridgeError> {
+    let [intent_path] = args else {
+        return Err(BridgeError::Config("usage: qs_wallet bridge-submit INTENT.json".into()));
+    };
+    let receipt = BridgeClient::from_env()?.submit(&read_intent(intent_path)?).await?;
+    println!("accepted intent {}: {} of {} to {} on {} (transaction {})",
+        receipt.nonce, receipt.amount, receipt.token, receipt.dest_address, receipt.dest_chain, receipt.signature);
+    Ok(())
+}
@@ -0,0 +1,353 @@
+// Solana side of the NovaTok bridge: moves NOVA (an SPL token) from a wallet
+// held by qs-walletd to a Solana address, as asked by a signed bridge intent.
+//
+// qs-walletd verifies the intent (signature, expiry, single use) and builds
+// and signs the transfer; this module only fetches what it
+// needs from the chain (a recent blockhash, the mint's decimals) and submits
+// the signed transaction over JSON-RPC. Both endpoints are configurable, so
+// pointing QS_SOLANA_RPC_URL at `solana-test-validator` (the default) or any
+// stand-in speaking the same three RPC methods exercises the whole path.
+//
+// An intent is checked first (signature, expiry, not used yet) without using
+// it up, so a forged or stale one never gets the daemon wallet to sign, and
+// only then is the transfer signed. Verifying uses the intent up, so it
+// comes last: a failure before then leaves the intent usable. If submitting
+// fails afterwards, the error carries the signed transaction, which can be
+// sent again (the node ignores a second copy) until its blockhash expires.
+use qs_core::Amount;
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+
+use super::{client::{Http, HttpConfig, Repeat}, BridgeError};
+
+pub const RPC_URL_ENV: &str = "QS_SOLANA_RPC_URL";
+pub const WALLETD_URL_ENV: &str = "QS_WALLETD_URL";
+pub const NOVA_MINT_ENV: &str = "QS_NOVA_MINT";
+pub const DEFAULT_RPC_URL: &str = "http://127.0.0.1:8899";
+pub const DEFAULT_WALLETD_URL: &str = "http://127.0.0.1:8787";
+
+/// What the bridge needs to know about a submitted transfer.
+#[derive(Debug)]
+pub struct Submitted {
+    pub signature: String,
+    pub from: String,
+    pub destination_account: String,
+}
+
+/// A transfer signed by qs-walletd, not yet sent.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SignedTransfer {
+    pub signature: String,
+    pub from: String,
+    pub destination_account: String,
+    pub transaction_base64: String,
+}
+
+/// The fields of an intent the Solana side acts on.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+pub struct VerifiedIntent {
+    pub dest_chain: String,
+    pub dest_address: String,
+    pub token: String,
+    pub amount: Amount,
+}
+
+pub struct SolanaBridge {
+    http: Http,
+    rpc_url: String,
+    walletd_url: String,
+}
+
+impl SolanaBridge {
+    pub fn new(rpc_url: impl Into<String>, walletd_url: impl Into<String>, config: HttpConfig) -> Result<Self, BridgeError> {
+        Ok(SolanaBridge { http: Http::new(config)?, rpc_url: rpc_url.into(), walletd_url: walletd_url.into() })
+    }
+
+    /// Endpoints from QS_SOLANA_RPC_URL and QS_WALLETD_URL, defaulting to a
+    /// local validator and daemon, with [`HttpConfig::from_env`].
+    pub fn from_env() -> Result<Self, BridgeError> {
+        let var = |k: &str, d: &str| std::env::var(k).unwrap_or_else(|_| d.to_string());
+        SolanaBridge::new(var(RPC_URL_ENV, DEFAULT_RPC_URL), var(WALLETD_URL_ENV, DEFAULT_WALLETD_URL), HttpConfig::from_env())
+    }
+
+    /// Every method used here may be repeated: reads are harmless and
+    /// sendTransaction is deduplicated by signature.
+    async fn rpc<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, BridgeError> {
+        let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        let res: Value = self.http.post("solana-rpc", &self.rpc_url, &body, None, Repeat::Safe).await?;
+        if let Some(err) = res.get("error") {
+            return Err(BridgeError::Rpc {
+                code: err["code"].as_i64().unwrap_or_default(),
+                message: err["message"].as_str().unwrap_or_default().to_string(),
+            });
+        }
+        serde_json::from_value(res["result"].clone())
+            .map_err(|e| BridgeError::Unexpected { service: "solana-rpc", message: format!("`{method}` result: {e}") })
+    }
+
+    pub async fn latest_blockhash(&self) -> Result<String, BridgeError> {
+        #[derive(Deserialize)] struct Hash { blockhash: String }
+        #[derive(Deserialize)] struct Res { value: Hash }
+        let res: Res = self.rpc("getLatestBlockhash", json!([{ "commitment": "confirmed" }])).await?;
+        Ok(res.value.blockhash)
+    }
+
+    pub async fn mint_decimals(&self, mint: &str) -> Result<u8, BridgeError> {
+        #[derive(Deserialize)] struct Supply { decimals: u8 }
+        #[derive(Deserialize)] struct Res { value: Supply }
+        let res: Res = self.rpc("getTokenSupply", json!([mint])).await?;
+        Ok(res.value.decimals)
+    }
+
+    /// Has qs-walletd check `signed_intent` (a `SignedIntent` as produced by
+    /// `POST /wallets/:name/bridge-intent`) without using up its nonce.
+    pub async fn check_intent(&self, signed_intent: &Value) -> Result<VerifiedIntent, BridgeError> {
+        #[derive(Deserialize)] struct Res { intent: VerifiedIntent }
+        let url = format!("{}/bridge-intents/check", self.walletd_url);
+        let res: Res = self.http.post("qs-walletd", &url, signed_intent, None, Repeat::Safe).await?;
+        Ok(res.intent)
+    }
+
+    /// Has qs-walletd verify `signed_intent`, using up its nonce.
+    pub async fn verify_intent(&self, signed_intent: &Value) -> Result<VerifiedIntent, BridgeError> {
+        #[derive(Deserialize)] struct Res { intent: VerifiedIntent }
+        let url = format!("{}/bridge-intents/verify", self.walletd_url);
+        let res: Res = self.http.post("qs-walletd", &url, signed_intent, None, Repeat::Never).await?;
+        Ok(res.intent)
+    }
+
+    /// Has daemon wallet `wallet` sign a transfer of `amount` (at the mint's
+    /// decimals) of `mint` to the owner `to`, opening `to`'s token account if
+    /// needed. Nothing is sent.
+    pub async fn sign_transfer(&self, wallet: &str, password: &str, mint: &str, to: &str, amount: Amount) -> Result<SignedTransfer, BridgeError> {
+        let recent_blockhash = self.latest_blockhash().await?;
+        // not repeated: each signature counts against the wallet's policy
+        let url = format!("{}/wallets/{wallet}/solana/spl-transfer", self.walletd_url);
+        let body = json!({
+            "password": password,
+            "recent_blockhash": recent_blockhash,
+            "mint": mint,
+            "to": to,
+            "amount": amount,
+            "create_recipient_account": true,
+        });
+        self.http.post("qs-walletd", &url, &body, None, Repeat::Never).await
+    }
+
+    /// Sends a signed transfer. Returns once the node has accepted the
+    /// transaction, not when it is confirmed.
+    pub async fn submit(&self, signed: &SignedTransfer) -> Result<Submitted, BridgeError> {
+        let signature: String = self.rpc("sendTransaction", json!([
+            signed.transaction_base64,
+            { "encoding": "base64", "preflightCommitment": "confirmed" },
+        ])).await?;
+        if signature != signed.signature {
+            return Err(BridgeError::Unexpected {
+                service: "solana-rpc",
+                message: format!("node reported transaction {signature}, expected {}", signed.signature),
+            });
+        }
+        Ok(Submitted { signature, from: signed.from.clone(), destination_account: signed.destination_account.clone() })
+    }
+
+    /// Carries out `signed_intent` for `mint`, paying from daemon wallet
+    /// `wallet`. The intent is checked before the transfer is signed and used
+    /// up only after. Its amount must be written at the mint's decimals
+    /// ("1.500000" for 6, not "1.5"), so the signature covers them.
+    pub async fn carry_out(&self, wallet: &str, password: &str, mint: &str, signed_intent: &Value) -> Result<Submitted, BridgeError> {
+        let claimed = self.check_intent(signed_intent).await?;
+        if claimed.dest_chain != "solana" || claimed.token != mint {
+            return Err(BridgeError::Config(format!("intent is for {} on {}, not {mint} on solana", claimed.token, claimed.dest_chain)));
+        }
+        let decimals = self.mint_decimals(mint).await?;
+        if claimed.amount.decimals() != decimals {
+            return Err(BridgeError::Config(format!("intent amount {} must have exactly {decimals} decimals for this mint", claimed.amount)));
+        }
+        let signed = self.sign_transfer(wallet, password, mint, &claimed.dest_address, claimed.amount).await?;
+
+        let intent = self.verify_intent(signed_intent).await?;
+        if intent != claimed {
+            return Err(BridgeError::Unexpected {
+                service: "qs-walletd",
+                message: format!("verified intent {intent:?} differs from the one submitted"),
+            });
+        }
+        self.submit(&signed).await.map_err(|cause| BridgeError::Unsubmitted {
+            signature: signed.signature.clone(),
+            transaction_base64: signed.transaction_base64.clone(),
+            cause: Box::new(cause),
+        })
+    }
+}
+
+/// Carries out a signed intent for NOVA (mint from QS_NOVA_MINT) on Solana,
+/// paying from daemon wallet `wallet`.
+pub async fn send_to_novatok(wallet: &str, password: &str, signed_intent: &Value) -> Result<Submitted, BridgeError> {
+    let mint = std::env::var(NOVA_MINT_ENV).map_err(|_| BridgeError::Config(format!("{NOVA_MINT_ENV} is not set")))?;
+    SolanaBridge::from_env()?.carry_out(wallet, password, &mint, signed_intent).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use warp::{http::StatusCode, path::FullPath, Filter};
+
+    const MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const OWNER: &str = "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM";
+    const SIGNATURE: &str = "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW";
+
+    /// Answers like a local validator (the three RPC methods used) and
+    /// qs-walletd (checking, signing and verifying), recording every call in order.
+    #[derive(Clone, Default)]
+    struct StandIn {
+        calls: Arc<Mutex<Vec<String>>>,
+        /// Whether walletd finds the intent forged, expired or used.
+        reject_intent: bool,
+        refuse_signing: bool,
+        fail_sending: bool,
+        /// Verified amount, if walletd should report a different intent.
+        verified_amount: Option<&'static str>,
+    }
+
+    impl StandIn {
+        fn answer(&self, path: &str, body: Value) -> (StatusCode, Value) {
+            let call = if path == "/rpc" { body["method"].as_str().unwrap_or_default().to_string() } else { path.to_string() };
+            self.calls.lock().unwrap().push(call.clone());
+            match call.as_str() {
+                "getTokenSupply" => (StatusCode::OK, json!({ "result": { "value": { "decimals": 6 } } })),
+                "getLatestBlockhash" => (StatusCode::OK, json!({ "result": { "value": { "blockhash": "11111111111111111111111111111111" } } })),
+                "sendTransaction" if self.fail_sending => {
+                    (StatusCode::OK, json!({ "error": { "code": -32002, "message": "Blockhash not found" } }))
+                }
+                "sendTransaction" => (StatusCode::OK, json!({ "result": SIGNATURE })),
+                "/wallets/payer/solana/spl-transfer" if self.refuse_signing => {
+                    (StatusCode::FORBIDDEN, json!({ "error": "policy_denied", "reason": "amount is outside the allowed bridge range" }))
+                }
+                "/wallets/payer/solana/spl-transfer" => {
+                    assert_eq!(body["amount"], "1.500000");
+                    (StatusCode::OK, json!({
+                        "signature": SIGNATURE,
+                        "from": "US517G5965aydkZ46HS38QLi7UQiSojurfbQfKCELFx",
+                        "destination_account": "FGETo8T8wMcN2wCjav8VK6eh3dLk63evNDPxzLSJra8B",
+                        "transaction_base64": "AQID",
+                    }))
+                }
+                "/bridge-intents/check" if self.reject_intent || !body["intent"].is_object() => {
+                    (StatusCode::UNAUTHORIZED, json!("bridge intent: bad signature"))
+                }
+                "/bridge-intents/check" => (StatusCode::OK, json!({ "intent": body["intent"], "public_key_hex": "00" })),
+                "/bridge-intents/verify" => {
+                    let mut intent = body["intent"].clone();
+                    if let Some(amount) = self.verified_amount {
+                        intent["amount"] = json!(amount);
+                    }
+                    (StatusCode::OK, json!({ "intent": intent, "public_key_hex": "00" }))
+                }
+                _ => (StatusCode::NOT_FOUND, json!({ "error": format!("no route {call}") })),
+            }
+        }
+
+        fn calls(&self) -> Vec<String> { self.calls.lock().unwrap().clone() }
+
+        async fn bridge(&self) -> SolanaBridge {
+            let state = self.clone();
+            let routes = warp::post().and(warp::path::full()).and(warp::body::json()).map(move |path: FullPath, body: Value| {
+                let (status, body) = state.answer(path.as_str(), body);
+                warp::reply::with_status(warp::reply::json(&body), status)
+            });
+            let (addr, server) = warp::serve(routes).bind_ephemeral(([127, 0, 0, 1], 0));
+            tokio::spawn(server);
+            SolanaBridge::new(format!("http://{addr}/rpc"), format!("http://{addr}"), HttpConfig::default()).unwrap()
+        }
+    }
+
+    fn signed_intent(chain: &str, token: &str) -> Value {
+        json!({
+            "intent": {
+                "v": 2,
+                "source": "QSsource",
+                "dest_chain": chain,
+                "dest_address": OWNER,
+                "token": token,
+                "amount": "1.500000",
+                "nonce": "00112233445566778899aabbccddeeff",
+                "issued_at": 1,
+                "expires_at": 2,
+            },
+            "public_key_hex": "00",
+            "signed_hex": "00",
+        })
+    }
+
+    #[tokio::test]
+    async fn intents_are_used_up_only_once_the_transfer_is_signed() {
+        let standin = StandIn::default();
+        let sent = standin.bridge().await.carry_out("payer", "pw", MINT, &signed_intent("solana", MINT)).await.unwrap();
+        assert_eq!(sent.signature, SIGNATURE);
+        assert_eq!(sent.destination_account, "FGETo8T8wMcN2wCjav8VK6eh3dLk63evNDPxzLSJra8B");
+        assert_eq!(standin.calls(), [
+            "/bridge-intents/check",
+            "getTokenSupply",
+            "getLatestBlockhash",
+            "/wallets/payer/solana/spl-transfer",
+            "/bridge-intents/verify",
+            "sendTransaction",
+        ]);
+    }
+
+    #[tokio::test]
+    async fn refused_transfers_leave_the_intent_usable() {
+        let standin = StandIn { refuse_signing: true, ..Default::default() };
+        let err = standin.bridge().await.carry_out("payer", "pw", MINT, &signed_intent("solana", MINT)).await.unwrap_err();
+        assert!(matches!(err, BridgeError::Status { status: 403, .. }), "{err}");
+        assert!(!standin.calls().iter().any(|c| c == "/bridge-intents/verify"));
+    }
+
+    #[tokio::test]
+    async fn unsent_transactions_come_back_with_the_error() {
+        let standin = StandIn { fail_sending: true, ..Default::default() };
+        let err = standin.bridge().await.carry_out("payer", "pw", MINT, &signed_intent("solana", MINT)).await.unwrap_err();
+        let BridgeError::Unsubmitted { signature, transaction_base64, cause } = &err else { panic!("{err}") };
+        assert_eq!((signature.as_str(), transaction_base64.as_str()), (SIGNATURE, "AQID"));
+        assert!(matches!(**cause, BridgeError::Rpc { code: -32002, .. }));
+        assert!(err.to_string().contains("AQID"));
+    }
+
+    #[tokio::test]
+    async fn nothing_is_sent_for_other_intents() {
+        let standin = StandIn { verified_amount: Some("2.5"), ..Default::default() };
+        let bridge = standin.bridge().await;
+        let err = bridge.carry_out("payer", "pw", MINT, &signed_intent("solana", MINT)).await.unwrap_err();
+        assert!(matches!(err, BridgeError::Unexpected { service: "qs-walletd", .. }), "{err}");
+        assert!(!standin.calls().iter().any(|c| c == "sendTransaction"));
+
+        let standin = StandIn::default();
+        let bridge = standin.bridge().await;
+        assert!(matches!(bridge.carry_out("payer", "pw", MINT, &signed_intent("ethereum", MINT)).await, Err(BridgeError::Config(_))));
+        assert!(matches!(bridge.carry_out("payer", "pw", MINT, &signed_intent("solana", OWNER)).await, Err(BridgeError::Config(_))));
+        assert!(matches!(bridge.carry_out("payer", "pw", MINT, &json!({ "intent": 1 })).await, Err(BridgeError::Status { status: 401, .. })));
+        assert!(standin.calls().iter().all(|c| c == "/bridge-intents/check"), "{:?}", standin.calls());
+    }
+
+    #[tokio::test]
+    async fn bad_intents_never_reach_the_signer() {
+        let standin = StandIn { reject_intent: true, ..Default::default() };
+        let err = standin.bridge().await.carry_out("payer", "pw", MINT, &signed_intent("solana", MINT)).await.unwrap_err();
+        assert!(matches!(&err, BridgeError::Status { status: 401, message, .. } if message.contains("bad signature")), "{err}");
+        assert_eq!(standin.calls(), ["/bridge-intents/check"]);
+    }
+
+    #[tokio::test]
+    async fn intent_amounts_must_be_at_the_mints_decimals() {
+        let standin = StandIn::default();
+        let bridge = standin.bridge().await;
+        for amount in ["1.5", "1.50", "1.5000000"] {
+            let mut intent = signed_intent("solana", MINT);
+            intent["intent"]["amount"] = json!(amount);
+            let err = bridge.carry_out("payer", "pw", MINT, &intent).await.unwrap_err();
+            assert!(matches!(&err, BridgeError::Config(message) if message.contains("exactly 6 decimals")), "{err}");
+        }
+        assert!(standin.calls().iter().all(|c| c == "/bridge-intents/check" || c == "getTokenSupply"), "{:?}", standin.calls());
+    }
+}
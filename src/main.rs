@@ -1,85 +1,72 @@
-use pqcrypto_dilithium::dilithium5::*;
-use serde::{Serialize, Deserialize};
-use sled;
-use std::fs;
-use std::path::Path;
-use warp::{Filter, http::Method};
-use base64;
-use tracing::{info, Level};
+use warp::{Filter, Reply, http::{header, HeaderValue, Method, StatusCode}};
+use serde::Serialize;
+use tracing::info;
 use tracing_subscriber::EnvFilter;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct Wallet {
-    public_key: String,
-    secret_key: String,
-    balance: f64,
-}
-
-fn init_wallet_dir() {
-    let dir = Path::new("./wallet_data");
-    if !dir.exists() {
-        fs::create_dir_all(dir).unwrap();
-    }
-}
-
-fn create_wallet() -> Wallet {
-    let (pk, sk) = keypair();
-    Wallet {
-        public_key: base64::encode(pk.as_bytes()),
-        secret_key: base64::encode(sk.as_bytes()),
-        balance: 0.0,
-    }
-}
-
-fn save_wallet(wallet: &Wallet) {
-    let db = sled::open("wallet_data/db").unwrap();
-    db.insert("wallet", serde_json::to_vec(wallet).unwrap()).unwrap();
-    db.flush().unwrap();
-}
-
-fn load_wallet() -> Wallet {
-    let db = sled::open("wallet_data/db").unwrap();
-    let bytes = db.get("wallet").unwrap().expect("wallet not found");
-    serde_json::from_slice(&bytes).unwrap()
-}
-
-fn ensure_wallet() -> Wallet {
-    let db = sled::open("wallet_data/db").unwrap();
-    if db.get("wallet").unwrap().is_none() {
-        let w = create_wallet();
-        save_wallet(&w);
-        return w;
-    }
-    load_wallet()
-}
-
-fn sign_message(msg: &str, wallet: &Wallet) -> String {
-    let sk_bytes = base64::decode(&wallet.secret_key).unwrap();
-    let sk = SecretKey::from_bytes(&sk_bytes).unwrap();
-    let signed = sign(msg.as_bytes(), &sk);
-    base64::encode(signed)
-}
-
-fn verify_message(msg: &str, sig_b64: &str, wallet: &Wallet) -> bool {
-    let pk_bytes = base64::decode(&wallet.public_key).unwrap();
-    let pk = PublicKey::from_bytes(&pk_bytes).unwrap();
-    let sig = base64::decode(sig_b64).unwrap();
-    let verify_res = open(&sig, &pk);
-    match verify_res {
-        Ok(plain) => plain == msg.as_bytes(),
-        Err(_) => false,
+use std::sync::Arc;
+
+use qs_core::{legacy, CoreError};
+
+use balances::BalanceService;
+
+mod balances;
+mod bridge;
+
+// Thin front-end kept for deployments still pointed at :8080. All wallet
+// logic lives in qs-core; qs-walletd serves the same routes on :8787, and
+// neither serves the old password-less `/sign/:msg` (see `qs_core::legacy`).
+// Unlike the daemon it talks to chains, so balances are refreshed here:
+// in the background, while the routes answer from the cache.
+fn reply<T: Serialize>(res: Result<T, CoreError>) -> warp::reply::Response {
+    match res {
+        Ok(body) => warp::reply::with_status(warp::reply::json(&body), StatusCode::OK).into_response(),
+        Err(e) => {
+            let retry_after = match &e {
+                CoreError::Throttled { retry_after_secs, .. } => Some((*retry_after_secs).max(1)),
+                _ => None,
+            };
+            let status = match e {
+                CoreError::NotFound(_) | CoreError::UnknownAddress(_) | CoreError::UnknownRequest(_) => StatusCode::NOT_FOUND,
+                CoreError::Exists(_)   => StatusCode::CONFLICT,
+                CoreError::WatchOnly(_) | CoreError::ApprovalRequired(_) | CoreError::PolicyDenied(_) => StatusCode::FORBIDDEN,
+                CoreError::BadPassword | CoreError::LoginRejected(_) | CoreError::IntentRejected(_) => StatusCode::UNAUTHORIZED,
+                CoreError::Throttled { .. } => StatusCode::TOO_MANY_REQUESTS,
+                CoreError::LockedOut(_) => StatusCode::LOCKED,
+                CoreError::Tampered(_) => StatusCode::INTERNAL_SERVER_ERROR,
+                CoreError::Invalid(_) | CoreError::WeakPassword(_) => StatusCode::BAD_REQUEST,
+                CoreError::Io(_)       => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            let mut res = warp::reply::with_status(warp::reply::json(&serde_json::json!({ "error": e.to_string() })), status).into_response();
+            if let Some(secs) = retry_after {
+                res.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(secs));
+            }
+            res
+        }
     }
 }
 
 #[tokio::main]
 async fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command: Option<Result<(), Box<dyn std::error::Error>>> = match args.first().map(String::as_str) {
+        Some("bridge-send")   => Some(bridge::send_cli(&args[1..]).await.map_err(Into::into)),
+        Some("bridge-submit") => Some(bridge::submit_cli(&args[1..]).await.map_err(Into::into)),
+        Some("balances")      => Some(balances::cli(&args[1..]).await.map_err(Into::into)),
+        _ => None,
+    };
+    if let Some(result) = command {
+        if let Err(e) = result {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // Logging
     let filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new("info"));
     tracing_subscriber::fmt().with_env_filter(filter).init();
 
-    init_wallet_dir();
-    let wallet = ensure_wallet();
     info!("QuantumShield API starting");
 
     // CORS
@@ -88,69 +75,51 @@ async fn main() {
         .allow_methods(&[Method::GET])
         .allow_headers(vec!["content-type"]);
 
+    let service = match BalanceService::from_env() {
+        Ok(service) => Arc::new(service),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+    let with_service = warp::any().map(move || service.clone());
+
     // Routes
-    let get_balance = warp::path("balance").map(move || {
-        let w = load_wallet();
-        warp::reply::json(&serde_json::json!({
-            "public_key": w.public_key,
-            "balance": w.balance
-        }))
+    let get_balance = warp::path("balance").and(with_service.clone()).map(|service: Arc<BalanceService>| {
+        let cached = || {
+            legacy::ensure_default()?;
+            legacy::balance_of(service.cached(legacy::DEFAULT_WALLET)?)
+        };
+        reply(cached())
     });
 
-    let sign = warp::path!("sign" / String).map(move |msg| {
-        let w = load_wallet();
-        let sig = sign_message(&msg, &w);
-        warp::reply::json(&serde_json::json!({ "message": msg, "signature": sig }))
-    });
+    let wallet_balances = warp::path!("wallets" / String / "balances").and(with_service)
+        .map(|name: String, service: Arc<BalanceService>| reply(service.cached(&name)));
 
-    let verify = warp::path!("verify" / String / String).map(move |msg, sig| {
-        let w = load_wallet();
-        let ok = verify_message(&msg, &sig, &w);
-        warp::reply::json(&serde_json::json!({ "verified": ok }))
-    });
+    let verify = warp::path!("verify" / String / String)
+        .map(|msg: String, sig: String| reply(legacy::verify(&msg, &sig)));
 
-    let routes = get_balance.or(sign).or(verify).with(cors);
+    let generate = warp::path("generate").map(|| warp::reply::json(&legacy::generate()));
+
+    let routes = get_balance.or(wallet_balances).or(verify).or(generate).with(cors);
 
     info!("API on http://127.0.0.1:8080");
     warp::serve(routes).run(([127,0,0,1], 8080)).await;
 }
-use pqcrypto_dilithium::dilithium5::*;
-use serde::{Serialize, Deserialize};
-use sled;
-use std::fs;
-use std::path::Path;
-
-#[derive(Serialize, Deserialize, Debug)]
-struct Wallet {
-    public_key: String,
-    secret_key: String,
-}
 
-fn init_wallet_dir() {
-    let dir = Path::new("./wallet_data");
-    if !dir.exists() {
-        fs::create_dir_all(dir).unwrap();
-    }
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn create_wallet() -> Wallet {
-    let (pk, sk) = keypair();
-    Wallet {
-        public_key: base64::encode(pk.as_bytes()),
-        secret_key: base64::encode(sk.as_bytes()),
-    }
-}
+    #[test]
+    fn throttled_replies_say_when_to_retry() {
+        let res = reply::<()>(Err(CoreError::Throttled { wallet: "w".into(), retry_after_secs: 30 }));
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()[header::RETRY_AFTER], "30");
 
-fn save_wallet(wallet: &Wallet) {
-    let db = sled::open("wallet_data/db").unwrap();
-    db.insert("wallet", serde_json::to_vec(wallet).unwrap()).unwrap();
-    db.flush().unwrap();
-    println!("✅ Wallet created and saved successfully!");
-    println!("Public Key: {}", wallet.public_key);
-}
-
-fn main() {
-    init_wallet_dir();
-    let wallet = create_wallet();
-    save_wallet(&wallet);
+        let res = reply::<()>(Err(CoreError::BadPassword));
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert!(!res.headers().contains_key(header::RETRY_AFTER));
+        assert_eq!(reply(Ok(1)).status(), StatusCode::OK);
+    }
 }